@@ -27,7 +27,7 @@ use crate::{
     mailer::{EmailSender, MailerWorker},
     prelude::BackgroundWorker,
     scheduler::{self, Scheduler},
-    storage::{self, Storage},
+    storage,
     task::{self, Tasks},
     Result,
 };
@@ -258,6 +258,50 @@ pub async fn run_scheduler<H: Hooks>(
     }
 }
 
+/// Prints the next `count` fire times of every scheduler job, resolving
+/// English schedules and timezones the same way the running scheduler does.
+///
+/// All jobs are printed before returning, so every invalid schedule is
+/// reported at once.
+///
+/// # Errors
+///
+/// When the scheduler could not be loaded or at least one job has an invalid
+/// schedule or timezone.
+pub fn preview_scheduler<H: Hooks>(
+    app_context: &AppContext,
+    config: Option<&PathBuf>,
+    name: Option<String>,
+    tag: Option<String>,
+    count: usize,
+) -> Result<()> {
+    let scheduler = scheduler::<H>(app_context, config, name, tag)?;
+
+    let mut first_error = None;
+    for (job_name, upcoming) in scheduler.upcoming(&chrono::Utc::now(), count) {
+        let job = &scheduler.jobs[&job_name];
+        match upcoming {
+            Ok(times) => {
+                println!(
+                    "{job_name}: {:?} => {:?} ({})",
+                    job.cron,
+                    job.cron_syntax().unwrap_or_default(),
+                    job.timezone.as_deref().unwrap_or("UTC"),
+                );
+                for time in times {
+                    println!("  {}", time.format("%Y-%m-%d %H:%M:%S %Z (%a)"));
+                }
+            }
+            Err(err) => {
+                println!("{job_name}: {:?} => invalid: {err}", job.cron);
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+
+    first_error.map_or(Ok(()), |err| Err(err.into()))
+}
+
 /// Represents commands for handling database-related operations.
 #[derive(Debug)]
 pub enum RunDbCommand {
@@ -379,7 +423,7 @@ pub async fn create_context<H: Hooks>(
         #[cfg(feature = "with-db")]
         db,
         queue_provider,
-        storage: Storage::single(storage::drivers::null::new()).into(),
+        storage: storage::create_storage(&config)?.into(),
         cache: cache::create_cache_provider(&config).await?,
         config,
         mailer,
@@ -569,14 +613,48 @@ pub fn list_middlewares<H: Hooks>(ctx: &AppContext) -> Vec<MiddlewareInfo> {
 
 /// Initializes an [`EmailSender`] based on the mailer configuration settings
 /// ([`config::Mailer`]).
+///
+/// # Errors
+///
+/// When more than one transport is enabled, or the enabled transport could
+/// not be initialized
 fn create_mailer(config: &config::Mailer) -> Result<Option<EmailSender>> {
     if config.stub {
         return Ok(Some(EmailSender::stub()));
     }
-    if let Some(smtp) = config.smtp.as_ref() {
-        if smtp.enable {
-            return Ok(Some(EmailSender::smtp(smtp)?));
-        }
+
+    let smtp = config.smtp.as_ref().filter(|smtp| smtp.enable);
+    let file = config.file.as_ref().filter(|file| file.enable);
+    let sendmail = config.sendmail.as_ref().filter(|sendmail| sendmail.enable);
+    let http = config.http.as_ref().filter(|http| http.enable);
+
+    let enabled = [
+        ("smtp", smtp.is_some()),
+        ("file", file.is_some()),
+        ("sendmail", sendmail.is_some()),
+        ("http", http.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(name, enabled)| enabled.then_some(name))
+    .collect::<Vec<_>>();
+    if enabled.len() > 1 {
+        return Err(Error::Message(format!(
+            "only one mailer transport can be enabled, found: {}",
+            enabled.join(", ")
+        )));
+    }
+
+    if let Some(smtp) = smtp {
+        return Ok(Some(EmailSender::smtp(smtp)?));
+    }
+    if let Some(file) = file {
+        return Ok(Some(EmailSender::file(file)?));
+    }
+    if let Some(sendmail) = sendmail {
+        return Ok(Some(EmailSender::sendmail(sendmail)));
+    }
+    if let Some(http) = http {
+        return Ok(Some(EmailSender::http(http)?));
     }
     Ok(None)
 }
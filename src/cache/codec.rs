@@ -0,0 +1,335 @@
+//! # Cache Value Encoding
+//!
+//! This module encodes the values stored by [`super::Cache`]. Values are
+//! serialized with a [`Codec`] and, when configured, compressed once they
+//! are larger than a threshold.
+//!
+//! Plain JSON values are stored as-is. Any other encoding is prefixed with a
+//! small header naming the codec and compression, so values written with a
+//! previous configuration can still be read after the configuration changes.
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{CacheError, CacheResult};
+use crate::config::{CacheCompressionConfig, CacheEncodingConfig};
+
+/// First byte of encoded values carrying a header. JSON text never starts
+/// with it.
+const HEADER_MAGIC: u8 = 0;
+
+const HEADER_LEN: usize = 3;
+
+/// Format used to serialize cache values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Codec {
+    /// JSON, readable by any client of the cache
+    #[default]
+    Json,
+    #[cfg(feature = "cache_msgpack")]
+    /// `MessagePack`, a compact self-describing binary format
+    MessagePack,
+    #[cfg(feature = "cache_bincode")]
+    /// bincode, the most compact format, only readable by Rust clients with
+    /// the same types
+    Bincode,
+}
+
+impl Codec {
+    const fn id(self) -> u8 {
+        match self {
+            Self::Json => 0,
+            #[cfg(feature = "cache_msgpack")]
+            Self::MessagePack => 1,
+            #[cfg(feature = "cache_bincode")]
+            Self::Bincode => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> CacheResult<Self> {
+        match id {
+            0 => Ok(Self::Json),
+            #[cfg(feature = "cache_msgpack")]
+            1 => Ok(Self::MessagePack),
+            #[cfg(not(feature = "cache_msgpack"))]
+            1 => Err(disabled("MessagePack", "cache_msgpack")),
+            #[cfg(feature = "cache_bincode")]
+            2 => Ok(Self::Bincode),
+            #[cfg(not(feature = "cache_bincode"))]
+            2 => Err(disabled("bincode", "cache_bincode")),
+            _ => Err(CacheError::Deserialization(format!("unknown codec `{id}`"))),
+        }
+    }
+
+    fn serialize<T: Serialize + ?Sized>(self, value: &T) -> CacheResult<Vec<u8>> {
+        match self {
+            Self::Json => {
+                serde_json::to_vec(value).map_err(|e| CacheError::Serialization(e.to_string()))
+            }
+            #[cfg(feature = "cache_msgpack")]
+            Self::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|e| CacheError::Serialization(e.to_string()))
+            }
+            #[cfg(feature = "cache_bincode")]
+            Self::Bincode => {
+                bincode::serialize(value).map_err(|e| CacheError::Serialization(e.to_string()))
+            }
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(self, bytes: &[u8]) -> CacheResult<T> {
+        match self {
+            Self::Json => serde_json::from_slice(bytes)
+                .map_err(|e| CacheError::Deserialization(e.to_string())),
+            #[cfg(feature = "cache_msgpack")]
+            Self::MessagePack => {
+                rmp_serde::from_slice(bytes).map_err(|e| CacheError::Deserialization(e.to_string()))
+            }
+            #[cfg(feature = "cache_bincode")]
+            Self::Bincode => {
+                bincode::deserialize(bytes).map_err(|e| CacheError::Deserialization(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Algorithm used to compress large cache values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Compression {
+    #[cfg(feature = "cache_zstd")]
+    Zstd,
+    #[cfg(feature = "cache_gzip")]
+    Gzip,
+}
+
+impl Compression {
+    const fn id(self) -> u8 {
+        match self {
+            #[cfg(feature = "cache_zstd")]
+            Self::Zstd => 1,
+            #[cfg(feature = "cache_gzip")]
+            Self::Gzip => 2,
+        }
+    }
+
+    fn from_id(id: u8) -> CacheResult<Option<Self>> {
+        match id {
+            0 => Ok(None),
+            #[cfg(feature = "cache_zstd")]
+            1 => Ok(Some(Self::Zstd)),
+            #[cfg(not(feature = "cache_zstd"))]
+            1 => Err(disabled("zstd", "cache_zstd")),
+            #[cfg(feature = "cache_gzip")]
+            2 => Ok(Some(Self::Gzip)),
+            #[cfg(not(feature = "cache_gzip"))]
+            2 => Err(disabled("gzip", "cache_gzip")),
+            _ => Err(CacheError::Deserialization(format!(
+                "unknown compression `{id}`"
+            ))),
+        }
+    }
+
+    #[allow(clippy::unnecessary_wraps, unused_variables)]
+    fn compress(self, bytes: &[u8], level: Option<i32>) -> CacheResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "cache_zstd")]
+            Self::Zstd => zstd::encode_all(bytes, level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL))
+                .map_err(|e| CacheError::Serialization(e.to_string())),
+            #[cfg(feature = "cache_gzip")]
+            Self::Gzip => {
+                use std::io::Write;
+
+                let level = level
+                    .and_then(|level| u32::try_from(level).ok())
+                    .map_or_else(flate2::Compression::default, flate2::Compression::new);
+                let mut encoder = flate2::write::GzEncoder::new(Vec::new(), level);
+                encoder
+                    .write_all(bytes)
+                    .and_then(|()| encoder.finish())
+                    .map_err(|e| CacheError::Serialization(e.to_string()))
+            }
+        }
+    }
+
+    #[allow(clippy::unnecessary_wraps, unused_variables)]
+    fn decompress(self, bytes: &[u8]) -> CacheResult<Vec<u8>> {
+        match self {
+            #[cfg(feature = "cache_zstd")]
+            Self::Zstd => {
+                zstd::decode_all(bytes).map_err(|e| CacheError::Deserialization(e.to_string()))
+            }
+            #[cfg(feature = "cache_gzip")]
+            Self::Gzip => {
+                use std::io::Read;
+
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(bytes)
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| CacheError::Deserialization(e.to_string()))?;
+                Ok(decoded)
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn disabled(name: &str, feature: &str) -> CacheError {
+    CacheError::Deserialization(format!(
+        "value is encoded with {name}, enable the `{feature}` feature to read it"
+    ))
+}
+
+/// Encodes and decodes cache values according to a [`CacheEncodingConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct Encoder {
+    codec: Codec,
+    compression: Option<CacheCompressionConfig>,
+}
+
+impl Encoder {
+    /// Creates an encoder from the given configuration.
+    #[must_use]
+    pub fn new(config: &CacheEncodingConfig) -> Self {
+        Self {
+            codec: config.codec,
+            compression: config.compression.clone(),
+        }
+    }
+
+    /// Serializes a value with the configured codec, compressing it when it
+    /// is larger than the compression threshold.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CacheError`] if the value could not be serialized or
+    /// compressed.
+    pub fn encode<T: Serialize + ?Sized>(&self, value: &T) -> CacheResult<Vec<u8>> {
+        let serialized = self.codec.serialize(value)?;
+        let compression = self
+            .compression
+            .as_ref()
+            .filter(|compression| serialized.len() > compression.threshold);
+
+        if compression.is_none() && self.codec == Codec::Json {
+            return Ok(serialized);
+        }
+
+        let mut encoded = Vec::with_capacity(HEADER_LEN + serialized.len());
+        encoded.push(HEADER_MAGIC);
+        encoded.push(self.codec.id());
+        match compression {
+            Some(compression) => {
+                encoded.push(compression.algorithm.id());
+                encoded.extend(
+                    compression
+                        .algorithm
+                        .compress(&serialized, compression.level)?,
+                );
+            }
+            None => {
+                encoded.push(0);
+                encoded.extend(serialized);
+            }
+        }
+        Ok(encoded)
+    }
+
+    /// Decodes a value, whichever codec and compression it was encoded with.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`CacheError`] if the value could not be decompressed or
+    /// deserialized, or was encoded with a codec or compression whose
+    /// feature is disabled.
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> CacheResult<T> {
+        match bytes {
+            [HEADER_MAGIC, codec, compression, payload @ ..] => {
+                let codec = Codec::from_id(*codec)?;
+                match Compression::from_id(*compression)? {
+                    Some(compression) => codec.deserialize(&compression.decompress(payload)?),
+                    None => codec.deserialize(payload),
+                }
+            }
+            _ => Codec::Json.deserialize(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Fragment {
+        id: u32,
+        html: String,
+    }
+
+    fn fragment() -> Fragment {
+        Fragment {
+            id: 42,
+            html: "<li>loco</li>".repeat(100),
+        }
+    }
+
+    fn encoder(codec: Codec, compression: Option<CacheCompressionConfig>) -> Encoder {
+        Encoder::new(&CacheEncodingConfig { codec, compression })
+    }
+
+    #[test]
+    fn can_store_plain_json() {
+        let encoder = Encoder::default();
+        let encoded = encoder.encode(&fragment()).unwrap();
+        assert_eq!(encoded, serde_json::to_vec(&fragment()).unwrap());
+        assert_eq!(encoder.decode::<Fragment>(&encoded).unwrap(), fragment());
+        // counters are stored as plain integers by the drivers
+        assert_eq!(encoder.decode::<i64>(b"-3").unwrap(), -3);
+    }
+
+    #[rstest]
+    #[case(Codec::Json)]
+    #[cfg_attr(feature = "cache_msgpack", case(Codec::MessagePack))]
+    #[cfg_attr(feature = "cache_bincode", case(Codec::Bincode))]
+    fn can_roundtrip_codec(#[case] codec: Codec) {
+        let encoder = encoder(codec, None);
+        let encoded = encoder.encode(&fragment()).unwrap();
+        assert_eq!(encoder.decode::<Fragment>(&encoded).unwrap(), fragment());
+        // values can be read whatever the current configuration
+        assert_eq!(
+            Encoder::default().decode::<Fragment>(&encoded).unwrap(),
+            fragment()
+        );
+    }
+
+    #[cfg(any(feature = "cache_zstd", feature = "cache_gzip"))]
+    #[rstest]
+    #[cfg_attr(feature = "cache_zstd", case(Compression::Zstd))]
+    #[cfg_attr(feature = "cache_gzip", case(Compression::Gzip))]
+    fn can_compress_above_threshold(#[case] algorithm: Compression) {
+        let encoder = encoder(
+            Codec::Json,
+            Some(CacheCompressionConfig {
+                algorithm,
+                threshold: 1024,
+                level: None,
+            }),
+        );
+
+        let small = encoder.encode("small").unwrap();
+        assert_eq!(small, b"\"small\"");
+
+        let large = encoder.encode(&fragment()).unwrap();
+        assert_eq!(large[..3], [HEADER_MAGIC, Codec::Json.id(), algorithm.id()]);
+        assert!(large.len() < serde_json::to_vec(&fragment()).unwrap().len() / 4);
+        assert_eq!(encoder.decode::<Fragment>(&large).unwrap(), fragment());
+    }
+
+    #[test]
+    fn cant_decode_unknown_encoding() {
+        let err = Encoder::default()
+            .decode::<String>(&[HEADER_MAGIC, 9, 0])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Deserialization error: unknown codec `9`");
+    }
+}
@@ -0,0 +1,904 @@
+//! # Database Cache Driver
+//!
+//! This module implements a cache driver storing entries in a table of the
+//! application database (Postgres or `SQLite`), so a cache can be shared
+//! across processes without running Redis.
+//!
+//! Entries are kept in the `loco_cache` table with their expiry time, tags in
+//! `loco_cache_tags` and the shared hit and miss counters in
+//! `loco_cache_stats`. The tables are created by a migration with
+//! [`crate::schema::create_cache_tables`], and the driver only checks that
+//! they exist when it starts.
+//! Expired entries are never returned, and are deleted by a periodic cleanup
+//! task.
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::Utc;
+use sea_orm::{
+    ConnectionTrait, DatabaseConnection, DbBackend, ExecResult, QueryResult, Statement,
+    TransactionTrait, Value,
+};
+use sea_orm_migration::SchemaManager;
+use tokio::task::JoinHandle;
+
+use super::CacheDriver;
+use crate::cache::{CacheError, CacheResult, CacheStats};
+use crate::config::{self, DatabaseCacheConfig};
+
+/// Tables created by [`crate::schema::create_cache_tables`].
+const TABLES: &[&str] = &["loco_cache", "loco_cache_tags", "loco_cache_stats"];
+
+/// Adds `$2` to the shared counter `$1`.
+const ADD_STATS: &str = "INSERT INTO loco_cache_stats (name, count) VALUES ($1, $2) ON CONFLICT \
+                         (name) DO UPDATE SET count = loco_cache_stats.count + excluded.count";
+
+/// Matches the rows which have not expired at `$2` (in unix milliseconds).
+const NOT_EXPIRED: &str = "(expires_at IS NULL OR expires_at > $2)";
+
+const UPSERT: &str = "INSERT INTO loco_cache (cache_key, value, expires_at) VALUES ($1, $2, $3) \
+                      ON CONFLICT (cache_key) DO UPDATE SET value = excluded.value, expires_at = \
+                      excluded.expires_at";
+
+/// Adds `$2` to a counter, or creates it with the value `$2` and expiry `$3`
+/// when it is missing or expired at `$4`. Counters are stored as the bytes of
+/// their decimal text.
+///
+/// `CAST` turns text which is not an integer into `0`, so a value is only
+/// updated when it reads back the same as an integer, and no row is returned
+/// otherwise.
+const INCREMENT_SQLITE: &str = "INSERT INTO loco_cache (cache_key, value, expires_at) VALUES ($1, \
+                                CAST(CAST($2 AS TEXT) AS BLOB), $3) ON CONFLICT (cache_key) DO \
+                                UPDATE SET value = CASE WHEN loco_cache.expires_at <= $4 THEN \
+                                excluded.value ELSE CAST(CAST(CAST(loco_cache.value AS INTEGER) \
+                                + $2 AS TEXT) AS BLOB) END, expires_at = CASE WHEN \
+                                loco_cache.expires_at <= $4 THEN excluded.expires_at ELSE \
+                                loco_cache.expires_at END WHERE loco_cache.expires_at <= $4 OR \
+                                CAST(CAST(CAST(loco_cache.value AS TEXT) AS INTEGER) AS TEXT) = \
+                                CAST(loco_cache.value AS TEXT) RETURNING value";
+
+/// Postgres version of [`INCREMENT_SQLITE`].
+const INCREMENT_POSTGRES: &str = "INSERT INTO loco_cache (cache_key, value, expires_at) VALUES \
+                                  ($1, convert_to(CAST($2 AS TEXT), 'UTF8'), $3) ON CONFLICT \
+                                  (cache_key) DO UPDATE SET value = CASE WHEN \
+                                  loco_cache.expires_at <= $4 THEN excluded.value ELSE \
+                                  convert_to(CAST(CAST(convert_from(loco_cache.value, 'UTF8') AS \
+                                  BIGINT) + $2 AS TEXT), 'UTF8') END, expires_at = CASE WHEN \
+                                  loco_cache.expires_at <= $4 THEN excluded.expires_at ELSE \
+                                  loco_cache.expires_at END RETURNING value";
+
+/// Creates a new instance of the database cache driver.
+///
+/// The driver connects to the application database unless a `uri` is set in
+/// the cache configuration, checks that the cache tables exist, and starts
+/// the periodic cleanup of expired entries.
+///
+/// # Returns
+///
+/// A [`crate::cache::Cache`] instance.
+///
+/// # Errors
+///
+/// Returns a `CacheError` if there is an error connecting to the database or
+/// when the cache tables are missing.
+pub async fn new(
+    database: &config::Database,
+    config: &DatabaseCacheConfig,
+) -> CacheResult<crate::cache::Cache> {
+    let mut database = database.clone();
+    if let Some(uri) = &config.uri {
+        database.uri.clone_from(uri);
+    }
+    let db = crate::db::connect(&database).await?;
+    let driver = Database::from(db, Duration::from_millis(config.cleanup_interval)).await?;
+    Ok(crate::cache::Cache::new(driver).with_encoding(&config.encoding))
+}
+
+/// Represents the database cache driver.
+pub struct Database {
+    db: DatabaseConnection,
+    cleanup: Option<JoinHandle<()>>,
+}
+
+impl Database {
+    /// Constructs a new [`Database`] driver from a given connection, checking
+    /// that the cache tables exist. Expired entries are deleted every
+    /// `cleanup_interval`, unless it is zero.
+    ///
+    /// # Returns
+    ///
+    /// A boxed [`CacheDriver`] instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the cache tables are missing.
+    pub async fn from(
+        db: DatabaseConnection,
+        cleanup_interval: Duration,
+    ) -> CacheResult<Box<dyn CacheDriver>> {
+        let manager = SchemaManager::new(&db);
+        for table in TABLES {
+            if !manager.has_table(table).await? {
+                return Err(CacheError::Any(
+                    format!(
+                        "table `{table}` of the database cache is missing, create the cache \
+                         tables in a migration with `loco_rs::schema::create_cache_tables`"
+                    )
+                    .into(),
+                ));
+            }
+        }
+
+        let cleanup = (!cleanup_interval.is_zero()).then(|| {
+            let db = db.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(cleanup_interval);
+                loop {
+                    interval.tick().await;
+                    if let Err(err) = cleanup(&db).await {
+                        tracing::warn!(error = %err, "could not delete expired cache entries");
+                    }
+                }
+            })
+        });
+
+        Ok(Box::new(Self { db, cleanup }))
+    }
+
+    fn statement(&self, sql: &str, values: Vec<Value>) -> Statement {
+        Statement::from_sql_and_values(self.db.get_database_backend(), sql, values)
+    }
+
+    async fn execute(&self, sql: &str, values: Vec<Value>) -> CacheResult<ExecResult> {
+        Ok(self.db.execute(self.statement(sql, values)).await?)
+    }
+
+    async fn query_one(&self, sql: &str, values: Vec<Value>) -> CacheResult<Option<QueryResult>> {
+        Ok(self.db.query_one(self.statement(sql, values)).await?)
+    }
+
+    /// Inserts an entry with its tags, replacing the tags of its previous
+    /// insert.
+    async fn upsert<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        expires_at: Option<i64>,
+    ) -> CacheResult<()> {
+        db.execute(self.statement(
+            "DELETE FROM loco_cache_tags WHERE cache_key = $1",
+            vec![key.into()],
+        ))
+        .await?;
+        db.execute(self.statement(UPSERT, vec![key.into(), value.into(), expires_at.into()]))
+            .await?;
+        for tag in tags {
+            db.execute(self.statement(
+                "INSERT INTO loco_cache_tags (tag, cache_key) VALUES ($1, $2) ON CONFLICT DO \
+                 NOTHING",
+                vec![(*tag).into(), key.into()],
+            ))
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Inserts an entry with its tags in a transaction.
+    async fn insert_entry(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let txn = self.db.begin().await?;
+        self.upsert(&txn, key, value, tags, duration.map(expires_at))
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        if let Some(cleanup) = &self.cleanup {
+            cleanup.abort();
+        }
+    }
+}
+
+/// Deletes expired entries, and the tags of entries which no longer exist.
+async fn cleanup(db: &DatabaseConnection) -> CacheResult<u64> {
+    let backend = db.get_database_backend();
+    let deleted = db
+        .execute(Statement::from_sql_and_values(
+            backend,
+            "DELETE FROM loco_cache WHERE expires_at <= $1",
+            [now().into()],
+        ))
+        .await?
+        .rows_affected();
+    db.execute(Statement::from_string(
+        backend,
+        "DELETE FROM loco_cache_tags WHERE cache_key NOT IN (SELECT cache_key FROM loco_cache)",
+    ))
+    .await?;
+    Ok(deleted)
+}
+
+/// Returns the current time in unix milliseconds.
+fn now() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// Returns the expiry time of an entry inserted now, in unix milliseconds.
+fn expires_at(duration: Duration) -> i64 {
+    now().saturating_add(i64::try_from(duration.as_millis()).unwrap_or(i64::MAX))
+}
+
+#[async_trait]
+impl CacheDriver for Database {
+    /// Pings the database to check if it is reachable.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn ping(&self) -> CacheResult<()> {
+        Ok(self.db.ping().await?)
+    }
+
+    /// Checks if a key exists in the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn contains_key(&self, key: &str) -> CacheResult<bool> {
+        let row = self
+            .query_one(
+                &format!("SELECT 1 FROM loco_cache WHERE cache_key = $1 AND {NOT_EXPIRED}"),
+                vec![key.into(), now().into()],
+            )
+            .await?;
+        Ok(row.is_some())
+    }
+
+    /// Retrieves a value from the cache based on the provided key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn get(&self, key: &str) -> CacheResult<Option<Vec<u8>>> {
+        let row = self
+            .query_one(
+                &format!("SELECT value FROM loco_cache WHERE cache_key = $1 AND {NOT_EXPIRED}"),
+                vec![key.into(), now().into()],
+            )
+            .await?;
+        Ok(row.map(|row| row.try_get("", "value")).transpose()?)
+    }
+
+    /// Inserts a key-value pair into the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert(&self, key: &str, value: &[u8]) -> CacheResult<()> {
+        self.insert_entry(key, value, &[], None).await
+    }
+
+    /// Inserts a key-value pair into the cache that expires after the
+    /// specified duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_expiry(
+        &self,
+        key: &str,
+        value: &[u8],
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.insert_entry(key, value, &[], Some(duration)).await
+    }
+
+    /// Inserts a key-value pair into the cache and associates the key with
+    /// the given tags, instead of the tags of its previous insert.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_tagged(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        self.insert_entry(key, value, tags, duration).await
+    }
+
+    /// Removes every key associated with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let txn = self.db.begin().await?;
+        txn.execute(self.statement(
+            "DELETE FROM loco_cache WHERE cache_key IN (SELECT cache_key FROM loco_cache_tags \
+             WHERE tag = $1)",
+            vec![tag.into()],
+        ))
+        .await?;
+        txn.execute(self.statement(
+            "DELETE FROM loco_cache_tags WHERE tag = $1",
+            vec![tag.into()],
+        ))
+        .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Atomically increments the integer value of a key by `delta`, creating
+    /// it with the given expiry when missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation or
+    /// the value is not an integer.
+    async fn increment(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        let row = self
+            .query_one(
+                match self.db.get_database_backend() {
+                    DbBackend::Postgres => INCREMENT_POSTGRES,
+                    _ => INCREMENT_SQLITE,
+                },
+                vec![
+                    key.into(),
+                    delta.into(),
+                    duration.map(expires_at).into(),
+                    now().into(),
+                ],
+            )
+            .await?
+            // the value of the key is not an integer, see `INCREMENT_SQLITE`
+            .ok_or_else(|| {
+                CacheError::Any(format!("value of key `{key}` is not an integer").into())
+            })?;
+        let value: Vec<u8> = row.try_get("", "value")?;
+        std::str::from_utf8(&value)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| {
+                CacheError::Any(format!("value of key `{key}` is not an integer").into())
+            })
+    }
+
+    /// Atomically decrements the integer value of a key by `delta`, creating
+    /// it with the given expiry when missing.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation or
+    /// the value is not an integer.
+    async fn decrement(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        let delta = delta
+            .checked_neg()
+            .ok_or_else(|| CacheError::Any("decrement overflow".into()))?;
+        self.increment(key, delta, duration).await
+    }
+
+    /// Returns the remaining time to live of a key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn ttl(&self, key: &str) -> CacheResult<Option<Duration>> {
+        let now = now();
+        let row = self
+            .query_one(
+                &format!(
+                    "SELECT expires_at FROM loco_cache WHERE cache_key = $1 AND {NOT_EXPIRED}"
+                ),
+                vec![key.into(), now.into()],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let expires_at: Option<i64> = row.try_get("", "expires_at")?;
+        Ok(expires_at.map(|expires_at| {
+            Duration::from_millis(u64::try_from(expires_at - now).unwrap_or_default())
+        }))
+    }
+
+    /// Sets a new expiry on an existing key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn expire(&self, key: &str, duration: Duration) -> CacheResult<bool> {
+        let result = self
+            .execute(
+                &format!(
+                    "UPDATE loco_cache SET expires_at = $3 WHERE cache_key = $1 AND {NOT_EXPIRED}"
+                ),
+                vec![key.into(), now().into(), expires_at(duration).into()],
+            )
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Always acquires the lock, concurrent loads are only coalesced within
+    /// a process.
+    ///
+    /// # Errors
+    ///
+    /// This driver never returns an error.
+    async fn acquire_lock(&self, _key: &str) -> CacheResult<bool> {
+        Ok(true)
+    }
+
+    /// Releases the load lock of a key.
+    ///
+    /// # Errors
+    ///
+    /// This driver never returns an error.
+    async fn release_lock(&self, _key: &str) -> CacheResult<()> {
+        Ok(())
+    }
+
+    /// Removes a key-value pair from the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove(&self, key: &str) -> CacheResult<()> {
+        self.execute(
+            "DELETE FROM loco_cache WHERE cache_key = $1",
+            vec![key.into()],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Clears all key-value pairs from the cache.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn clear(&self) -> CacheResult<()> {
+        let txn = self.db.begin().await?;
+        txn.execute_unprepared("DELETE FROM loco_cache").await?;
+        txn.execute_unprepared("DELETE FROM loco_cache_tags")
+            .await?;
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Retrieves the values of several keys with a single query.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn get_many(&self, keys: &[&str]) -> CacheResult<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let placeholders = (0..keys.len())
+            .map(|i| format!("${}", i + 2))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut values: Vec<Value> = vec![now().into()];
+        values.extend(keys.iter().map(|key| Value::from(*key)));
+        let rows = self
+            .db
+            .query_all(self.statement(
+                &format!(
+                    "SELECT cache_key, value FROM loco_cache WHERE cache_key IN ({placeholders}) \
+                     AND (expires_at IS NULL OR expires_at > $1)"
+                ),
+                values,
+            ))
+            .await?;
+
+        let mut found = std::collections::HashMap::with_capacity(rows.len());
+        for row in rows {
+            let key: String = row.try_get("", "cache_key")?;
+            let value: Vec<u8> = row.try_get("", "value")?;
+            found.insert(key, value);
+        }
+        Ok(keys.iter().map(|key| found.remove(*key)).collect())
+    }
+
+    /// Inserts several key-value pairs in a single transaction.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_many(
+        &self,
+        entries: &[(&str, &[u8])],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let expires_at = duration.map(expires_at);
+        let txn = self.db.begin().await?;
+        for (key, value) in entries {
+            self.upsert(&txn, key, value, &[], expires_at).await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Adds lookups to the shared counters in `loco_cache_stats`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn add_stats(&self, stats: CacheStats) -> CacheResult<()> {
+        let txn = self.db.begin().await?;
+        for (name, count) in [("hits", stats.hits), ("misses", stats.misses)] {
+            let count = i64::try_from(count).unwrap_or(i64::MAX);
+            txn.execute(self.statement(ADD_STATS, vec![name.into(), count.into()]))
+                .await?;
+        }
+        txn.commit().await?;
+        Ok(())
+    }
+
+    /// Returns the shared counters in `loco_cache_stats`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn stats(&self) -> CacheResult<CacheStats> {
+        let rows = self
+            .db
+            .query_all(self.statement("SELECT name, count FROM loco_cache_stats", vec![]))
+            .await?;
+        let mut stats = CacheStats::default();
+        for row in rows {
+            let name: String = row.try_get("", "name")?;
+            let count = u64::try_from(row.try_get::<i64>("", "count")?).unwrap_or_default();
+            match name.as_str() {
+                "hits" => stats.hits = count,
+                "misses" => stats.misses = count,
+                _ => {}
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    async fn setup(cleanup_interval: Duration) -> (Box<dyn CacheDriver>, DatabaseConnection) {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        crate::schema::create_cache_tables(&SchemaManager::new(&db))
+            .await
+            .unwrap();
+        let driver = Database::from(db.clone(), cleanup_interval).await.unwrap();
+        (driver, db)
+    }
+
+    async fn count_rows(db: &DatabaseConnection) -> i64 {
+        db.query_one(Statement::from_string(
+            DbBackend::Sqlite,
+            "SELECT COUNT(*) AS count FROM loco_cache",
+        ))
+        .await
+        .unwrap()
+        .unwrap()
+        .try_get("", "count")
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn requires_cache_tables() {
+        let db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        let err = Database::from(db.clone(), Duration::ZERO)
+            .await
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("create_cache_tables"));
+
+        let manager = SchemaManager::new(&db);
+        crate::schema::create_cache_tables(&manager).await.unwrap();
+        assert!(Database::from(db.clone(), Duration::ZERO).await.is_ok());
+        crate::schema::drop_cache_tables(&manager).await.unwrap();
+        assert!(!manager.has_table("loco_cache").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn is_contains_key() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        assert!(!cache.contains_key("key").await.unwrap());
+        cache.insert("key", b"loco").await.unwrap();
+        assert!(cache.contains_key("key").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_get_insert_and_remove() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+
+        assert_eq!(cache.get("key").await.unwrap(), None);
+        cache.insert("key", b"loco").await.unwrap();
+        cache.insert("key", b"updated").await.unwrap();
+        assert_eq!(cache.get("key").await.unwrap(), Some(b"updated".to_vec()));
+
+        cache.remove("key").await.unwrap();
+        assert_eq!(cache.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn keeps_stats_apart_from_entries() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+
+        cache
+            .add_stats(CacheStats { hits: 2, misses: 1 })
+            .await
+            .unwrap();
+        cache
+            .add_stats(CacheStats { hits: 1, misses: 0 })
+            .await
+            .unwrap();
+        cache.clear().await.unwrap();
+
+        assert_eq!(cache.get("loco:cache:stats:hits").await.unwrap(), None);
+        assert_eq!(
+            cache.stats().await.unwrap(),
+            CacheStats { hits: 3, misses: 1 }
+        );
+    }
+
+    #[tokio::test]
+    async fn can_store_binary_values() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        let value = [0, 1, 2, 255, 0];
+        cache.insert("key", &value).await.unwrap();
+        assert_eq!(cache.get("key").await.unwrap(), Some(value.to_vec()));
+    }
+
+    #[tokio::test]
+    async fn can_clear() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        cache.insert("key1", b"loco").await.unwrap();
+        cache
+            .insert_tagged("key2", b"loco", &["tag"], None)
+            .await
+            .unwrap();
+
+        cache.clear().await.unwrap();
+        assert!(!cache.contains_key("key1").await.unwrap());
+        assert!(!cache.contains_key("key2").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_expire_entries() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        cache
+            .insert_with_expiry("key", b"loco", Duration::from_millis(50))
+            .await
+            .unwrap();
+        assert!(cache.contains_key("key").await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!cache.contains_key("key").await.unwrap());
+        assert_eq!(cache.get("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn can_cleanup_expired_entries() {
+        let (cache, db) = setup(Duration::from_millis(50)).await;
+        cache.insert("key1", b"loco").await.unwrap();
+        cache
+            .insert_with_expiry("key2", b"loco", Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(count_rows(&db).await, 2);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(count_rows(&db).await, 1);
+    }
+
+    #[tokio::test]
+    async fn can_use_bulk_operations() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        cache
+            .insert_many(
+                &[
+                    ("key1", b"value1".as_slice()),
+                    ("key2", b"value2".as_slice()),
+                ],
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get_many(&["key2", "missing", "key1"]).await.unwrap(),
+            vec![Some(b"value2".to_vec()), None, Some(b"value1".to_vec())]
+        );
+
+        cache.remove_many(&["key1", "key2"]).await.unwrap();
+        assert_eq!(
+            cache.get_many(&["key1", "key2"]).await.unwrap(),
+            vec![None, None]
+        );
+    }
+
+    #[tokio::test]
+    async fn can_invalidate_tag() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        cache
+            .insert_tagged("key1", b"loco", &["users"], None)
+            .await
+            .unwrap();
+        cache
+            .insert_tagged("key2", b"loco", &["users", "posts"], None)
+            .await
+            .unwrap();
+        cache.insert("key3", b"loco").await.unwrap();
+
+        cache.invalidate_tag("users").await.unwrap();
+        assert!(!cache.contains_key("key1").await.unwrap());
+        assert!(!cache.contains_key("key2").await.unwrap());
+        assert!(cache.contains_key("key3").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn keeps_reinserted_untagged_keys() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        cache
+            .insert_tagged("product:1", b"a", &["products"], None)
+            .await
+            .unwrap();
+        cache.insert("product:1", b"b").await.unwrap();
+        cache.invalidate_tag("products").await.unwrap();
+        assert_eq!(cache.get("product:1").await.unwrap(), Some(b"b".to_vec()));
+
+        // re-inserting with other tags moves the key
+        cache
+            .insert_tagged("product:2", b"a", &["products"], None)
+            .await
+            .unwrap();
+        cache
+            .insert_tagged("product:2", b"b", &["featured"], None)
+            .await
+            .unwrap();
+        cache.invalidate_tag("products").await.unwrap();
+        assert!(cache.contains_key("product:2").await.unwrap());
+        cache.invalidate_tag("featured").await.unwrap();
+        assert!(!cache.contains_key("product:2").await.unwrap());
+
+        cache
+            .insert_tagged("product:3", b"a", &["products"], None)
+            .await
+            .unwrap();
+        cache
+            .insert_many(&[("product:3", b"b".as_slice())], None)
+            .await
+            .unwrap();
+        cache.invalidate_tag("products").await.unwrap();
+        assert!(cache.contains_key("product:3").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_increment_and_decrement() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        assert_eq!(cache.increment("counter", 5, None).await.unwrap(), 5);
+        assert_eq!(cache.increment("counter", 2, None).await.unwrap(), 7);
+        assert_eq!(cache.decrement("counter", 10, None).await.unwrap(), -3);
+        assert_eq!(cache.get("counter").await.unwrap(), Some(b"-3".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn cant_increment_non_integer_values() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        for value in [b"loco".as_slice(), b"1.5", b"12abc", b""] {
+            cache.insert("key", value).await.unwrap();
+            assert!(cache.increment("key", 1, None).await.is_err());
+            assert_eq!(cache.get("key").await.unwrap(), Some(value.to_vec()));
+        }
+
+        // an expired value is replaced
+        cache
+            .insert_with_expiry("key", b"loco", Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(cache.increment("key", 1, None).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn can_restart_expired_counter() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        let window = Some(Duration::from_millis(50));
+        assert_eq!(cache.increment("counter", 1, window).await.unwrap(), 1);
+        assert_eq!(cache.increment("counter", 1, window).await.unwrap(), 2);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(cache.increment("counter", 1, window).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn can_get_ttl_and_expire() {
+        let (cache, _db) = setup(Duration::ZERO).await;
+        assert_eq!(cache.ttl("key").await.unwrap(), None);
+        assert!(!cache.expire("key", Duration::from_secs(60)).await.unwrap());
+
+        cache.insert("key", b"loco").await.unwrap();
+        assert_eq!(cache.ttl("key").await.unwrap(), None);
+
+        assert!(cache.expire("key", Duration::from_secs(60)).await.unwrap());
+        let ttl = cache.ttl("key").await.unwrap().unwrap();
+        assert!(ttl > Duration::from_secs(50) && ttl <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn can_share_entries_across_connections() {
+        let dir = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let uri = format!(
+            "sqlite://{}?mode=rwc",
+            dir.root.join("cache.sqlite").display()
+        );
+
+        let first = sea_orm::Database::connect(&uri).await.unwrap();
+        crate::schema::create_cache_tables(&SchemaManager::new(&first))
+            .await
+            .unwrap();
+        let first = Database::from(first, Duration::ZERO).await.unwrap();
+        let second = sea_orm::Database::connect(&uri).await.unwrap();
+        let second = Database::from(second, Duration::ZERO).await.unwrap();
+
+        first.insert("key", b"loco").await.unwrap();
+        assert_eq!(second.get("key").await.unwrap(), Some(b"loco".to_vec()));
+    }
+
+    #[tokio::test]
+    async fn can_use_postgres() {
+        let (pg_url, _container) = crate::tests_cfg::postgres::setup_postgres_container().await;
+        let db = sea_orm::Database::connect(&pg_url).await.unwrap();
+        crate::schema::create_cache_tables(&SchemaManager::new(&db))
+            .await
+            .unwrap();
+        let cache = new(
+            &crate::tests_cfg::config::get_database_config(),
+            &DatabaseCacheConfig {
+                uri: Some(pg_url),
+                cleanup_interval: 0,
+                encoding: config::CacheEncodingConfig::default(),
+            },
+        )
+        .await
+        .unwrap()
+        .driver;
+
+        cache.insert("key", b"loco").await.unwrap();
+        assert_eq!(cache.get("key").await.unwrap(), Some(b"loco".to_vec()));
+
+        cache
+            .insert_tagged("tagged", b"loco", &["tag"], Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        assert!(cache.ttl("tagged").await.unwrap().is_some());
+        cache.invalidate_tag("tag").await.unwrap();
+        assert!(!cache.contains_key("tagged").await.unwrap());
+
+        assert_eq!(cache.increment("counter", 5, None).await.unwrap(), 5);
+        assert_eq!(cache.decrement("counter", 2, None).await.unwrap(), 3);
+    }
+}
@@ -2,15 +2,19 @@
 //!
 //! This module implements a cache driver using an in-memory cache.
 use std::{
-    sync::Arc,
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
     time::{Duration, Instant},
 };
 
 use async_trait::async_trait;
-use moka::{sync::Cache, Expiry};
+use moka::{ops::compute::Op, sync::Cache, Expiry};
 
 use super::CacheDriver;
-use crate::cache::CacheResult;
+use crate::cache::{CacheError, CacheResult, CacheStats};
 use crate::config::InMemCacheConfig;
 
 /// Creates a new instance of the in-memory cache driver, with a default Loco
@@ -21,7 +25,7 @@ use crate::config::InMemCacheConfig;
 /// A [`Cache`] instance.
 #[must_use]
 pub fn new(config: &InMemCacheConfig) -> crate::cache::Cache {
-    let cache: Cache<String, (Expiration, String)> = Cache::builder()
+    let cache: Cache<String, (Expiration, Vec<u8>)> = Cache::builder()
         .max_capacity(config.max_capacity)
         .expire_after(InMemExpiry)
         .build();
@@ -31,7 +35,103 @@ pub fn new(config: &InMemCacheConfig) -> crate::cache::Cache {
 /// Represents the in-memory cache driver.
 #[derive(Debug)]
 pub struct Inmem {
-    cache: Cache<String, (Expiration, String)>,
+    cache: Cache<String, (Expiration, Vec<u8>)>,
+    tags: Mutex<TagIndex>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Number of tagged keys below which the tag index is not swept.
+const MIN_SWEEP_KEYS: usize = 1024;
+
+/// Tags of the keys currently cached with tags, and the other way around.
+#[derive(Debug)]
+struct TagIndex {
+    tags: HashMap<String, HashSet<String>>,
+    keys: HashMap<String, HashSet<String>>,
+    /// Number of tagged keys at which keys expired or evicted from the cache
+    /// are pruned.
+    next_sweep: usize,
+}
+
+impl Default for TagIndex {
+    fn default() -> Self {
+        Self {
+            tags: HashMap::new(),
+            keys: HashMap::new(),
+            next_sweep: MIN_SWEEP_KEYS,
+        }
+    }
+}
+
+impl TagIndex {
+    fn tag(&mut self, key: &str, tags: &[&str]) {
+        self.untag(key);
+        if tags.is_empty() {
+            return;
+        }
+        for tag in tags {
+            self.tags
+                .entry((*tag).to_string())
+                .or_default()
+                .insert(key.to_string());
+        }
+        self.keys.insert(
+            key.to_string(),
+            tags.iter().map(|tag| (*tag).to_string()).collect(),
+        );
+    }
+
+    fn untag(&mut self, key: &str) {
+        let Some(tags) = self.keys.remove(key) else {
+            return;
+        };
+        for tag in tags {
+            if let Some(keys) = self.tags.get_mut(&tag) {
+                keys.remove(key);
+                if keys.is_empty() {
+                    self.tags.remove(&tag);
+                }
+            }
+        }
+    }
+
+    /// Removes a tag, returning its keys.
+    fn take(&mut self, tag: &str) -> HashSet<String> {
+        let keys = self.tags.remove(tag).unwrap_or_default();
+        for key in &keys {
+            self.untag(key);
+        }
+        keys
+    }
+
+    /// Prunes the keys no longer cached once the index doubled in size since
+    /// the last sweep, so the index stays proportional to the cache.
+    fn sweep(&mut self, cache: &Cache<String, (Expiration, Vec<u8>)>) {
+        if self.keys.len() < self.next_sweep {
+            return;
+        }
+        let gone = self
+            .keys
+            .keys()
+            .filter(|key| !cache.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in gone {
+            self.untag(&key);
+        }
+        self.next_sweep = (self.keys.len() * 2).max(MIN_SWEEP_KEYS);
+    }
+}
+
+impl Inmem {
+    /// Locks the tag index. The cache is updated while the lock is held, so
+    /// the index and the cache stay in sync.
+    fn tags(&self) -> std::sync::MutexGuard<'_, TagIndex> {
+        self.tags
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
 }
 
 impl Inmem {
@@ -41,11 +141,21 @@ impl Inmem {
     ///
     /// A boxed [`CacheDriver`] instance.
     #[must_use]
-    pub fn from(cache: Cache<String, (Expiration, String)>) -> Box<dyn CacheDriver> {
-        Box::new(Self { cache })
+    pub fn from(cache: Cache<String, (Expiration, Vec<u8>)>) -> Box<dyn CacheDriver> {
+        Box::new(Self {
+            cache,
+            tags: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
     }
 }
 
+/// Parses a counter, stored as the decimal representation of an integer.
+fn parse_integer(value: &[u8]) -> Option<i64> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
 #[async_trait]
 impl CacheDriver for Inmem {
     /// Pings the cache to check if it is reachable.
@@ -71,7 +181,7 @@ impl CacheDriver for Inmem {
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
-    async fn get(&self, key: &str) -> CacheResult<Option<String>> {
+    async fn get(&self, key: &str) -> CacheResult<Option<Vec<u8>>> {
         let result = self.cache.get(key);
         match result {
             None => Ok(None),
@@ -84,11 +194,11 @@ impl CacheDriver for Inmem {
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
-    async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
-        self.cache.insert(
-            key.to_string(),
-            (Expiration::Never, Arc::new(value).to_string()),
-        );
+    async fn insert(&self, key: &str, value: &[u8]) -> CacheResult<()> {
+        let mut tags = self.tags();
+        self.cache
+            .insert(key.to_string(), (Expiration::Never, value.to_vec()));
+        tags.untag(key);
         Ok(())
     }
 
@@ -102,16 +212,153 @@ impl CacheDriver for Inmem {
     async fn insert_with_expiry(
         &self,
         key: &str,
-        value: &str,
+        value: &[u8],
         duration: Duration,
     ) -> CacheResult<()> {
+        let mut tags = self.tags();
         self.cache.insert(
             key.to_string(),
-            (
-                Expiration::AfterDuration(duration),
-                Arc::new(value).to_string(),
-            ),
+            (Expiration::after(duration), value.to_vec()),
         );
+        tags.untag(key);
+        Ok(())
+    }
+
+    /// Inserts a key-value pair into the cache and associates it with the
+    /// given tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_tagged(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let expiration = duration.map_or(Expiration::Never, Expiration::after);
+        let mut index = self.tags();
+        self.cache
+            .insert(key.to_string(), (expiration, value.to_vec()));
+        index.tag(key, tags);
+        index.sweep(&self.cache);
+        Ok(())
+    }
+
+    /// Removes all key-value pairs associated with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let mut tags = self.tags();
+        for key in tags.take(tag) {
+            self.cache.invalidate(&key);
+        }
+        Ok(())
+    }
+
+    /// Atomically increments the integer stored at the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the stored value is not an integer or the
+    /// result overflows.
+    async fn increment(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        let result = self
+            .cache
+            .entry(key.to_string())
+            .and_try_compute_with(|entry| {
+                let (expiration, current) = match entry {
+                    Some(entry) => {
+                        let (expiration, value) = entry.into_value();
+                        let current = parse_integer(&value).ok_or_else(|| {
+                            CacheError::Any(
+                                format!("value of key `{key}` is not an integer").into(),
+                            )
+                        })?;
+                        (expiration, current)
+                    }
+                    None => (duration.map_or(Expiration::Never, Expiration::after), 0),
+                };
+                let value = current.checked_add(delta).ok_or_else(|| {
+                    CacheError::Any(format!("increment of key `{key}` overflows").into())
+                })?;
+                Ok::<_, CacheError>(Op::Put((expiration, value.to_string().into_bytes())))
+            })?;
+
+        result
+            .into_entry()
+            .and_then(|entry| parse_integer(&entry.into_value().1))
+            .ok_or_else(|| CacheError::Any(format!("failed to increment key `{key}`").into()))
+    }
+
+    /// Atomically decrements the integer stored at the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the stored value is not an integer or the
+    /// result overflows.
+    async fn decrement(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        let delta = delta
+            .checked_neg()
+            .ok_or_else(|| CacheError::Any(format!("decrement of key `{key}` overflows").into()))?;
+        self.increment(key, delta, duration).await
+    }
+
+    /// Returns the remaining time to live of a key.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn ttl(&self, key: &str) -> CacheResult<Option<Duration>> {
+        Ok(self
+            .cache
+            .get(key)
+            .and_then(|(expiration, _)| expiration.as_duration()))
+    }
+
+    /// Sets a key to expire after the specified duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn expire(&self, key: &str, duration: Duration) -> CacheResult<bool> {
+        let result = self.cache.entry(key.to_string()).and_compute_with(|entry| {
+            entry.map_or(Op::Nop, |entry| {
+                Op::Put((Expiration::after(duration), entry.into_value().1))
+            })
+        });
+        Ok(result.into_entry().is_some())
+    }
+
+    /// Acquires the load lock of a key. Loads are only coalesced within the
+    /// process, so the lock is always acquired.
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error
+    async fn acquire_lock(&self, _key: &str) -> CacheResult<bool> {
+        Ok(true)
+    }
+
+    /// Releases the load lock of a key.
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error
+    async fn release_lock(&self, _key: &str) -> CacheResult<()> {
         Ok(())
     }
 
@@ -121,7 +368,9 @@ impl CacheDriver for Inmem {
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn remove(&self, key: &str) -> CacheResult<()> {
+        let mut tags = self.tags();
         self.cache.remove(key);
+        tags.untag(key);
         Ok(())
     }
 
@@ -131,35 +380,82 @@ impl CacheDriver for Inmem {
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn clear(&self) -> CacheResult<()> {
+        let mut tags = self.tags();
         self.cache.invalidate_all();
+        *tags = TagIndex::default();
+        Ok(())
+    }
+
+    /// Adds lookups to the counters of this cache, which only this process
+    /// shares.
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error
+    async fn add_stats(&self, stats: CacheStats) -> CacheResult<()> {
+        self.hits.fetch_add(stats.hits, Ordering::Relaxed);
+        self.misses.fetch_add(stats.misses, Ordering::Relaxed);
         Ok(())
     }
+
+    /// Returns the counters added with [`CacheDriver::add_stats`].
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error
+    async fn stats(&self) -> CacheResult<CacheStats> {
+        Ok(CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        })
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Expiration {
     Never,
     AfterDuration(Duration),
+    At(Instant),
 }
 
 impl Expiration {
+    /// Creates an expiration at the given duration from now.
+    #[must_use]
+    pub fn after(duration: Duration) -> Self {
+        Instant::now()
+            .checked_add(duration)
+            .map_or(Self::Never, Self::At)
+    }
+
+    /// Returns the remaining duration until the entry expires.
     #[must_use]
     pub fn as_duration(&self) -> Option<Duration> {
         match self {
             Self::Never => None,
             Self::AfterDuration(d) => Some(*d),
+            Self::At(instant) => Some(instant.saturating_duration_since(Instant::now())),
         }
     }
 }
 
 pub struct InMemExpiry;
 
-impl Expiry<String, (Expiration, String)> for InMemExpiry {
+impl Expiry<String, (Expiration, Vec<u8>)> for InMemExpiry {
     fn expire_after_create(
         &self,
         _key: &String,
-        value: &(Expiration, String),
+        value: &(Expiration, Vec<u8>),
+        _current_time: Instant,
+    ) -> Option<Duration> {
+        value.0.as_duration()
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &(Expiration, Vec<u8>),
         _current_time: Instant,
+        _duration_until_expiry: Option<Duration>,
     ) -> Option<Duration> {
         value.0.as_duration()
     }
@@ -214,6 +510,145 @@ mod tests {
         assert!(!mem.contains_key("key").await.unwrap());
     }
 
+    #[tokio::test]
+    async fn can_invalidate_tag() {
+        let config = create_test_config();
+        let mem = new(&config);
+
+        mem.insert_tagged("product:1", &"a", &["products", "product:1"])
+            .await
+            .unwrap();
+        mem.insert_tagged("product:2", &"b", &["products"])
+            .await
+            .unwrap();
+        mem.insert("other", &"c").await.unwrap();
+
+        mem.invalidate_tag("product:1").await.unwrap();
+        assert!(!mem.contains_key("product:1").await.unwrap());
+        assert!(mem.contains_key("product:2").await.unwrap());
+
+        mem.invalidate_tag("products").await.unwrap();
+        assert!(!mem.contains_key("product:2").await.unwrap());
+        assert!(mem.contains_key("other").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn keeps_reinserted_untagged_keys() {
+        let config = create_test_config();
+        let mem = new(&config);
+
+        mem.insert_tagged("product:1", &"a", &["products"])
+            .await
+            .unwrap();
+        mem.insert("product:1", &"b").await.unwrap();
+        mem.invalidate_tag("products").await.unwrap();
+        assert_eq!(
+            mem.get::<String>("product:1").await.unwrap(),
+            Some("b".to_string())
+        );
+
+        // re-inserting with other tags moves the key
+        mem.insert_tagged("product:2", &"a", &["products"])
+            .await
+            .unwrap();
+        mem.insert_tagged("product:2", &"b", &["featured"])
+            .await
+            .unwrap();
+        mem.invalidate_tag("products").await.unwrap();
+        assert!(mem.contains_key("product:2").await.unwrap());
+        mem.invalidate_tag("featured").await.unwrap();
+        assert!(!mem.contains_key("product:2").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn prunes_tag_index() {
+        let cache: Cache<String, (Expiration, Vec<u8>)> = Cache::builder()
+            .max_capacity(10_000)
+            .expire_after(InMemExpiry)
+            .build();
+        let mem = Inmem {
+            cache,
+            tags: Mutex::default(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        };
+
+        mem.insert_tagged("product:1", b"a", &["products"], None)
+            .await
+            .unwrap();
+        mem.remove("product:1").await.unwrap();
+        assert!(mem.tags().keys.is_empty());
+        assert!(mem.tags().tags.is_empty());
+
+        // the last insert reaches the sweep threshold
+        for i in 1..MIN_SWEEP_KEYS {
+            mem.insert_tagged(
+                &format!("product:{i}"),
+                b"a",
+                &["products"],
+                Some(Duration::from_millis(1)),
+            )
+            .await
+            .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        mem.insert_tagged("other", b"b", &["products"], None)
+            .await
+            .unwrap();
+        assert_eq!(mem.tags().keys.len(), 1);
+        assert_eq!(mem.tags().tags["products"].len(), 1);
+    }
+
+    #[tokio::test]
+    async fn can_increment_and_decrement() {
+        let config = create_test_config();
+        let mem = new(&config);
+
+        assert_eq!(mem.increment("views", 1, None).await.unwrap(), 1);
+        assert_eq!(mem.increment("views", 5, None).await.unwrap(), 6);
+        assert_eq!(mem.decrement("views", 2, None).await.unwrap(), 4);
+        assert_eq!(mem.get::<i64>("views").await.unwrap(), Some(4));
+
+        mem.insert("name", &"loco").await.unwrap();
+        assert!(mem.increment("name", 1, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_set_expiry_on_first_increment() {
+        let config = create_test_config();
+        let mem = new(&config);
+
+        mem.increment("rate", 1, Some(Duration::from_secs(60)))
+            .await
+            .unwrap();
+        let ttl = mem.ttl("rate").await.unwrap().unwrap();
+        assert!(ttl <= Duration::from_secs(60) && ttl > Duration::from_secs(55));
+
+        // later increments keep the original expiry
+        mem.increment("rate", 1, Some(Duration::from_secs(600)))
+            .await
+            .unwrap();
+        assert!(mem.ttl("rate").await.unwrap().unwrap() <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn can_get_ttl_and_expire() {
+        let config = create_test_config();
+        let mem = new(&config);
+
+        assert_eq!(mem.ttl("key").await.unwrap(), None);
+        assert!(!mem.expire("key", Duration::from_secs(10)).await.unwrap());
+
+        mem.insert("key", &"loco").await.unwrap();
+        assert_eq!(mem.ttl("key").await.unwrap(), None);
+
+        assert!(mem.expire("key", Duration::from_millis(100)).await.unwrap());
+        assert!(mem.ttl("key").await.unwrap().unwrap() <= Duration::from_millis(100));
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(!mem.contains_key("key").await.unwrap());
+    }
+
     #[tokio::test]
     async fn can_clear() {
         let config = create_test_config();
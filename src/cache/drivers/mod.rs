@@ -5,13 +5,17 @@ use std::time::Duration;
 
 use async_trait::async_trait;
 
-use super::CacheResult;
+use super::{CacheResult, CacheStats};
 
+#[cfg(feature = "with-db")]
+pub mod database;
 #[cfg(feature = "cache_inmem")]
 pub mod inmem;
 pub mod null;
 #[cfg(feature = "cache_redis")]
 pub mod redis;
+#[cfg(all(feature = "cache_inmem", feature = "cache_redis"))]
+pub mod tiered;
 
 /// Trait representing a cache driver.
 #[async_trait]
@@ -38,7 +42,7 @@ pub trait CacheDriver: Sync + Send {
     ///
     /// Returns a [`super::CacheError`] if there is an error during the
     /// operation.
-    async fn get(&self, key: &str) -> CacheResult<Option<String>>;
+    async fn get(&self, key: &str) -> CacheResult<Option<Vec<u8>>>;
 
     /// Inserts a key-value pair into the cache.
     ///
@@ -46,7 +50,7 @@ pub trait CacheDriver: Sync + Send {
     ///
     /// Returns a [`super::CacheError`] if there is an error during the
     /// operation.
-    async fn insert(&self, key: &str, value: &str) -> CacheResult<()>;
+    async fn insert(&self, key: &str, value: &[u8]) -> CacheResult<()>;
 
     /// Inserts a key-value pair into the cache that expires after the
     /// specified duration.
@@ -58,10 +62,103 @@ pub trait CacheDriver: Sync + Send {
     async fn insert_with_expiry(
         &self,
         key: &str,
-        value: &str,
+        value: &[u8],
         duration: Duration,
     ) -> CacheResult<()>;
 
+    /// Inserts a key-value pair into the cache and associates it with the
+    /// given tags, optionally expiring after the specified duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn insert_tagged(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()>;
+
+    /// Removes all key-value pairs associated with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()>;
+
+    /// Atomically increments the integer stored at the key by `delta` and
+    /// returns the new value. A missing key is created with the value `delta`
+    /// and, when provided, expires after the given duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if the stored value is not an integer
+    /// or there is an error during the operation.
+    async fn increment(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64>;
+
+    /// Atomically decrements the integer stored at the key by `delta` and
+    /// returns the new value. A missing key is created with the value `-delta`
+    /// and, when provided, expires after the given duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if the stored value is not an integer
+    /// or there is an error during the operation.
+    async fn decrement(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64>;
+
+    /// Returns the remaining time to live of a key, or `None` when the key
+    /// does not exist or has no expiry.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn ttl(&self, key: &str) -> CacheResult<Option<Duration>>;
+
+    /// Sets a key to expire after the specified duration. Returns `false` when
+    /// the key does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn expire(&self, key: &str, duration: Duration) -> CacheResult<bool>;
+
+    /// Acquires the lock used to coalesce loads of the given key across
+    /// instances, waiting while another instance holds it.
+    ///
+    /// Returns `true` when the lock was acquired and must be released with
+    /// [`Self::release_lock`], or `false` when another instance held it until
+    /// it was released or the wait timed out. Drivers without distributed
+    /// locking always return `true`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn acquire_lock(&self, key: &str) -> CacheResult<bool>;
+
+    /// Releases a lock acquired with [`Self::acquire_lock`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn release_lock(&self, key: &str) -> CacheResult<()>;
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
@@ -77,4 +174,89 @@ pub trait CacheDriver: Sync + Send {
     /// Returns a [`super::CacheError`] if there is an error during the
     /// operation.
     async fn clear(&self) -> CacheResult<()>;
+
+    /// Retrieves the values of several keys, in the order of the keys.
+    ///
+    /// The default implementation gets the keys one by one, drivers should
+    /// override it when they can batch the lookups.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn get_many(&self, keys: &[&str]) -> CacheResult<Vec<Option<Vec<u8>>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        for key in keys {
+            values.push(self.get(key).await?);
+        }
+        Ok(values)
+    }
+
+    /// Inserts several key-value pairs, optionally expiring after the
+    /// specified duration.
+    ///
+    /// The default implementation inserts the pairs one by one, drivers
+    /// should override it when they can batch the writes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn insert_many(
+        &self,
+        entries: &[(&str, &[u8])],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        for (key, value) in entries {
+            match duration {
+                Some(duration) => self.insert_with_expiry(key, value, duration).await?,
+                None => self.insert(key, value).await?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes several keys.
+    ///
+    /// The default implementation removes the keys one by one, drivers
+    /// should override it when they can batch the deletes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn remove_many(&self, keys: &[&str]) -> CacheResult<()> {
+        for key in keys {
+            self.remove(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Adds lookups to the hit and miss counters shared by every process
+    /// using the cache. The counters are kept apart from the cached entries,
+    /// so clearing the cache keeps them.
+    ///
+    /// The default implementation does not keep shared counters.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn add_stats(&self, _stats: CacheStats) -> CacheResult<()> {
+        Ok(())
+    }
+
+    /// Returns the hit and miss counters shared by every process using the
+    /// cache.
+    ///
+    /// The default implementation does not keep shared counters, and returns
+    /// zero.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`super::CacheError`] if there is an error during the
+    /// operation.
+    async fn stats(&self) -> CacheResult<CacheStats> {
+        Ok(CacheStats::default())
+    }
 }
@@ -54,7 +54,7 @@ impl CacheDriver for Null {
     /// # Errors
     ///
     /// Returns always error
-    async fn get(&self, _key: &str) -> CacheResult<Option<String>> {
+    async fn get(&self, _key: &str) -> CacheResult<Option<Vec<u8>>> {
         Ok(None)
     }
 
@@ -63,7 +63,7 @@ impl CacheDriver for Null {
     /// # Errors
     ///
     /// Returns always error
-    async fn insert(&self, _key: &str, _value: &str) -> CacheResult<()> {
+    async fn insert(&self, _key: &str, _value: &[u8]) -> CacheResult<()> {
         Err(CacheError::Any(
             "Operation not supported by null cache".into(),
         ))
@@ -78,7 +78,7 @@ impl CacheDriver for Null {
     async fn insert_with_expiry(
         &self,
         _key: &str,
-        _value: &str,
+        _value: &[u8],
         _duration: Duration,
     ) -> CacheResult<()> {
         Err(CacheError::Any(
@@ -86,6 +86,105 @@ impl CacheDriver for Null {
         ))
     }
 
+    /// Inserts a key-value pair associated with the given tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn insert_tagged(
+        &self,
+        _key: &str,
+        _value: &[u8],
+        _tags: &[&str],
+        _duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
+    /// Removes all key-value pairs associated with the given tag.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn invalidate_tag(&self, _tag: &str) -> CacheResult<()> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
+    /// Increments the integer stored at the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn increment(
+        &self,
+        _key: &str,
+        _delta: i64,
+        _duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
+    /// Decrements the integer stored at the key.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn decrement(
+        &self,
+        _key: &str,
+        _delta: i64,
+        _duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
+    /// Returns the remaining time to live of a key.
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error, the null cache holds no keys
+    async fn ttl(&self, _key: &str) -> CacheResult<Option<Duration>> {
+        Ok(None)
+    }
+
+    /// Sets a key to expire after the specified duration.
+    ///
+    /// # Errors
+    ///
+    /// Returns always error
+    async fn expire(&self, _key: &str, _duration: Duration) -> CacheResult<bool> {
+        Err(CacheError::Any(
+            "Operation not supported by null cache".into(),
+        ))
+    }
+
+    /// Acquires the load lock of a key. Loads are only coalesced within the
+    /// process, so the lock is always acquired.
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error
+    async fn acquire_lock(&self, _key: &str) -> CacheResult<bool> {
+        Ok(true)
+    }
+
+    /// Releases the load lock of a key.
+    ///
+    /// # Errors
+    ///
+    /// Never returns an error
+    async fn release_lock(&self, _key: &str) -> CacheResult<()> {
+        Ok(())
+    }
+
     /// Removes a key-value pair from the cache.
     ///
     /// # Errors
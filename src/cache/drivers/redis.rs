@@ -1,19 +1,128 @@
 //! # Redis Cache Driver
 //!
 //! This module implements a cache driver using Redis.
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use bb8::Pool;
 use bb8_redis::{
     bb8,
-    redis::{cmd, AsyncCommands},
+    redis::{self, cmd, AsyncCommands},
     RedisConnectionManager,
 };
 
 use super::CacheDriver;
-use crate::cache::{CacheError, CacheResult};
-use crate::config::RedisCacheConfig;
+use crate::cache::{CacheError, CacheResult, CacheStats};
+use crate::config::{RedisCacheConfig, RedisCacheLockConfig};
+
+/// Prefix of the Redis sets holding the keys associated with a tag.
+const TAG_KEY_PREFIX: &str = "loco:cache:tag:";
+
+/// Prefix of the Redis sets holding the tag sets a key was inserted in, so
+/// it can be removed from them when it is inserted again.
+const KEY_TAGS_PREFIX: &str = "loco:cache:key-tags:";
+
+/// Prefix of the Redis keys used as load locks.
+const LOCK_KEY_PREFIX: &str = "loco:cache:lock:";
+
+/// Hash holding the hit and miss counters shared by every process. A hash
+/// can't be read as a cached value, and [`Redis::clear`] keeps it.
+const STATS_KEY: &str = "loco:cache:stats";
+
+/// Flushes the database, keeping the shared counters in `KEYS[1]`.
+const CLEAR_SCRIPT: &str = r"
+local stats = redis.call('HGETALL', KEYS[1])
+redis.call('FLUSHDB')
+if #stats > 0 then
+    redis.call('HSET', KEYS[1], unpack(stats))
+end
+";
+
+/// How often to check whether a lock held by another instance was released.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Deletes a lock only when it is still held by the given token.
+const RELEASE_LOCK_SCRIPT: &str = r"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+";
+
+/// Increments a key and sets its expiry only when the increment created it.
+const INCREMENT_WITH_EXPIRY_SCRIPT: &str = r"
+local created = redis.call('EXISTS', KEYS[1]) == 0
+local value = redis.call('INCRBY', KEYS[1], ARGV[1])
+if created then
+    redis.call('PEXPIRE', KEYS[1], ARGV[2])
+end
+return value
+";
+
+/// Inserts a key, removes it from the tag sets of its previous insert, and
+/// adds it to the set of each tag. A tag set lives as long as the longest
+/// lived of its keys, so sets of expiring keys expire too.
+///
+/// `KEYS[1]` is the key, `KEYS[2]` the set of its tag sets and the other keys
+/// the tag sets, `ARGV[1]` is the value and `ARGV[2]` the time to live in
+/// milliseconds, `0` for none.
+const INSERT_SCRIPT: &str = r"
+local ttl = tonumber(ARGV[2])
+for _, tag in ipairs(redis.call('SMEMBERS', KEYS[2])) do
+    redis.call('SREM', tag, KEYS[1])
+end
+redis.call('DEL', KEYS[2])
+if ttl > 0 then
+    redis.call('SET', KEYS[1], ARGV[1], 'PX', ttl)
+else
+    redis.call('SET', KEYS[1], ARGV[1])
+end
+for i = 3, #KEYS do
+    local existed = redis.call('EXISTS', KEYS[i]) == 1
+    local tag_ttl = redis.call('PTTL', KEYS[i])
+    redis.call('SADD', KEYS[i], KEYS[1])
+    redis.call('SADD', KEYS[2], KEYS[i])
+    if ttl == 0 then
+        redis.call('PERSIST', KEYS[i])
+    elseif not existed or (tag_ttl >= 0 and tag_ttl < ttl) then
+        redis.call('PEXPIRE', KEYS[i], ttl)
+    end
+end
+if #KEYS > 2 and ttl > 0 then
+    redis.call('PEXPIRE', KEYS[2], ttl)
+end
+";
+
+/// Deletes the keys of a tag set and the set, atomically so keys tagged
+/// concurrently are either deleted or added to a new set. Keys which expired
+/// and were inserted again without the tag are kept.
+///
+/// `KEYS[1]` is the tag set and `ARGV[1]` the prefix of the sets of tag sets.
+const INVALIDATE_TAG_SCRIPT: &str = r"
+for _, key in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    local tags = ARGV[1] .. key
+    if redis.call('SISMEMBER', tags, KEYS[1]) == 1 then
+        redis.call('DEL', key, tags)
+    end
+end
+redis.call('DEL', KEYS[1])
+";
+
+pub(crate) fn tag_key(tag: &str) -> String {
+    format!("{TAG_KEY_PREFIX}{tag}")
+}
+
+fn key_tags_key(key: &str) -> String {
+    format!("{KEY_TAGS_PREFIX}{key}")
+}
+
+fn lock_key(key: &str) -> String {
+    format!("{LOCK_KEY_PREFIX}{key}")
+}
+
+fn duration_millis(duration: Duration) -> i64 {
+    i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
 
 /// Creates a new instance of the Redis cache driver with a default configuration.
 ///
@@ -25,19 +134,32 @@ use crate::config::RedisCacheConfig;
 ///
 /// Returns a `CacheError` if there is an error connecting to Redis.
 pub async fn new(config: &RedisCacheConfig) -> CacheResult<crate::cache::Cache> {
+    let pool = create_pool(config).await?;
+    Ok(crate::cache::Cache::new(Redis::from_config(pool, config)).with_encoding(&config.encoding))
+}
+
+/// Creates a Redis connection pool from the given configuration.
+///
+/// # Errors
+///
+/// Returns a `CacheError` if there is an error connecting to Redis.
+pub(crate) async fn create_pool(
+    config: &RedisCacheConfig,
+) -> CacheResult<Pool<RedisConnectionManager>> {
     let manager = RedisConnectionManager::new(config.uri.clone())?;
-    let pool = Pool::builder()
+    Ok(Pool::builder()
         .max_size(config.max_size)
         .build(manager)
-        .await?;
-
-    Ok(crate::cache::Cache::new(Redis::from(pool)))
+        .await?)
 }
 
 /// Represents the Redis cache driver.
 #[derive(Clone, Debug)]
 pub struct Redis {
     pool: Pool<RedisConnectionManager>,
+    lock: Option<RedisCacheLockConfig>,
+    /// Identifies the locks held by this driver instance.
+    lock_token: String,
 }
 
 impl Redis {
@@ -48,8 +170,63 @@ impl Redis {
     /// A boxed [`CacheDriver`] instance.
     #[must_use]
     pub fn from(pool: Pool<RedisConnectionManager>) -> Box<dyn CacheDriver> {
-        Box::new(Self { pool })
+        Box::new(Self::new(pool, None))
+    }
+
+    /// Constructs a new [`Redis`] instance from a given connection pool and
+    /// the driver options of the given configuration.
+    ///
+    /// # Returns
+    ///
+    /// A boxed [`CacheDriver`] instance.
+    #[must_use]
+    pub fn from_config(
+        pool: Pool<RedisConnectionManager>,
+        config: &RedisCacheConfig,
+    ) -> Box<dyn CacheDriver> {
+        Box::new(Self::new(pool, config.lock.clone()))
+    }
+
+    fn new(pool: Pool<RedisConnectionManager>, lock: Option<RedisCacheLockConfig>) -> Self {
+        Self {
+            pool,
+            lock,
+            lock_token: uuid::Uuid::new_v4().to_string(),
+        }
+    }
+
+    /// Inserts a key with its tags, replacing the tags of its previous
+    /// insert, in a single script.
+    async fn set(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let mut conn = self.pool.get().await?;
+        insert_script(key, value, tags, duration)
+            .query_async::<()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Returns the [`INSERT_SCRIPT`] call inserting a key with its tags.
+fn insert_script(key: &str, value: &[u8], tags: &[&str], duration: Option<Duration>) -> redis::Cmd {
+    let mut script = cmd("EVAL");
+    script
+        .arg(INSERT_SCRIPT)
+        .arg(tags.len() + 2)
+        .arg(key)
+        .arg(key_tags_key(key));
+    for tag in tags {
+        script.arg(tag_key(tag));
     }
+    // a zero duration would be stored without expiry
+    let ttl = duration.map_or(0, |duration| duration_millis(duration).max(1));
+    script.arg(value).arg(ttl);
+    script
 }
 
 #[async_trait]
@@ -82,9 +259,9 @@ impl CacheDriver for Redis {
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
-    async fn get(&self, key: &str) -> CacheResult<Option<String>> {
+    async fn get(&self, key: &str) -> CacheResult<Option<Vec<u8>>> {
         let mut conn = self.pool.get().await?;
-        let result: Option<String> = conn.get(key).await?;
+        let result: Option<Vec<u8>> = conn.get(key).await?;
         Ok(result)
     }
 
@@ -93,10 +270,8 @@ impl CacheDriver for Redis {
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
-    async fn insert(&self, key: &str, value: &str) -> CacheResult<()> {
-        let mut conn = self.pool.get().await?;
-        conn.set::<_, _, ()>(key, value).await?;
-        Ok(())
+    async fn insert(&self, key: &str, value: &[u8]) -> CacheResult<()> {
+        self.set(key, value, &[], None).await
     }
 
     /// Inserts a key-value pair into the cache that expires after the specified
@@ -109,12 +284,172 @@ impl CacheDriver for Redis {
     async fn insert_with_expiry(
         &self,
         key: &str,
-        value: &str,
+        value: &[u8],
         duration: Duration,
     ) -> CacheResult<()> {
+        self.set(key, value, &[], Some(duration)).await
+    }
+
+    /// Inserts a key-value pair into the cache and adds the key to a Redis set
+    /// per tag, in a single script. Tag sets expire with their keys.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_tagged(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        self.set(key, value, tags, duration).await
+    }
+
+    /// Removes all key-value pairs associated with the given tag, along with
+    /// the tag set itself, in a single script.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let mut conn = self.pool.get().await?;
+        cmd("EVAL")
+            .arg(INVALIDATE_TAG_SCRIPT)
+            .arg(1)
+            .arg(tag_key(tag))
+            .arg(KEY_TAGS_PREFIX)
+            .query_async::<()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Atomically increments the integer stored at the key using `INCRBY`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the stored value is not an integer or there
+    /// is an error during the operation.
+    async fn increment(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
         let mut conn = self.pool.get().await?;
-        // Redis expects the expiry in seconds as a u64
-        conn.set_ex::<_, _, ()>(key, value, duration.as_secs())
+        match duration {
+            Some(duration) => Ok(cmd("EVAL")
+                .arg(INCREMENT_WITH_EXPIRY_SCRIPT)
+                .arg(1)
+                .arg(key)
+                .arg(delta)
+                .arg(duration_millis(duration))
+                .query_async(&mut *conn)
+                .await?),
+            None => Ok(conn.incr(key, delta).await?),
+        }
+    }
+
+    /// Atomically decrements the integer stored at the key using `DECRBY`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if the stored value is not an integer or there
+    /// is an error during the operation.
+    async fn decrement(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        match duration {
+            Some(_) => {
+                let delta = delta.checked_neg().ok_or_else(|| {
+                    CacheError::Any(format!("decrement of key `{key}` overflows").into())
+                })?;
+                self.increment(key, delta, duration).await
+            }
+            None => {
+                let mut conn = self.pool.get().await?;
+                Ok(conn.decr(key, delta).await?)
+            }
+        }
+    }
+
+    /// Returns the remaining time to live of a key using `PTTL`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn ttl(&self, key: &str) -> CacheResult<Option<Duration>> {
+        let mut conn = self.pool.get().await?;
+        // PTTL returns -2 when the key does not exist and -1 when it has no
+        // expiry
+        let millis: i64 = conn.pttl(key).await?;
+        Ok(u64::try_from(millis).ok().map(Duration::from_millis))
+    }
+
+    /// Sets a key to expire after the specified duration using `PEXPIRE`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn expire(&self, key: &str, duration: Duration) -> CacheResult<bool> {
+        let mut conn = self.pool.get().await?;
+        Ok(conn.pexpire(key, duration_millis(duration)).await?)
+    }
+
+    /// Acquires the load lock of a key with `SET NX`, waiting while another
+    /// instance holds it. Always acquired when no lock is configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn acquire_lock(&self, key: &str) -> CacheResult<bool> {
+        let Some(lock) = &self.lock else {
+            return Ok(true);
+        };
+        let lock_key = lock_key(key);
+
+        let mut conn = self.pool.get().await?;
+        let acquired: Option<String> = cmd("SET")
+            .arg(&lock_key)
+            .arg(&self.lock_token)
+            .arg("NX")
+            .arg("PX")
+            .arg(lock.ttl)
+            .query_async(&mut *conn)
+            .await?;
+        if acquired.is_some() {
+            return Ok(true);
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(lock.wait);
+        while Instant::now() < deadline {
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+            if !conn.exists::<_, bool>(&lock_key).await? {
+                break;
+            }
+        }
+        Ok(false)
+    }
+
+    /// Releases the load lock of a key when it is still held by this driver.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn release_lock(&self, key: &str) -> CacheResult<()> {
+        if self.lock.is_none() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        cmd("EVAL")
+            .arg(RELEASE_LOCK_SCRIPT)
+            .arg(1)
+            .arg(lock_key(key))
+            .arg(&self.lock_token)
+            .query_async::<()>(&mut *conn)
             .await?;
         Ok(())
     }
@@ -126,20 +461,116 @@ impl CacheDriver for Redis {
     /// Returns a `CacheError` if there is an error during the operation.
     async fn remove(&self, key: &str) -> CacheResult<()> {
         let mut conn = self.pool.get().await?;
-        conn.del::<_, ()>(key).await?;
+        conn.del::<_, ()>(&[key.to_string(), key_tags_key(key)])
+            .await?;
         Ok(())
     }
 
-    /// Clears all key-value pairs from the cache.
+    /// Clears all key-value pairs from the cache, keeping the shared hit and
+    /// miss counters.
     ///
     /// # Errors
     ///
     /// Returns a `CacheError` if there is an error during the operation.
     async fn clear(&self) -> CacheResult<()> {
         let mut conn = self.pool.get().await?;
-        cmd("FLUSHDB").query_async::<()>(&mut *conn).await?;
+        cmd("EVAL")
+            .arg(CLEAR_SCRIPT)
+            .arg(1)
+            .arg(STATS_KEY)
+            .query_async::<()>(&mut *conn)
+            .await?;
+        Ok(())
+    }
+
+    /// Retrieves the values of several keys with a single `MGET`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn get_many(&self, keys: &[&str]) -> CacheResult<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = self.pool.get().await?;
+        Ok(cmd("MGET").arg(keys).query_async(&mut *conn).await?)
+    }
+
+    /// Inserts several key-value pairs in a single pipeline of scripts.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_many(
+        &self,
+        entries: &[(&str, &[u8])],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        let mut pipe = redis::pipe();
+        for (key, value) in entries {
+            pipe.add_command(insert_script(key, value, &[], duration))
+                .ignore();
+        }
+        pipe.query_async::<()>(&mut *conn).await?;
+        Ok(())
+    }
+
+    /// Removes several keys with a single `DEL`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove_many(&self, keys: &[&str]) -> CacheResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.pool.get().await?;
+        let keys = keys
+            .iter()
+            .flat_map(|key| [(*key).to_string(), key_tags_key(key)])
+            .collect::<Vec<_>>();
+        conn.del::<_, ()>(keys).await?;
+        Ok(())
+    }
+
+    /// Adds lookups to the shared counters with `HINCRBY`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn add_stats(&self, stats: CacheStats) -> CacheResult<()> {
+        let mut conn = self.pool.get().await?;
+        redis::pipe()
+            .hincr(STATS_KEY, "hits", stats.hits)
+            .ignore()
+            .hincr(STATS_KEY, "misses", stats.misses)
+            .ignore()
+            .query_async::<()>(&mut *conn)
+            .await?;
         Ok(())
     }
+
+    /// Returns the shared counters.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn stats(&self) -> CacheResult<CacheStats> {
+        let mut conn = self.pool.get().await?;
+        let (hits, misses): (Option<u64>, Option<u64>) = redis::pipe()
+            .hget(STATS_KEY, "hits")
+            .hget(STATS_KEY, "misses")
+            .query_async(&mut *conn)
+            .await?;
+        Ok(CacheStats {
+            hits: hits.unwrap_or_default(),
+            misses: misses.unwrap_or_default(),
+        })
+    }
 }
 
 #[cfg(test)]
@@ -156,6 +587,8 @@ mod tests {
         let redis_config = crate::config::RedisCacheConfig {
             uri: redis_url,
             max_size: 10,
+            lock: None,
+            encoding: crate::config::CacheEncodingConfig::default(),
         };
 
         let cache = new(&redis_config)
@@ -185,7 +618,7 @@ mod tests {
             .expect("Failed to check if key exists"));
 
         redis
-            .insert("test_key", "test_value")
+            .insert("test_key", b"test_value")
             .await
             .expect("Failed to insert key");
 
@@ -200,7 +633,7 @@ mod tests {
         let (redis, _container) = setup_redis_driver().await;
 
         redis
-            .insert("test_key", "test_value")
+            .insert("test_key", b"test_value")
             .await
             .expect("Failed to insert key");
 
@@ -209,7 +642,7 @@ mod tests {
                 .get("test_key")
                 .await
                 .expect("Failed to get value for key"),
-            Some("test_value".to_string())
+            Some(b"test_value".to_vec())
         );
 
         assert_eq!(
@@ -226,7 +659,7 @@ mod tests {
         let (redis, _container) = setup_redis_driver().await;
 
         redis
-            .insert("test_key", "test_value")
+            .insert("test_key", b"test_value")
             .await
             .expect("Failed to insert key");
 
@@ -253,7 +686,7 @@ mod tests {
         let keys = vec!["key1", "key2", "key3"];
         for key in &keys {
             redis
-                .insert(key, "test_value")
+                .insert(key, b"test_value")
                 .await
                 .expect("Failed to insert key");
         }
@@ -275,12 +708,269 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_invalidate_tag() {
+        let (redis, _container) = setup_redis_driver().await;
+
+        redis
+            .insert_tagged("product:1", b"a", &["products", "product:1"], None)
+            .await
+            .expect("Failed to insert tagged key");
+        redis
+            .insert_tagged(
+                "product:2",
+                b"b",
+                &["products"],
+                Some(Duration::from_secs(60)),
+            )
+            .await
+            .expect("Failed to insert tagged key with expiry");
+        redis
+            .insert("other", b"c")
+            .await
+            .expect("Failed to insert key");
+
+        redis
+            .invalidate_tag("product:1")
+            .await
+            .expect("Failed to invalidate tag");
+        assert!(!redis.contains_key("product:1").await.unwrap());
+        assert!(redis.contains_key("product:2").await.unwrap());
+
+        redis
+            .invalidate_tag("products")
+            .await
+            .expect("Failed to invalidate tag");
+        assert!(!redis.contains_key("product:2").await.unwrap());
+        assert!(redis.contains_key("other").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_keeps_reinserted_untagged_keys() {
+        let (redis, _container) = setup_redis_driver().await;
+
+        redis
+            .insert_tagged("product:1", b"a", &["products"], None)
+            .await
+            .unwrap();
+        redis.insert("product:1", b"b").await.unwrap();
+        redis.invalidate_tag("products").await.unwrap();
+        assert_eq!(redis.get("product:1").await.unwrap(), Some(b"b".to_vec()));
+
+        // re-inserting with other tags moves the key
+        redis
+            .insert_tagged("product:2", b"a", &["products"], None)
+            .await
+            .unwrap();
+        redis
+            .insert_tagged("product:2", b"b", &["featured"], None)
+            .await
+            .unwrap();
+        redis.invalidate_tag("products").await.unwrap();
+        assert!(redis.contains_key("product:2").await.unwrap());
+        redis.invalidate_tag("featured").await.unwrap();
+        assert!(!redis.contains_key("product:2").await.unwrap());
+
+        // a key inserted again after expiring keeps out of its old tag set
+        redis
+            .insert_tagged(
+                "product:3",
+                b"a",
+                &["products"],
+                Some(Duration::from_millis(100)),
+            )
+            .await
+            .unwrap();
+        redis
+            .insert_tagged("product:4", b"d", &["products"], None)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        redis.insert("product:3", b"b").await.unwrap();
+        redis.invalidate_tag("products").await.unwrap();
+        assert!(redis.contains_key("product:3").await.unwrap());
+        assert!(!redis.contains_key("product:4").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_tag_sets_expire_with_their_keys() {
+        let (redis_url, _container) = setup_redis_container().await;
+        let pool = create_pool(&crate::config::RedisCacheConfig {
+            uri: redis_url,
+            max_size: 10,
+            lock: None,
+            encoding: crate::config::CacheEncodingConfig::default(),
+        })
+        .await
+        .expect("Failed to create Redis pool");
+        let redis = Redis::new(pool.clone(), None);
+        let tag_ttl = || async {
+            let mut conn = pool.get().await.unwrap();
+            conn.pttl::<_, i64>(tag_key("products")).await.unwrap()
+        };
+
+        redis
+            .insert_tagged(
+                "product:1",
+                b"a",
+                &["products"],
+                Some(Duration::from_secs(10)),
+            )
+            .await
+            .unwrap();
+        let ttl = tag_ttl().await;
+        assert!(ttl > 9_000 && ttl <= 10_000);
+
+        // the set lives as long as its longest lived key
+        redis
+            .insert_tagged(
+                "product:2",
+                b"b",
+                &["products"],
+                Some(Duration::from_secs(60)),
+            )
+            .await
+            .unwrap();
+        assert!(tag_ttl().await > 50_000);
+        redis
+            .insert_tagged(
+                "product:3",
+                b"c",
+                &["products"],
+                Some(Duration::from_secs(5)),
+            )
+            .await
+            .unwrap();
+        assert!(tag_ttl().await > 50_000);
+
+        redis
+            .insert_tagged("product:4", b"d", &["products"], None)
+            .await
+            .unwrap();
+        assert_eq!(tag_ttl().await, -1);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_stats_on_clear() {
+        let (redis, _container) = setup_redis_driver().await;
+
+        redis
+            .add_stats(CacheStats { hits: 2, misses: 1 })
+            .await
+            .unwrap();
+        redis
+            .add_stats(CacheStats { hits: 1, misses: 0 })
+            .await
+            .unwrap();
+        redis.insert("key", b"value").await.unwrap();
+        redis.clear().await.unwrap();
+
+        assert!(!redis.contains_key("key").await.unwrap());
+        assert_eq!(
+            redis.stats().await.unwrap(),
+            CacheStats { hits: 3, misses: 1 }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_increment_and_decrement() {
+        let (redis, _container) = setup_redis_driver().await;
+
+        assert_eq!(redis.increment("views", 1, None).await.unwrap(), 1);
+        assert_eq!(redis.increment("views", 5, None).await.unwrap(), 6);
+        assert_eq!(redis.decrement("views", 2, None).await.unwrap(), 4);
+        assert_eq!(redis.get("views").await.unwrap(), Some(b"4".to_vec()));
+
+        assert_eq!(
+            redis
+                .increment("rate", 1, Some(Duration::from_secs(60)))
+                .await
+                .unwrap(),
+            1
+        );
+        let ttl = redis.ttl("rate").await.unwrap().unwrap();
+        assert!(ttl <= Duration::from_secs(60) && ttl > Duration::from_secs(55));
+
+        // later increments keep the original expiry
+        redis
+            .increment("rate", 1, Some(Duration::from_secs(600)))
+            .await
+            .unwrap();
+        assert!(redis.ttl("rate").await.unwrap().unwrap() <= Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_ttl_and_expire() {
+        let (redis, _container) = setup_redis_driver().await;
+
+        assert_eq!(redis.ttl("key").await.unwrap(), None);
+        assert!(!redis.expire("key", Duration::from_secs(10)).await.unwrap());
+
+        redis.insert("key", b"value").await.unwrap();
+        assert_eq!(redis.ttl("key").await.unwrap(), None);
+
+        assert!(redis.expire("key", Duration::from_secs(10)).await.unwrap());
+        assert!(redis.ttl("key").await.unwrap().unwrap() <= Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn test_bulk_operations() {
+        let (cache, _container) = setup_redis_driver().await;
+
+        cache
+            .insert_many(
+                &[
+                    ("key1", b"value1".as_slice()),
+                    ("key2", b"value2".as_slice()),
+                ],
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            cache.get_many(&["key1", "missing", "key2"]).await.unwrap(),
+            vec![Some(b"value1".to_vec()), None, Some(b"value2".to_vec())]
+        );
+
+        cache.remove_many(&["key1", "key2"]).await.unwrap();
+        assert_eq!(
+            cache.get_many(&["key1", "key2"]).await.unwrap(),
+            vec![None, None]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_distributed_lock() {
+        let (redis_url, _container) = setup_redis_container().await;
+        let redis_config = crate::config::RedisCacheConfig {
+            uri: redis_url,
+            max_size: 10,
+            lock: Some(RedisCacheLockConfig {
+                ttl: 10_000,
+                wait: 200,
+            }),
+            encoding: crate::config::CacheEncodingConfig::default(),
+        };
+        let first = new(&redis_config).await.unwrap().driver;
+        let second = new(&redis_config).await.unwrap().driver;
+
+        assert!(first.acquire_lock("key").await.unwrap());
+        // held by the first instance until the wait times out
+        assert!(!second.acquire_lock("key").await.unwrap());
+        // only the holder can release it
+        second.release_lock("key").await.unwrap();
+        assert!(!second.acquire_lock("key").await.unwrap());
+
+        first.release_lock("key").await.unwrap();
+        assert!(second.acquire_lock("key").await.unwrap());
+    }
+
     #[tokio::test]
     async fn test_expiry() {
         let (redis, _container) = setup_redis_driver().await;
 
         redis
-            .insert_with_expiry("expiring_key", "test_value", Duration::from_secs(1))
+            .insert_with_expiry("expiring_key", b"test_value", Duration::from_secs(1))
             .await
             .expect("Failed to insert key with expiry");
 
@@ -0,0 +1,658 @@
+//! # Tiered Cache Driver
+//!
+//! This module implements a two-tier cache driver: a bounded in-memory (L1)
+//! cache with a short time to live in front of Redis (L2). Reads are served
+//! from L1 when possible, writes go to Redis and evict the key from L1.
+//!
+//! When an invalidation channel is configured, every write is published over
+//! Redis pub/sub so other instances evict the key from their own L1.
+//!
+//! Entries may be served from L1 for up to the configured L1 time to live
+//! after another instance changed them when no invalidation channel is set.
+//! An entry never outlives its remaining time to live in Redis.
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use bb8::Pool;
+use bb8_redis::{
+    bb8,
+    redis::{self, AsyncCommands},
+    RedisConnectionManager,
+};
+use futures_util::StreamExt;
+use moka::{sync::Cache, Expiry};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use uuid::Uuid;
+
+use super::{redis::Redis, CacheDriver};
+use crate::cache::{CacheResult, CacheStats};
+use crate::config::TieredCacheConfig;
+
+/// Creates a new instance of the tiered cache driver.
+///
+/// # Returns
+///
+/// A [`crate::cache::Cache`] instance.
+///
+/// # Errors
+///
+/// Returns a `CacheError` if there is an error connecting to Redis.
+pub async fn new(config: &TieredCacheConfig) -> CacheResult<crate::cache::Cache> {
+    let pool = super::redis::create_pool(&config.redis).await?;
+    let l1 = Cache::builder()
+        .max_capacity(config.l1_max_capacity)
+        .expire_after(L1Expiry {
+            ttl: Duration::from_millis(config.l1_ttl),
+        })
+        .build();
+
+    let mut tiered = Tiered {
+        l1,
+        l2: Redis::from_config(pool.clone(), &config.redis),
+        pool,
+        invalidation: None,
+    };
+
+    if let Some(channel) = &config.invalidation_channel {
+        let client = redis::Client::open(config.redis.uri.clone())?;
+        let origin = Uuid::new_v4();
+        let subscriber = tokio::spawn(subscribe(
+            client,
+            channel.clone(),
+            tiered.l1.clone(),
+            origin,
+        ));
+        tiered.invalidation = Some(Invalidation {
+            channel: channel.clone(),
+            origin,
+            subscriber,
+        });
+    }
+
+    Ok(crate::cache::Cache::new(Box::new(tiered)).with_encoding(&config.redis.encoding))
+}
+
+/// Represents the tiered cache driver.
+pub struct Tiered {
+    l1: Cache<String, L1Entry>,
+    l2: Box<dyn CacheDriver>,
+    pool: Pool<RedisConnectionManager>,
+    invalidation: Option<Invalidation>,
+}
+
+/// A value held in L1, with its remaining time to live in Redis when it was
+/// read.
+#[derive(Clone, Debug)]
+struct L1Entry {
+    value: Arc<Vec<u8>>,
+    ttl: Option<Duration>,
+}
+
+/// Expires L1 entries after the L1 time to live, or their time to live in
+/// Redis when shorter.
+struct L1Expiry {
+    ttl: Duration,
+}
+
+impl L1Expiry {
+    fn expire_after(&self, entry: &L1Entry) -> Duration {
+        entry.ttl.map_or(self.ttl, |ttl| ttl.min(self.ttl))
+    }
+}
+
+impl Expiry<String, L1Entry> for L1Expiry {
+    fn expire_after_create(
+        &self,
+        _key: &String,
+        value: &L1Entry,
+        _current_time: Instant,
+    ) -> Option<Duration> {
+        Some(self.expire_after(value))
+    }
+
+    fn expire_after_update(
+        &self,
+        _key: &String,
+        value: &L1Entry,
+        _current_time: Instant,
+        _duration_until_expiry: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(self.expire_after(value))
+    }
+}
+
+/// Pub/sub fan-out state of a [`Tiered`] driver.
+struct Invalidation {
+    channel: String,
+    /// Identifies this instance so it skips its own messages.
+    origin: Uuid,
+    subscriber: JoinHandle<()>,
+}
+
+/// Message published to other instances when keys change.
+#[derive(Debug, Serialize, Deserialize)]
+struct InvalidationMessage {
+    origin: Uuid,
+    /// The keys to evict, or `None` to evict everything.
+    keys: Option<Vec<String>>,
+}
+
+impl Drop for Tiered {
+    fn drop(&mut self) {
+        if let Some(invalidation) = &self.invalidation {
+            invalidation.subscriber.abort();
+        }
+    }
+}
+
+impl Tiered {
+    /// Evicts the given keys (or everything when `None`) from the local L1
+    /// and publishes the eviction to the other instances.
+    async fn evict(&self, keys: Option<Vec<String>>) -> CacheResult<()> {
+        match &keys {
+            Some(keys) => {
+                for key in keys {
+                    self.l1.invalidate(key);
+                }
+            }
+            None => self.l1.invalidate_all(),
+        }
+
+        if let Some(invalidation) = &self.invalidation {
+            let message = serde_json::to_string(&InvalidationMessage {
+                origin: invalidation.origin,
+                keys,
+            })
+            .map_err(|e| crate::cache::CacheError::Serialization(e.to_string()))?;
+            let mut conn = self.pool.get().await?;
+            conn.publish::<_, _, ()>(&invalidation.channel, message)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn evict_key(&self, key: &str) -> CacheResult<()> {
+        self.evict(Some(vec![key.to_string()])).await
+    }
+
+    /// Reads values from Redis along with their remaining time to live, and
+    /// populates L1 with them.
+    async fn fetch(&self, keys: &[&str]) -> CacheResult<Vec<Option<Vec<u8>>>> {
+        let mut conn = self.pool.get().await?;
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for key in keys {
+            pipe.get(*key).pttl(*key);
+        }
+        let replies: Vec<redis::Value> = pipe.query_async(&mut *conn).await?;
+
+        let mut values = Vec::with_capacity(keys.len());
+        for (key, reply) in keys.iter().zip(replies.chunks(2)) {
+            let [value, ttl] = reply else {
+                return Err(crate::cache::CacheError::Any(
+                    "unexpected reply from Redis".into(),
+                ));
+            };
+            let value: Option<Vec<u8>> = redis::from_redis_value(value)?;
+            // PTTL returns -1 when the key has no expiry
+            let ttl: i64 = redis::from_redis_value(ttl)?;
+            if let Some(value) = &value {
+                self.l1.insert(
+                    (*key).to_string(),
+                    L1Entry {
+                        value: Arc::new(value.clone()),
+                        ttl: u64::try_from(ttl).ok().map(Duration::from_millis),
+                    },
+                );
+            }
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+/// Listens for invalidation messages and evicts the keys from L1, reconnecting
+/// when the subscription is lost.
+async fn subscribe(
+    client: redis::Client,
+    channel: String,
+    l1: Cache<String, L1Entry>,
+    origin: Uuid,
+) {
+    loop {
+        match client.get_async_pubsub().await {
+            Ok(mut pubsub) => {
+                if let Err(err) = pubsub.subscribe(&channel).await {
+                    tracing::warn!(error = %err, channel, "could not subscribe to cache invalidation channel");
+                } else {
+                    let mut messages = pubsub.on_message();
+                    while let Some(msg) = messages.next().await {
+                        let Ok(payload) = msg.get_payload::<String>() else {
+                            continue;
+                        };
+                        match serde_json::from_str::<InvalidationMessage>(&payload) {
+                            Ok(message) if message.origin == origin => {}
+                            Ok(InvalidationMessage {
+                                keys: Some(keys), ..
+                            }) => {
+                                for key in keys {
+                                    l1.invalidate(&key);
+                                }
+                            }
+                            Ok(InvalidationMessage { keys: None, .. }) => l1.invalidate_all(),
+                            Err(err) => {
+                                tracing::warn!(error = %err, "invalid cache invalidation message");
+                            }
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, channel, "could not connect to cache invalidation channel");
+            }
+        }
+
+        // messages may have been missed while disconnected
+        l1.invalidate_all();
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[async_trait]
+impl CacheDriver for Tiered {
+    /// Sends a ping to Redis to check if it is reachable.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn ping(&self) -> CacheResult<()> {
+        self.l2.ping().await
+    }
+
+    /// Checks if a key exists in L1 or in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn contains_key(&self, key: &str) -> CacheResult<bool> {
+        if self.l1.contains_key(key) {
+            return Ok(true);
+        }
+        self.l2.contains_key(key).await
+    }
+
+    /// Retrieves a value from L1, falling back to Redis and populating L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn get(&self, key: &str) -> CacheResult<Option<Vec<u8>>> {
+        if let Some(entry) = self.l1.get(key) {
+            return Ok(Some(entry.value.as_ref().clone()));
+        }
+        Ok(self.fetch(&[key]).await?.pop().flatten())
+    }
+
+    /// Inserts a key-value pair into Redis and evicts it from L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert(&self, key: &str, value: &[u8]) -> CacheResult<()> {
+        self.l2.insert(key, value).await?;
+        self.evict_key(key).await
+    }
+
+    /// Inserts a key-value pair into Redis that expires after the specified
+    /// duration and evicts it from L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_with_expiry(
+        &self,
+        key: &str,
+        value: &[u8],
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.l2.insert_with_expiry(key, value, duration).await?;
+        self.evict_key(key).await
+    }
+
+    /// Inserts a tagged key-value pair into Redis and evicts it from L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_tagged(
+        &self,
+        key: &str,
+        value: &[u8],
+        tags: &[&str],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        self.l2.insert_tagged(key, value, tags, duration).await?;
+        self.evict_key(key).await
+    }
+
+    /// Removes all key-value pairs associated with the given tag from Redis
+    /// and evicts them from L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        let keys: Vec<String> = {
+            let mut conn = self.pool.get().await?;
+            conn.smembers(super::redis::tag_key(tag)).await?
+        };
+        self.l2.invalidate_tag(tag).await?;
+        self.evict(Some(keys)).await
+    }
+
+    /// Atomically increments the integer stored at the key in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn increment(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        let value = self.l2.increment(key, delta, duration).await?;
+        self.evict_key(key).await?;
+        Ok(value)
+    }
+
+    /// Atomically decrements the integer stored at the key in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn decrement(
+        &self,
+        key: &str,
+        delta: i64,
+        duration: Option<Duration>,
+    ) -> CacheResult<i64> {
+        let value = self.l2.decrement(key, delta, duration).await?;
+        self.evict_key(key).await?;
+        Ok(value)
+    }
+
+    /// Returns the remaining time to live of a key in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn ttl(&self, key: &str) -> CacheResult<Option<Duration>> {
+        self.l2.ttl(key).await
+    }
+
+    /// Sets a key in Redis to expire after the specified duration, and evicts
+    /// it from L1 so the new time to live is read from Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn expire(&self, key: &str, duration: Duration) -> CacheResult<bool> {
+        let exists = self.l2.expire(key, duration).await?;
+        if exists {
+            self.evict_key(key).await?;
+        }
+        Ok(exists)
+    }
+
+    /// Acquires the load lock of a key in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn acquire_lock(&self, key: &str) -> CacheResult<bool> {
+        self.l2.acquire_lock(key).await
+    }
+
+    /// Releases the load lock of a key in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn release_lock(&self, key: &str) -> CacheResult<()> {
+        self.l2.release_lock(key).await
+    }
+
+    /// Removes a key-value pair from Redis and L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove(&self, key: &str) -> CacheResult<()> {
+        self.l2.remove(key).await?;
+        self.evict_key(key).await
+    }
+
+    /// Clears all key-value pairs from Redis and L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn clear(&self) -> CacheResult<()> {
+        self.l2.clear().await?;
+        self.evict(None).await
+    }
+
+    /// Retrieves several values, fetching the ones missing from L1 from Redis
+    /// in a single request.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn get_many(&self, keys: &[&str]) -> CacheResult<Vec<Option<Vec<u8>>>> {
+        let mut values: Vec<Option<Vec<u8>>> = keys
+            .iter()
+            .map(|key| self.l1.get(*key).map(|entry| entry.value.as_ref().clone()))
+            .collect();
+        let missing: Vec<usize> = (0..keys.len()).filter(|i| values[*i].is_none()).collect();
+        if missing.is_empty() {
+            return Ok(values);
+        }
+
+        let missing_keys: Vec<&str> = missing.iter().map(|i| keys[*i]).collect();
+        let fetched = self.fetch(&missing_keys).await?;
+        for (i, value) in missing.into_iter().zip(fetched) {
+            values[i] = value;
+        }
+        Ok(values)
+    }
+
+    /// Adds lookups to the shared counters in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn add_stats(&self, stats: CacheStats) -> CacheResult<()> {
+        self.l2.add_stats(stats).await
+    }
+
+    /// Returns the shared counters in Redis.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn stats(&self) -> CacheResult<CacheStats> {
+        self.l2.stats().await
+    }
+
+    /// Inserts several key-value pairs into Redis and evicts them from L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn insert_many(
+        &self,
+        entries: &[(&str, &[u8])],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        self.l2.insert_many(entries, duration).await?;
+        self.evict(Some(
+            entries.iter().map(|(key, _)| (*key).to_string()).collect(),
+        ))
+        .await
+    }
+
+    /// Removes several keys from Redis and L1.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `CacheError` if there is an error during the operation.
+    async fn remove_many(&self, keys: &[&str]) -> CacheResult<()> {
+        self.l2.remove_many(keys).await?;
+        self.evict(Some(keys.iter().map(|key| (*key).to_string()).collect()))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use testcontainers::{ContainerAsync, GenericImage};
+
+    use super::*;
+    use crate::{config::RedisCacheConfig, tests_cfg::redis::setup_redis_container};
+
+    async fn setup_tiered_cache(
+        invalidation_channel: Option<&str>,
+    ) -> (
+        crate::cache::Cache,
+        crate::cache::Cache,
+        ContainerAsync<GenericImage>,
+    ) {
+        let (redis_url, container) = setup_redis_container().await;
+
+        let config = TieredCacheConfig {
+            redis: RedisCacheConfig {
+                uri: redis_url,
+                max_size: 10,
+                lock: None,
+                encoding: crate::config::CacheEncodingConfig::default(),
+            },
+            l1_max_capacity: 100,
+            l1_ttl: 60_000,
+            invalidation_channel: invalidation_channel.map(ToString::to_string),
+        };
+
+        let first = new(&config).await.expect("Failed to create tiered driver");
+        let second = new(&config).await.expect("Failed to create tiered driver");
+        // give the subscribers time to subscribe
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        (first, second, container)
+    }
+
+    #[tokio::test]
+    async fn can_read_through_l1() {
+        let (first, second, _container) = setup_tiered_cache(None).await;
+
+        first.insert("key", &"loco").await.unwrap();
+        assert_eq!(
+            second.get::<String>("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+
+        // without fan-out the second instance keeps serving its L1 entry
+        first.insert("key", &"changed").await.unwrap();
+        assert_eq!(
+            second.get::<String>("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+        assert_eq!(
+            first.get::<String>("key").await.unwrap(),
+            Some("changed".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn caps_l1_entries_at_redis_ttl() {
+        let (first, _second, _container) = setup_tiered_cache(None).await;
+
+        first
+            .insert_with_expiry("key", &"loco", Duration::from_millis(300))
+            .await
+            .unwrap();
+        assert_eq!(
+            first.get::<String>("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(first.get::<String>("key").await.unwrap(), None);
+    }
+
+    #[test]
+    fn expires_l1_entries_with_redis_ttl() {
+        let expiry = L1Expiry {
+            ttl: Duration::from_secs(60),
+        };
+        let entry = |ttl| L1Entry {
+            value: Arc::new(Vec::new()),
+            ttl,
+        };
+        assert_eq!(expiry.expire_after(&entry(None)), Duration::from_secs(60));
+        assert_eq!(
+            expiry.expire_after(&entry(Some(Duration::from_secs(5)))),
+            Duration::from_secs(5)
+        );
+        assert_eq!(
+            expiry.expire_after(&entry(Some(Duration::from_secs(600)))),
+            Duration::from_secs(60)
+        );
+    }
+
+    #[tokio::test]
+    async fn can_fan_out_invalidation() {
+        let (first, second, _container) = setup_tiered_cache(Some("loco:cache:l1")).await;
+
+        first.insert("key", &"loco").await.unwrap();
+        assert_eq!(
+            second.get::<String>("key").await.unwrap(),
+            Some("loco".to_string())
+        );
+
+        first.insert("key", &"changed").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(
+            second.get::<String>("key").await.unwrap(),
+            Some("changed".to_string())
+        );
+
+        first.remove("key").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert_eq!(second.get::<String>("key").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn evicts_l1_entries_on_expire() {
+        let (first, second, _container) = setup_tiered_cache(Some("loco:cache:l1")).await;
+
+        first.insert("key", &"loco").await.unwrap();
+        for cache in [&first, &second] {
+            assert_eq!(
+                cache.get::<String>("key").await.unwrap(),
+                Some("loco".to_string())
+            );
+        }
+
+        assert!(first
+            .expire("key", Duration::from_millis(300))
+            .await
+            .unwrap());
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        for cache in [&first, &second] {
+            assert_eq!(cache.get::<String>("key").await.unwrap(), None);
+        }
+    }
+}
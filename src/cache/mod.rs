@@ -1,12 +1,19 @@
 //! # Cache Module
 //!
 //! This module provides a generic cache interface for various cache drivers.
+pub mod codec;
 pub mod drivers;
 
-use std::{future::Future, time::Duration};
+use std::{
+    future::Future,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
+use dashmap::DashMap;
 use serde::{de::DeserializeOwned, Serialize};
 
+use self::codec::Encoder;
 pub use self::drivers::CacheDriver;
 use crate::config;
 use crate::Result as LocoResult;
@@ -32,10 +39,89 @@ pub enum CacheError {
     #[cfg(feature = "cache_redis")]
     #[error(transparent)]
     RedisConnectionError(#[from] bb8_redis::bb8::RunError<bb8_redis::redis::RedisError>),
+
+    #[cfg(feature = "with-db")]
+    #[error(transparent)]
+    Database(#[from] sea_orm::DbErr),
 }
 
 pub type CacheResult<T> = std::result::Result<T, CacheError>;
 
+/// How often the hits and misses of a process are added to the shared
+/// counters.
+const STATS_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Hit and miss counters of cache lookups.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Returns the share of lookups which were hits, between `0` and `1`.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn hit_ratio(&self) -> f64 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+/// Counts the hits and misses of a [`Cache`], and the ones not yet added to
+/// the shared counters.
+struct StatsRecorder {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    unflushed_hits: AtomicU64,
+    unflushed_misses: AtomicU64,
+}
+
+impl StatsRecorder {
+    fn new() -> Self {
+        Self {
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            unflushed_hits: AtomicU64::new(0),
+            unflushed_misses: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, hit: bool) {
+        if hit {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            self.unflushed_hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            self.unflushed_misses.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Adds the lookups of a cache to the shared counters every
+/// [`STATS_FLUSH_INTERVAL`], until the cache is dropped.
+fn spawn_stats_flush(cache: &Arc<Cache>) {
+    let cache = Arc::downgrade(cache);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(STATS_FLUSH_INTERVAL);
+        // the first tick completes immediately
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let Some(cache) = cache.upgrade() else {
+                break;
+            };
+            if let Err(err) = cache.flush_stats().await {
+                tracing::debug!(error = %err, "could not flush cache stats");
+            }
+        }
+    });
+}
+
 /// Create a provider
 ///
 /// # Errors
@@ -43,12 +129,30 @@ pub type CacheResult<T> = std::result::Result<T, CacheError>;
 /// This function will return an error if fails to build
 #[allow(clippy::unused_async)]
 pub async fn create_cache_provider(config: &config::Config) -> crate::Result<Arc<Cache>> {
+    let cache = build_cache(config).await?;
+    spawn_stats_flush(&cache);
+    Ok(cache)
+}
+
+#[allow(clippy::unused_async)]
+async fn build_cache(config: &config::Config) -> crate::Result<Arc<Cache>> {
     match &config.cache {
         #[cfg(feature = "cache_redis")]
         config::CacheConfig::Redis(config) => {
             let cache = crate::cache::drivers::redis::new(config).await?;
             Ok(Arc::new(cache))
         }
+        #[cfg(all(feature = "cache_inmem", feature = "cache_redis"))]
+        config::CacheConfig::Tiered(config) => {
+            let cache = crate::cache::drivers::tiered::new(config).await?;
+            Ok(Arc::new(cache))
+        }
+        #[cfg(feature = "with-db")]
+        config::CacheConfig::Database(cache_config) => {
+            let cache =
+                crate::cache::drivers::database::new(&config.database, cache_config).await?;
+            Ok(Arc::new(cache))
+        }
         #[cfg(feature = "cache_inmem")]
         config::CacheConfig::InMem(config) => {
             let cache = crate::cache::drivers::inmem::new(config);
@@ -65,13 +169,37 @@ pub async fn create_cache_provider(config: &config::Config) -> crate::Result<Arc
 pub struct Cache {
     /// The cache driver used for underlying operations
     pub driver: Box<dyn CacheDriver>,
+    /// Per-key locks of the values currently being computed, so concurrent
+    /// misses on the same key run the loader once.
+    loading: DashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    stats: StatsRecorder,
+    encoder: Encoder,
 }
 
 impl Cache {
     /// Creates a new cache instance with the specified cache driver.
     #[must_use]
     pub fn new(driver: Box<dyn CacheDriver>) -> Self {
-        Self { driver }
+        Self {
+            driver,
+            loading: DashMap::new(),
+            stats: StatsRecorder::new(),
+            encoder: Encoder::default(),
+        }
+    }
+
+    /// Sets how values are serialized and compressed. Values are stored as
+    /// uncompressed JSON by default.
+    #[must_use]
+    pub fn with_encoding(mut self, config: &config::CacheEncodingConfig) -> Self {
+        self.encoder = Encoder::new(config);
+        self
+    }
+
+    /// Returns the encoder used to serialize and deserialize values.
+    #[must_use]
+    pub fn encoder(&self) -> &Encoder {
+        &self.encoder
     }
 
     /// Pings the cache to check if it is reachable.
@@ -152,13 +280,49 @@ impl Cache {
     /// and deserialized value.
     pub async fn get<T: DeserializeOwned>(&self, key: &str) -> CacheResult<Option<T>> {
         let result = self.driver.get(key).await?;
-        if let Some(value) = result {
-            let deserialized = serde_json::from_str::<T>(&value)
-                .map_err(|e| CacheError::Deserialization(e.to_string()))?;
-            Ok(Some(deserialized))
-        } else {
-            Ok(None)
-        }
+        self.record_lookups(&[result.is_some()]);
+        self.decode(result)
+    }
+
+    /// Like [`Cache::get`], without counting the lookup, for the lookups
+    /// repeated by a caller which already counted its own.
+    async fn get_uncounted<T: DeserializeOwned>(&self, key: &str) -> CacheResult<Option<T>> {
+        let result = self.driver.get(key).await?;
+        self.decode(result)
+    }
+
+    fn decode<T: DeserializeOwned>(&self, value: Option<Vec<u8>>) -> CacheResult<Option<T>> {
+        value.map(|value| self.encoder.decode(&value)).transpose()
+    }
+
+    /// Retrieves and deserializes the values of several keys, in the order of
+    /// the keys.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn get_many() -> CacheResult<Vec<Option<String>>> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.get_many::<String>(&["key1", "key2"]).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// A [`CacheResult`] containing the retrieved and deserialized values.
+    pub async fn get_many<T: DeserializeOwned>(
+        &self,
+        keys: &[&str],
+    ) -> CacheResult<Vec<Option<T>>> {
+        let values = self.driver.get_many(keys).await?;
+        let lookups: Vec<bool> = values.iter().map(Option::is_some).collect();
+        self.record_lookups(&lookups);
+        values
+            .into_iter()
+            .map(|value| value.map(|value| self.encoder.decode(&value)).transpose())
+            .collect()
     }
 
     /// Inserts a serializable value into the cache with the provided key.
@@ -203,8 +367,7 @@ impl Cache {
         key: &str,
         value: &T,
     ) -> CacheResult<()> {
-        let serialized =
-            serde_json::to_string(value).map_err(|e| CacheError::Serialization(e.to_string()))?;
+        let serialized = self.encoder.encode(value)?;
         self.driver.insert(key, &serialized).await
     }
 
@@ -253,13 +416,232 @@ impl Cache {
         value: &T,
         duration: Duration,
     ) -> CacheResult<()> {
-        let serialized =
-            serde_json::to_string(value).map_err(|e| CacheError::Serialization(e.to_string()))?;
+        let serialized = self.encoder.encode(value)?;
         self.driver
             .insert_with_expiry(key, &serialized, duration)
             .await
     }
 
+    /// Inserts a serializable value into the cache and associates it with the
+    /// given tags, so it can later be removed with [`Self::invalidate_tag`].
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn insert_tagged() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.insert_tagged("user:1:profile", &"Alice", &["user:1"]).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_tagged<T: Serialize + Sync + ?Sized>(
+        &self,
+        key: &str,
+        value: &T,
+        tags: &[&str],
+    ) -> CacheResult<()> {
+        let serialized = self.encoder.encode(value)?;
+        self.driver
+            .insert_tagged(key, &serialized, tags, None)
+            .await
+    }
+
+    /// Inserts a serializable value into the cache with the provided expiry
+    /// duration and associates it with the given tags.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn insert_tagged() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache
+    ///         .insert_tagged_with_expiry("product:1", &"Chair", &["products"], Duration::from_secs(300))
+    ///         .await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_tagged_with_expiry<T: Serialize + Sync + ?Sized>(
+        &self,
+        key: &str,
+        value: &T,
+        tags: &[&str],
+        duration: Duration,
+    ) -> CacheResult<()> {
+        let serialized = self.encoder.encode(value)?;
+        self.driver
+            .insert_tagged(key, &serialized, tags, Some(duration))
+            .await
+    }
+
+    /// Removes every entry that was inserted with the given tag.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn invalidate_tag() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.insert_tagged("user:1:profile", &"Alice", &["user:1"]).await?;
+    ///     cache.invalidate_tag("user:1").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn invalidate_tag(&self, tag: &str) -> CacheResult<()> {
+        self.driver.invalidate_tag(tag).await
+    }
+
+    /// Atomically increments the integer stored at the key by `delta` and
+    /// returns the new value. A missing key is created with the value `delta`
+    /// and, when `expiry` is provided, expires after that duration; later
+    /// increments keep the original expiry.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn rate_limit() -> CacheResult<bool> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     let hits = cache
+    ///         .increment("rate:127.0.0.1", 1, Some(Duration::from_secs(60)))
+    ///         .await?;
+    ///     Ok(hits <= 100)
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] with the new value, or an error when the stored value
+    /// is not an integer.
+    pub async fn increment(
+        &self,
+        key: &str,
+        delta: i64,
+        expiry: Option<Duration>,
+    ) -> CacheResult<i64> {
+        self.driver.increment(key, delta, expiry).await
+    }
+
+    /// Atomically decrements the integer stored at the key by `delta` and
+    /// returns the new value. A missing key is created with the value `-delta`
+    /// and, when `expiry` is provided, expires after that duration.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn consume_quota() -> CacheResult<i64> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.decrement("quota:user:1", 1, None).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] with the new value, or an error when the stored value
+    /// is not an integer.
+    pub async fn decrement(
+        &self,
+        key: &str,
+        delta: i64,
+        expiry: Option<Duration>,
+    ) -> CacheResult<i64> {
+        self.driver.decrement(key, delta, expiry).await
+    }
+
+    /// Returns the remaining time to live of a key, or `None` when the key
+    /// does not exist or has no expiry.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn ttl() -> CacheResult<Option<Duration>> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.ttl("key").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] containing the remaining time to live.
+    pub async fn ttl(&self, key: &str) -> CacheResult<Option<Duration>> {
+        self.driver.ttl(key).await
+    }
+
+    /// Sets a key to expire after the specified duration. Returns `false` when
+    /// the key does not exist.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn expire() -> CacheResult<bool> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.expire("key", Duration::from_secs(300)).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating whether the key exists.
+    pub async fn expire(&self, key: &str, duration: Duration) -> CacheResult<bool> {
+        self.driver.expire(key, duration).await
+    }
+
+    /// Returns a view of the cache where every key is prefixed with the given
+    /// namespace. All entries written through the view can be dropped at once
+    /// with [`Namespace::clear`].
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn namespace() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     let user_cache = cache.namespace("user:1");
+    ///     user_cache.insert("profile", &"Alice").await?;
+    ///     user_cache.clear().await
+    /// }
+    /// ```
+    #[must_use]
+    pub fn namespace(&self, prefix: &str) -> Namespace<'_> {
+        Namespace {
+            cache: self,
+            prefix: prefix.to_string(),
+        }
+    }
+
     /// Retrieves and deserializes the value associated with the given key from the cache,
     /// or inserts it if it does not exist, using the provided closure to
     /// generate the value.
@@ -310,9 +692,7 @@ impl Cache {
         if let Some(value) = self.get::<T>(key).await? {
             Ok(value)
         } else {
-            let value = f.await?;
-            self.insert(key, &value).await?;
-            Ok(value)
+            self.load(key, None, &[], f).await
         }
     }
 
@@ -373,61 +753,642 @@ impl Cache {
         if let Some(value) = self.get::<T>(key).await? {
             Ok(value)
         } else {
-            let value = f.await?;
-            self.insert_with_expiry(key, &value, duration).await?;
-            Ok(value)
+            self.load(key, Some(duration), &[], f).await
         }
     }
 
-    /// Removes a key-value pair from the cache.
+    /// Like [`Cache::get_or_insert_with_expiry`], but keeps serving the cached
+    /// value for up to `stale` after it expired while a single caller
+    /// recomputes it. If the recomputation fails, the stale value is returned.
     ///
     /// # Example
     /// ```
-    /// use loco_rs::cache::{self, CacheResult};
-    /// use loco_rs::config::InMemCacheConfig;
+    /// use std::time::Duration;
+    /// use loco_rs::tests_cfg::app::*;
     ///
-    /// pub async fn remove() -> CacheResult<()> {
-    ///     let config = InMemCacheConfig { max_capacity: 100 };
-    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
-    ///     cache.remove("key").await
+    /// pub async fn get_or_insert_with_stale(){
+    ///    let app_ctx = get_app_context().await;
+    ///    let res = app_ctx.cache.get_or_insert_with_stale::<String, _, _>(
+    ///            "key",
+    ///            Duration::from_secs(60),
+    ///            Duration::from_secs(10),
+    ///            || async { Ok("value".to_string()) },
+    ///     ).await.unwrap();
+    ///    assert_eq!(res, "value");
     /// }
     /// ```
     ///
     /// # Errors
     ///
-    /// A [`CacheResult`] indicating the success of the operation.
-    pub async fn remove(&self, key: &str) -> CacheResult<()> {
-        self.driver.remove(key).await
-    }
+    /// A [`LocoResult`] indicating the success of the operation.
+    pub async fn get_or_insert_with_stale<T, F, Fut>(
+        &self,
+        key: &str,
+        duration: Duration,
+        stale: Duration,
+        f: F,
+    ) -> LocoResult<T>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: FnOnce() -> Fut + Send,
+        Fut: Future<Output = LocoResult<T>> + Send,
+    {
+        // entries live for `duration + stale`, so they are fresh while more
+        // than `stale` of their time to live remains
+        let expiry = duration.saturating_add(stale);
+        let Some(value) = self.get::<T>(key).await? else {
+            return self.load(key, Some(expiry), &[], f()).await;
+        };
+        let fresh = self
+            .driver
+            .ttl(key)
+            .await?
+            .map_or(true, |remaining| remaining > stale);
+        if fresh {
+            return Ok(value);
+        }
 
-    /// Clears all key-value pairs from the cache.
-    ///
-    /// # Example
-    /// ```
-    /// use loco_rs::cache::{self, CacheResult};
-    /// use loco_rs::config::InMemCacheConfig;
-    ///
-    /// pub async fn clear() -> CacheResult<()> {
-    ///     let config = InMemCacheConfig { max_capacity: 100 };
-    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
-    ///     cache.clear().await
-    /// }
-    /// ```
-    ///
-    /// # Errors
-    ///
-    /// A [`CacheResult`] indicating the success of the operation.
-    pub async fn clear(&self) -> CacheResult<()> {
-        self.driver.clear().await
+        // only one caller revalidates, the others get the stale value
+        let lock = self.loading_lock(key);
+        let Ok(guard) = lock.try_lock() else {
+            drop(lock);
+            self.release_loading_lock(key);
+            return Ok(value);
+        };
+        let result = match f().await {
+            Ok(refreshed) => self
+                .insert_with_expiry(key, &refreshed, expiry)
+                .await
+                .map(|()| refreshed)
+                .map_err(Into::into),
+            Err(err) => {
+                tracing::warn!(key, error = %err, "could not revalidate cache entry, serving stale value");
+                Ok(value)
+            }
+        };
+        drop(guard);
+        drop(lock);
+        self.release_loading_lock(key);
+        result
     }
-}
-
-#[cfg(test)]
-mod tests {
+
+    /// Computes and stores the value of a missing key with the given tags,
+    /// making sure concurrent callers (and, when the driver supports it, other
+    /// instances) run the loader only once.
+    async fn load<T, F>(
+        &self,
+        key: &str,
+        duration: Option<Duration>,
+        tags: &[&str],
+        f: F,
+    ) -> LocoResult<T>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: Future<Output = LocoResult<T>> + Send,
+    {
+        let lock = self.loading_lock(key);
+        let result = {
+            let _guard = lock.lock().await;
+            self.load_locked(key, duration, tags, f).await
+        };
+        drop(lock);
+        self.release_loading_lock(key);
+        result
+    }
+
+    async fn load_locked<T, F>(
+        &self,
+        key: &str,
+        duration: Option<Duration>,
+        tags: &[&str],
+        f: F,
+    ) -> LocoResult<T>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: Future<Output = LocoResult<T>> + Send,
+    {
+        // another caller may have loaded the value while we were waiting,
+        // the miss of this caller is already counted
+        if let Some(value) = self.get_uncounted::<T>(key).await? {
+            return Ok(value);
+        }
+
+        let acquired = self.driver.acquire_lock(key).await?;
+        if !acquired {
+            // another instance held the lock, it has most likely stored the value
+            if let Some(value) = self.get_uncounted::<T>(key).await? {
+                return Ok(value);
+            }
+        }
+
+        let result = match f.await {
+            Ok(value) => match (duration, tags.is_empty()) {
+                (Some(duration), true) => self.insert_with_expiry(key, &value, duration).await,
+                (None, true) => self.insert(key, &value).await,
+                (Some(duration), false) => {
+                    self.insert_tagged_with_expiry(key, &value, tags, duration)
+                        .await
+                }
+                (None, false) => self.insert_tagged(key, &value, tags).await,
+            }
+            .map(|()| value)
+            .map_err(Into::into),
+            Err(err) => Err(err),
+        };
+        if acquired {
+            // the lock expires on its own, so failing to release it must not
+            // hide the loaded value
+            if let Err(err) = self.driver.release_lock(key).await {
+                tracing::warn!(key, error = %err, "could not release cache load lock");
+            }
+        }
+        result
+    }
+
+    fn loading_lock(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.loading.entry(key.to_string()).or_default().clone()
+    }
+
+    fn release_loading_lock(&self, key: &str) {
+        self.loading
+            .remove_if(key, |_, lock| Arc::strong_count(lock) == 1);
+    }
+
+    /// Removes a key-value pair from the cache.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn remove() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.remove("key").await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn remove(&self, key: &str) -> CacheResult<()> {
+        self.driver.remove(key).await
+    }
+
+    /// Inserts several serializable values into the cache.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn insert_many() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.insert_many(&[("key1", "value1"), ("key2", "value2")]).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_many<T: Serialize + Sync>(&self, entries: &[(&str, T)]) -> CacheResult<()> {
+        self.insert_many_inner(entries, None).await
+    }
+
+    /// Inserts several serializable values into the cache with the provided
+    /// expiry duration.
+    ///
+    /// # Example
+    /// ```
+    /// use std::time::Duration;
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn insert_many_with_expiry() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache
+    ///         .insert_many_with_expiry(&[("key1", 1), ("key2", 2)], Duration::from_secs(300))
+    ///         .await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_many_with_expiry<T: Serialize + Sync>(
+        &self,
+        entries: &[(&str, T)],
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.insert_many_inner(entries, Some(duration)).await
+    }
+
+    async fn insert_many_inner<T: Serialize + Sync>(
+        &self,
+        entries: &[(&str, T)],
+        duration: Option<Duration>,
+    ) -> CacheResult<()> {
+        let serialized = entries
+            .iter()
+            .map(|(key, value)| self.encoder.encode(value).map(|value| (*key, value)))
+            .collect::<CacheResult<Vec<_>>>()?;
+        let entries: Vec<(&str, &[u8])> = serialized
+            .iter()
+            .map(|(key, value)| (*key, value.as_slice()))
+            .collect();
+        self.driver.insert_many(&entries, duration).await
+    }
+
+    /// Removes several keys from the cache.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn remove_many() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.remove_many(&["key1", "key2"]).await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn remove_many(&self, keys: &[&str]) -> CacheResult<()> {
+        self.driver.remove_many(keys).await
+    }
+
+    /// Returns the hits and misses of the lookups made by this cache instance.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache;
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn stats() {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     let _ = cache.get::<String>("key").await;
+    ///     assert_eq!(cache.stats().misses, 1);
+    /// }
+    /// ```
+    #[must_use]
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.stats.hits.load(Ordering::Relaxed),
+            misses: self.stats.misses.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the hits and misses of every process using the cache, as
+    /// stored in the cache itself.
+    ///
+    /// Each process adds its lookups to the shared counters from a background
+    /// task every ten seconds, or when [`Cache::flush_stats`] is called, so
+    /// recent lookups may be missing. The counters are kept apart from the
+    /// cached entries, and drivers without shared counters return zero.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] with the shared counters.
+    pub async fn shared_stats(&self) -> CacheResult<CacheStats> {
+        self.driver.stats().await
+    }
+
+    /// Adds the lookups made since the last flush to the shared counters
+    /// returned by [`Cache::shared_stats`].
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn flush_stats(&self) -> CacheResult<()> {
+        let stats = CacheStats {
+            hits: self.stats.unflushed_hits.swap(0, Ordering::Relaxed),
+            misses: self.stats.unflushed_misses.swap(0, Ordering::Relaxed),
+        };
+        if stats == CacheStats::default() {
+            return Ok(());
+        }
+        self.driver.add_stats(stats).await
+    }
+
+    fn record_lookups(&self, lookups: &[bool]) {
+        for hit in lookups {
+            self.stats.record(*hit);
+        }
+    }
+
+    /// Clears all key-value pairs from the cache.
+    ///
+    /// # Example
+    /// ```
+    /// use loco_rs::cache::{self, CacheResult};
+    /// use loco_rs::config::InMemCacheConfig;
+    ///
+    /// pub async fn clear() -> CacheResult<()> {
+    ///     let config = InMemCacheConfig { max_capacity: 100 };
+    ///     let cache = cache::Cache::new(cache::drivers::inmem::new(&config).driver);
+    ///     cache.clear().await
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn clear(&self) -> CacheResult<()> {
+        self.driver.clear().await
+    }
+}
+
+/// A view of a [`Cache`] that prefixes every key with a namespace and tags
+/// every entry with it, created by [`Cache::namespace`].
+pub struct Namespace<'a> {
+    cache: &'a Cache,
+    prefix: String,
+}
+
+impl Namespace<'_> {
+    /// Returns the full cache key for a key in this namespace.
+    #[must_use]
+    pub fn key(&self, key: &str) -> String {
+        format!("{}:{key}", self.prefix)
+    }
+
+    /// Returns the tag every entry of this namespace is associated with.
+    #[must_use]
+    pub fn tag(&self) -> String {
+        format!("namespace:{}", self.prefix)
+    }
+
+    /// Checks if a key exists in this namespace.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating whether the key exists in the cache.
+    pub async fn contains_key(&self, key: &str) -> CacheResult<bool> {
+        self.cache.contains_key(&self.key(key)).await
+    }
+
+    /// Retrieves and deserializes a value from this namespace.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] containing an `Option` representing the retrieved
+    /// and deserialized value.
+    pub async fn get<T: DeserializeOwned>(&self, key: &str) -> CacheResult<Option<T>> {
+        self.cache.get(&self.key(key)).await
+    }
+
+    /// Inserts a serializable value into this namespace.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert<T: Serialize + Sync + ?Sized>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> CacheResult<()> {
+        self.cache
+            .insert_tagged(&self.key(key), value, &[&self.tag()])
+            .await
+    }
+
+    /// Inserts a serializable value into this namespace with the provided
+    /// expiry duration.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn insert_with_expiry<T: Serialize + Sync + ?Sized>(
+        &self,
+        key: &str,
+        value: &T,
+        duration: Duration,
+    ) -> CacheResult<()> {
+        self.cache
+            .insert_tagged_with_expiry(&self.key(key), value, &[&self.tag()], duration)
+            .await
+    }
+
+    /// Retrieves the value associated with the given key from this namespace,
+    /// or inserts the value produced by `f` if it does not exist. Like
+    /// [`Cache::get_or_insert`], concurrent callers run `f` only once.
+    ///
+    /// # Errors
+    ///
+    /// A [`LocoResult`] indicating the success of the operation.
+    pub async fn get_or_insert<T, F>(&self, key: &str, f: F) -> LocoResult<T>
+    where
+        T: Serialize + DeserializeOwned + Send + Sync,
+        F: Future<Output = LocoResult<T>> + Send,
+    {
+        if let Some(value) = self.get::<T>(key).await? {
+            Ok(value)
+        } else {
+            self.cache
+                .load(&self.key(key), None, &[&self.tag()], f)
+                .await
+        }
+    }
+
+    /// Removes a key-value pair from this namespace.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn remove(&self, key: &str) -> CacheResult<()> {
+        self.cache.remove(&self.key(key)).await
+    }
+
+    /// Removes every entry written through this namespace.
+    ///
+    /// # Errors
+    ///
+    /// A [`CacheResult`] indicating the success of the operation.
+    pub async fn clear(&self) -> CacheResult<()> {
+        self.cache.invalidate_tag(&self.tag()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
 
     use crate::tests_cfg;
     use serde::{Deserialize, Serialize};
 
+    #[tokio::test]
+    async fn can_get_insert_and_remove_many() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+
+        app_ctx
+            .cache
+            .insert_many(&[("many:1", 1), ("many:2", 2)])
+            .await
+            .unwrap();
+        assert_eq!(
+            app_ctx
+                .cache
+                .get_many::<i32>(&["many:1", "many:missing", "many:2"])
+                .await
+                .unwrap(),
+            vec![Some(1), None, Some(2)]
+        );
+
+        app_ctx
+            .cache
+            .remove_many(&["many:1", "many:2"])
+            .await
+            .unwrap();
+        assert_eq!(
+            app_ctx
+                .cache
+                .get_many::<i32>(&["many:1", "many:2"])
+                .await
+                .unwrap(),
+            vec![None, None]
+        );
+    }
+
+    #[tokio::test]
+    async fn can_track_stats() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+        app_ctx.cache.insert("stats", "loco").await.unwrap();
+
+        app_ctx.cache.get::<String>("stats").await.unwrap();
+        app_ctx.cache.get::<String>("stats:missing").await.unwrap();
+        app_ctx
+            .cache
+            .get_many::<String>(&["stats", "stats:missing"])
+            .await
+            .unwrap();
+
+        let stats = app_ctx.cache.stats();
+        assert_eq!(stats, super::CacheStats { hits: 2, misses: 2 });
+        assert!((stats.hit_ratio() - 0.5).abs() < f64::EPSILON);
+
+        assert_eq!(
+            app_ctx.cache.shared_stats().await.unwrap(),
+            super::CacheStats::default()
+        );
+        app_ctx.cache.flush_stats().await.unwrap();
+        assert_eq!(app_ctx.cache.shared_stats().await.unwrap(), stats);
+
+        // the shared counters are not cache entries
+        app_ctx.cache.clear().await.unwrap();
+        assert_eq!(app_ctx.cache.shared_stats().await.unwrap(), stats);
+    }
+
+    #[tokio::test]
+    async fn counts_one_lookup_per_get_or_insert() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+        let load = || async { Ok("loco".to_string()) };
+
+        app_ctx
+            .cache
+            .get_or_insert::<String, _>("stats", load())
+            .await
+            .unwrap();
+        assert_eq!(
+            app_ctx.cache.stats(),
+            super::CacheStats { hits: 0, misses: 1 }
+        );
+
+        app_ctx
+            .cache
+            .get_or_insert::<String, _>("stats", load())
+            .await
+            .unwrap();
+        assert_eq!(
+            app_ctx.cache.stats(),
+            super::CacheStats { hits: 1, misses: 1 }
+        );
+    }
+
+    #[tokio::test]
+    async fn can_run_loader_once_on_concurrent_misses() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+        let calls = AtomicUsize::new(0);
+
+        let load = || {
+            app_ctx.cache.get_or_insert::<String, _>("stampede", async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok("value".to_string())
+            })
+        };
+        let results = futures_util::future::join_all((0..10).map(|_| load())).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.into_iter().all(|result| result.unwrap() == "value"));
+    }
+
+    #[tokio::test]
+    async fn can_run_namespace_loader_once_on_concurrent_misses() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+        let namespace = app_ctx.cache.namespace("user:1");
+        let calls = AtomicUsize::new(0);
+
+        let load = || {
+            namespace.get_or_insert::<String, _>("profile", async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                Ok("value".to_string())
+            })
+        };
+        let results = futures_util::future::join_all((0..10).map(|_| load())).await;
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert!(results.into_iter().all(|result| result.unwrap() == "value"));
+
+        namespace.clear().await.unwrap();
+        assert!(!namespace.contains_key("profile").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_serve_stale_value_while_revalidating() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+        let load = |value: &'static str| {
+            app_ctx.cache.get_or_insert_with_stale::<String, _, _>(
+                "stale",
+                Duration::from_millis(100),
+                Duration::from_secs(60),
+                move || async move { Ok(value.to_string()) },
+            )
+        };
+
+        assert_eq!(load("first").await.unwrap(), "first");
+        // still fresh
+        assert_eq!(load("second").await.unwrap(), "first");
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        // stale: revalidated by the caller
+        assert_eq!(load("second").await.unwrap(), "second");
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        // a failed revalidation keeps serving the stale value
+        let res = app_ctx
+            .cache
+            .get_or_insert_with_stale::<String, _, _>(
+                "stale",
+                Duration::from_millis(100),
+                Duration::from_secs(60),
+                || async { Err(crate::Error::string("unavailable")) },
+            )
+            .await
+            .unwrap();
+        assert_eq!(res, "second");
+    }
+
     #[tokio::test]
     async fn can_get_or_insert() {
         let app_ctx = tests_cfg::app::get_app_context().await;
@@ -515,4 +1476,48 @@ mod tests {
         assert_eq!(retrieved.name, "Alice");
         assert_eq!(retrieved.age, 30);
     }
+
+    #[tokio::test]
+    async fn can_invalidate_tag() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+
+        app_ctx
+            .cache
+            .insert_tagged("fragment:user:1", &"a", &["user:1"])
+            .await
+            .unwrap();
+        app_ctx
+            .cache
+            .insert_tagged("fragment:user:2", &"b", &["user:2"])
+            .await
+            .unwrap();
+
+        app_ctx.cache.invalidate_tag("user:1").await.unwrap();
+
+        assert!(!app_ctx.cache.contains_key("fragment:user:1").await.unwrap());
+        assert!(app_ctx.cache.contains_key("fragment:user:2").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn can_clear_namespace() {
+        let app_ctx = tests_cfg::app::get_app_context().await;
+        let user_1 = app_ctx.cache.namespace("user:1");
+        let user_2 = app_ctx.cache.namespace("user:2");
+
+        user_1.insert("profile", &"Alice").await.unwrap();
+        user_2.insert("profile", &"Bob").await.unwrap();
+
+        assert_eq!(
+            app_ctx.cache.get::<String>("user:1:profile").await.unwrap(),
+            Some("Alice".to_string())
+        );
+
+        user_1.clear().await.unwrap();
+
+        assert_eq!(user_1.get::<String>("profile").await.unwrap(), None);
+        assert_eq!(
+            user_2.get::<String>("profile").await.unwrap(),
+            Some("Bob".to_string())
+        );
+    }
 }
@@ -31,8 +31,8 @@ use crate::controller;
 use crate::{
     app::{AppContext, Hooks},
     boot::{
-        create_app, create_context, list_endpoints, list_middlewares, run_scheduler, run_task,
-        start, RunDbCommand, ServeParams, StartMode,
+        create_app, create_context, list_endpoints, list_middlewares, preview_scheduler,
+        run_scheduler, run_task, start, RunDbCommand, ServeParams, StartMode,
     },
     config::Config,
     doctor,
@@ -115,6 +115,16 @@ enum Commands {
         #[command(subcommand)]
         command: JobsCommands,
     },
+    /// Inspect and manage cache entries.
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommands,
+    },
+    /// Compare and synchronize storage stores.
+    Storage {
+        #[command(subcommand)]
+        command: StorageCommands,
+    },
     /// Run the scheduler
     Scheduler {
         /// Run a specific job by its name.
@@ -131,6 +141,9 @@ enum Commands {
         /// Show all configured jobs
         #[arg(short, long, action)]
         list: bool,
+        /// Print the next N fire times of each job and flag invalid schedules
+        #[arg(long, value_name = "N")]
+        next: Option<usize>,
     },
     /// code generation creates a set of files and code templates based on a
     /// predefined set of rules.
@@ -640,6 +653,101 @@ enum JobsCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum CacheCommands {
+    /// Prints the stored value of a key and its time to live.
+    Get {
+        /// The key to print.
+        key: String,
+    },
+    /// Deletes one or more keys.
+    Delete {
+        /// The keys to delete.
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Deletes every entry of the cache.
+    Clear {},
+    /// Prints the hits and misses of every process using the cache.
+    Stats {},
+}
+
+#[derive(Subcommand)]
+enum StorageCommands {
+    /// Copies the objects missing or differing in a store from another store.
+    /// Without `--from` and `--to`, synchronizes the secondaries of the
+    /// configured mirror or backup strategy from its primary.
+    Sync {
+        #[command(flatten)]
+        stores: StoreArgs,
+        /// Deletes the objects which are not in the source store.
+        #[arg(long, action)]
+        delete: bool,
+        /// Prints the objects to synchronize without changing them.
+        #[arg(long, action)]
+        dry_run: bool,
+    },
+    /// Prints the objects missing or differing in a store compared to another
+    /// store, and exits with an error when there are any. Without `--from`
+    /// and `--to`, compares the secondaries of the configured mirror or
+    /// backup strategy to its primary.
+    Verify {
+        #[command(flatten)]
+        stores: StoreArgs,
+    },
+}
+
+#[derive(clap::Args)]
+struct StoreArgs {
+    /// Name of the source store.
+    #[arg(long, requires = "to")]
+    from: Option<String>,
+    /// Name of the target store.
+    #[arg(long, requires = "from")]
+    to: Option<String>,
+    /// Only compares the objects under this directory, such as `users/`.
+    #[arg(long)]
+    prefix: Option<PathBuf>,
+    /// Compares the content of objects of the same size.
+    #[arg(long, action)]
+    checksum: bool,
+}
+
+impl StoreArgs {
+    /// Returns the pairs of source and target stores to compare.
+    fn pairs(&self, config: &Config) -> crate::Result<Vec<(String, String)>> {
+        use crate::config::StorageStrategyConfig;
+
+        if let (Some(from), Some(to)) = (&self.from, &self.to) {
+            return Ok(vec![(from.clone(), to.clone())]);
+        }
+        match config
+            .storage
+            .as_ref()
+            .and_then(|storage| storage.strategy.as_ref())
+        {
+            Some(
+                StorageStrategyConfig::Mirror {
+                    primary,
+                    secondaries,
+                    ..
+                }
+                | StorageStrategyConfig::Backup {
+                    primary,
+                    secondaries,
+                    ..
+                },
+            ) => Ok(secondaries
+                .iter()
+                .map(|secondary| (primary.clone(), secondary.clone()))
+                .collect()),
+            _ => Err(Error::string(
+                "no mirror or backup storage strategy is configured, set `--from` and `--to`",
+            )),
+        }
+    }
+}
+
 /// Parse a single key-value pair
 fn parse_key_val<T, U>(
     s: &str,
@@ -758,6 +866,12 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
         Commands::Jobs { command } => {
             handle_job_command::<H>(command, &environment, app_context.config).await?;
         }
+        Commands::Cache { command } => {
+            handle_cache_command(command, &app_context).await?;
+        }
+        Commands::Storage { command } => {
+            handle_storage_command(command, &app_context).await?;
+        }
         Commands::Routes {} => {
             let app_context = create_context::<H>(&environment, app_context.config).await?;
             show_list_endpoints::<H>(&app_context);
@@ -791,9 +905,14 @@ pub async fn main<H: Hooks, M: MigratorTrait>() -> crate::Result<()> {
             config_path,
             tag,
             list,
+            next,
         } => {
             let app_context = create_context::<H>(&environment, app_context.config).await?;
-            run_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, list).await?;
+            if let Some(count) = next {
+                preview_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, count)?;
+            } else {
+                run_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, list).await?;
+            }
         }
         #[cfg(debug_assertions)]
         Commands::Generate { component } => {
@@ -924,13 +1043,20 @@ pub async fn main<H: Hooks>() -> crate::Result<()> {
         Commands::Jobs { command } => {
             handle_job_command::<H>(command, &environment, app_context.config).await?
         }
+        Commands::Cache { command } => handle_cache_command(command, &app_context).await?,
+        Commands::Storage { command } => handle_storage_command(command, &app_context).await?,
         Commands::Scheduler {
             name,
             config_path,
             tag,
             list,
+            next,
         } => {
-            run_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, list).await?;
+            if let Some(count) = next {
+                preview_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, count)?;
+            } else {
+                run_scheduler::<H>(&app_context, config_path.as_ref(), name, tag, list).await?;
+            }
         }
         #[cfg(debug_assertions)]
         Commands::Generate { component } => {
@@ -1266,6 +1392,132 @@ async fn handle_job_command<H: Hooks>(
     }
 }
 
+async fn handle_cache_command(
+    command: CacheCommands,
+    app_context: &AppContext,
+) -> crate::Result<()> {
+    let cache = &app_context.cache;
+    match command {
+        CacheCommands::Get { key } => {
+            // read through the driver so inspecting a key doesn't count as a lookup
+            let Some(value) = cache.driver.get(&key).await? else {
+                println!("{}", format!("key `{key}` not found").red());
+                exit(1);
+            };
+            let ttl = cache.driver.ttl(&key).await?;
+            println!(
+                "{} {}",
+                "ttl:".bold(),
+                ttl.map_or_else(|| "none".to_string(), |ttl| format!("{}s", ttl.as_secs()))
+            );
+            match cache.encoder().decode::<serde_json::Value>(&value) {
+                Ok(json) => println!("{}", serde_json::to_string_pretty(&json)?),
+                // values which aren't self-describing, such as bincode
+                Err(_) => println!("<{} bytes>", value.len()),
+            }
+        }
+        CacheCommands::Delete { keys } => {
+            let keys: Vec<&str> = keys.iter().map(String::as_str).collect();
+            cache.remove_many(&keys).await?;
+            println!("Deleted {} key(s)", keys.len());
+        }
+        CacheCommands::Clear {} => {
+            cache.clear().await?;
+            println!("Cache cleared");
+        }
+        CacheCommands::Stats {} => {
+            let stats = cache.shared_stats().await?;
+            println!("{:<10} {}", "hits".bold(), stats.hits);
+            println!("{:<10} {}", "misses".bold(), stats.misses);
+            println!(
+                "{:<10} {:.2}%",
+                "hit ratio".bold(),
+                stats.hit_ratio() * 100.0
+            );
+        }
+    }
+    Ok(())
+}
+
+async fn handle_storage_command(
+    command: StorageCommands,
+    app_context: &AppContext,
+) -> crate::Result<()> {
+    use crate::storage::sync::{self, Drift, SyncOptions};
+
+    let (stores, options) = match &command {
+        StorageCommands::Sync {
+            stores,
+            delete,
+            dry_run,
+        } => (
+            stores,
+            SyncOptions {
+                prefix: stores.prefix.clone().unwrap_or_default(),
+                checksum: stores.checksum,
+                delete: *delete,
+                dry_run: *dry_run,
+            },
+        ),
+        StorageCommands::Verify { stores } => (
+            stores,
+            SyncOptions {
+                prefix: stores.prefix.clone().unwrap_or_default(),
+                checksum: stores.checksum,
+                ..SyncOptions::default()
+            },
+        ),
+    };
+
+    let mut out_of_sync = false;
+    for (from, to) in stores.pairs(&app_context.config)? {
+        let source = app_context.storage.as_store_err(&from)?;
+        let target = app_context.storage.as_store_err(&to)?;
+        println!("{}", format!("{from} -> {to}").bold());
+
+        let (drifts, failed) = match command {
+            StorageCommands::Sync { .. } => {
+                let report = sync::sync(source, target, &options).await?;
+                (report.drifts, report.failed)
+            }
+            StorageCommands::Verify { .. } => (
+                sync::verify(source, target, &options).await?,
+                BTreeMap::new(),
+            ),
+        };
+        for entry in &drifts {
+            let drift = format!("{:<8}", entry.drift.to_string());
+            let drift = match entry.drift {
+                Drift::Missing => drift.red(),
+                Drift::Differs => drift.yellow(),
+                Drift::Extra => drift.dimmed(),
+            };
+            match failed.get(&entry.path) {
+                Some(err) => println!("{drift} {} ({})", entry.path.display(), err.red()),
+                None => println!("{drift} {}", entry.path.display()),
+            }
+        }
+        if failed.is_empty() {
+            println!("{} object(s) out of sync\n", drifts.len());
+        } else {
+            println!(
+                "{} object(s) out of sync, {} failed to synchronize\n",
+                drifts.len(),
+                failed.len()
+            );
+        }
+
+        out_of_sync |= match command {
+            StorageCommands::Sync { .. } => !failed.is_empty(),
+            StorageCommands::Verify { .. } => !drifts.is_empty(),
+        };
+    }
+    if out_of_sync {
+        exit(1);
+    }
+    Ok(())
+}
+
 #[cfg(debug_assertions)]
 fn handle_generate_command<H: Hooks>(
     component: ComponentArg,
@@ -56,6 +56,7 @@ pub struct Config {
     #[serde(default)]
     pub workers: Workers,
     pub mailer: Option<Mailer>,
+    pub storage: Option<StorageConfig>,
     pub initializers: Option<Initializers>,
 
     /// Custom app settings
@@ -251,6 +252,12 @@ pub enum CacheConfig {
     #[cfg(feature = "cache_redis")]
     /// Redis cache
     Redis(RedisCacheConfig),
+    #[cfg(all(feature = "cache_inmem", feature = "cache_redis"))]
+    /// In-memory cache in front of a Redis cache
+    Tiered(TieredCacheConfig),
+    #[cfg(feature = "with-db")]
+    /// Cache stored in a table of the application database
+    Database(DatabaseCacheConfig),
     /// Null cache
     #[default]
     Null,
@@ -271,6 +278,99 @@ pub struct RedisCacheConfig {
     pub uri: String,
     /// Sets the maximum number of connections managed by the pool.
     pub max_size: u32,
+    /// Distributed lock used by `get_or_insert` so only one instance loads a
+    /// missing key. Disabled when not set.
+    #[serde(default)]
+    pub lock: Option<RedisCacheLockConfig>,
+    /// How values are serialized and compressed.
+    #[serde(flatten)]
+    pub encoding: CacheEncodingConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RedisCacheLockConfig {
+    /// How long a lock is held before it expires, in milliseconds.
+    #[serde(default = "cache_redis_lock_ttl")]
+    pub ttl: u64,
+    /// How long to wait for another instance to finish loading a key, in
+    /// milliseconds.
+    #[serde(default = "cache_redis_lock_wait")]
+    pub wait: u64,
+}
+
+fn cache_redis_lock_ttl() -> u64 {
+    10_000
+}
+
+fn cache_redis_lock_wait() -> u64 {
+    5_000
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TieredCacheConfig {
+    /// The Redis (L2) cache
+    #[serde(flatten)]
+    pub redis: RedisCacheConfig,
+    /// Maximum number of entries kept in the in-memory (L1) cache.
+    #[serde(default = "cache_tiered_l1_max_capacity")]
+    pub l1_max_capacity: u64,
+    /// Time to live of in-memory (L1) entries in milliseconds.
+    #[serde(default = "cache_tiered_l1_ttl")]
+    pub l1_ttl: u64,
+    /// Redis pub/sub channel used to tell other instances to evict their L1
+    /// entries when a key changes. Fan-out is disabled when not set.
+    pub invalidation_channel: Option<String>,
+}
+
+fn cache_tiered_l1_max_capacity() -> u64 {
+    10_000
+}
+
+fn cache_tiered_l1_ttl() -> u64 {
+    5_000
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DatabaseCacheConfig {
+    /// The URI of the database holding the cache table. Defaults to the
+    /// application database.
+    pub uri: Option<String>,
+    /// How often expired entries are deleted, in milliseconds. `0` disables
+    /// the periodic cleanup.
+    #[serde(default = "cache_database_cleanup_interval")]
+    pub cleanup_interval: u64,
+    /// How values are serialized and compressed.
+    #[serde(flatten)]
+    pub encoding: CacheEncodingConfig,
+}
+
+fn cache_database_cleanup_interval() -> u64 {
+    60_000
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CacheEncodingConfig {
+    /// Format used to serialize values. Defaults to JSON.
+    #[serde(default)]
+    pub codec: crate::cache::codec::Codec,
+    /// Compresses values larger than a threshold. Disabled when not set.
+    #[serde(default)]
+    pub compression: Option<CacheCompressionConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CacheCompressionConfig {
+    pub algorithm: crate::cache::codec::Compression,
+    /// Values are compressed when their serialized size in bytes exceeds this
+    /// threshold.
+    #[serde(default = "cache_compression_threshold")]
+    pub threshold: usize,
+    /// Compression level, the algorithm's default when not set.
+    pub level: Option<i32>,
+}
+
+fn cache_compression_threshold() -> usize {
+    1024
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -384,6 +484,183 @@ fn num_workers() -> u32 {
     2
 }
 
+/// Storage configuration, built into `AppContext.storage`. The storage is a
+/// null store when not set.
+///
+/// Example (production):
+/// ```yaml
+/// # config/production.yaml
+/// storage:
+///   stores:
+///     files:
+///       kind: S3
+///       bucket: my-app-files
+///       region: us-east-1
+///     archive:
+///       kind: Local
+///       root: /var/lib/my-app/archive
+///   strategy:
+///     kind: Backup
+///     primary: files
+///     secondaries: [archive]
+///     failure_mode: AllowBackupFailure
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StorageConfig {
+    /// Stores by name.
+    pub stores: BTreeMap<String, StoreConfig>,
+    /// How operations are spread over the stores. Optional when there is a
+    /// single store.
+    pub strategy: Option<StorageStrategyConfig>,
+    /// Signs the URLs served by the application for stores which can't
+    /// presign requests, such as `Local` and `Mem`.
+    pub signed_urls: Option<SignedUrlsConfig>,
+    /// Deletes the objects under a prefix once they are older than the
+    /// expiry of the rule, see [`crate::storage::lifecycle`].
+    #[serde(default)]
+    pub lifecycle: Vec<LifecycleRule>,
+}
+
+/// Example:
+/// ```yaml
+/// lifecycle:
+///   - prefix: tmp/exports/
+///     expire_after: 86400 # 24 hours
+/// ```
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LifecycleRule {
+    /// Directory path of the objects, such as `tmp/exports/`.
+    pub prefix: String,
+    /// Age in seconds after which an object is deleted, based on its last
+    /// modification time.
+    pub expire_after: u64,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum StoreConfig {
+    /// Files on the local filesystem
+    Local(LocalStoreConfig),
+    /// In-memory store, lost when the application stops
+    Mem,
+    #[cfg(feature = "storage_aws_s3")]
+    /// AWS S3 or a S3 compatible service
+    S3(S3StoreConfig),
+    #[cfg(feature = "storage_gcp")]
+    /// Google Cloud Storage
+    Gcs(GcsStoreConfig),
+    #[cfg(feature = "storage_azure")]
+    /// Azure Blob Storage
+    Azure(AzureStoreConfig),
+    #[cfg(feature = "storage_encryption")]
+    /// Another store, with the content encrypted by the application
+    Encrypted(EncryptedStoreConfig),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LocalStoreConfig {
+    /// Directory holding the files. Paths are relative to the filesystem
+    /// root when not set.
+    pub root: Option<String>,
+}
+
+#[cfg(feature = "storage_aws_s3")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct S3StoreConfig {
+    pub bucket: String,
+    pub region: String,
+    /// Endpoint of a S3 compatible service, requires `credentials`.
+    pub endpoint: Option<String>,
+    /// Credentials, read from the environment when not set.
+    pub credentials: Option<S3Credentials>,
+}
+
+#[cfg(feature = "storage_aws_s3")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct S3Credentials {
+    pub key_id: String,
+    pub secret_key: String,
+    pub token: Option<String>,
+}
+
+#[cfg(feature = "storage_gcp")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GcsStoreConfig {
+    pub bucket: String,
+    pub credential_path: String,
+}
+
+#[cfg(feature = "storage_azure")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AzureStoreConfig {
+    pub container: String,
+    pub account_name: String,
+    pub access_key: String,
+    pub endpoint: String,
+}
+
+/// Example:
+/// ```yaml
+/// documents:
+///   kind: Encrypted
+///   store:
+///     kind: S3
+///     bucket: my-app-documents
+///     region: us-east-1
+///   keys:
+///     - id: "2024-06"
+///       key: {{ get_env(name="DOCUMENTS_KEY") }}
+///     - id: "2023-01"
+///       key: {{ get_env(name="DOCUMENTS_OLD_KEY") }}
+/// ```
+#[cfg(feature = "storage_encryption")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EncryptedStoreConfig {
+    /// Store holding the encrypted content.
+    pub store: Box<StoreConfig>,
+    /// Encryption keys. The first key encrypts new content, all keys decrypt
+    /// existing content.
+    pub keys: Vec<EncryptionKeyConfig>,
+}
+
+#[cfg(feature = "storage_encryption")]
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EncryptionKeyConfig {
+    /// Identifies the key in the stored content. Must not change once
+    /// content is encrypted with the key.
+    pub id: String,
+    /// 256 bits key, base64 encoded.
+    pub key: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "kind")]
+pub enum StorageStrategyConfig {
+    /// All operations use one store
+    Single { store: String },
+    /// Writes go to all stores, reads fall back to the secondaries
+    Mirror {
+        primary: String,
+        secondaries: Vec<String>,
+        failure_mode: crate::storage::strategies::mirror::FailureMode,
+    },
+    /// Writes go to all stores, reads only use the primary
+    Backup {
+        primary: String,
+        secondaries: Vec<String>,
+        failure_mode: crate::storage::strategies::backup::FailureMode,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SignedUrlsConfig {
+    /// Secret used to sign the URLs.
+    pub secret: String,
+    /// Base URL of the application serving the URLs, such as
+    /// `https://example.com`.
+    pub base_url: String,
+}
+
 /// User authentication configuration.
 ///
 /// Example (development):
@@ -529,9 +806,24 @@ pub enum WorkerMode {
 ///     port: 1025
 ///     secure: false
 /// ```
+///
+/// Example (staging), to write mails to `.eml` files instead of sending them:
+/// ```yaml
+/// # config/staging.yaml
+/// mailer:
+///   file:
+///     enable: true
+///     dir: tmp/mails
+/// ```
+///
+/// Only one of the `smtp`, `file`, `sendmail` and `http` transports can be
+/// enabled, and `stub` takes precedence over all of them.
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Mailer {
     pub smtp: Option<SmtpMailer>,
+    pub file: Option<FileMailer>,
+    pub sendmail: Option<SendmailMailer>,
+    pub http: Option<HttpMailer>,
 
     #[serde(default)]
     pub stub: bool,
@@ -567,6 +859,37 @@ pub struct SmtpMailer {
     pub hello_name: Option<String>,
 }
 
+/// File mailer configuration, writing every mail to an `.eml` file.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileMailer {
+    pub enable: bool,
+    /// Directory of the mails, created when missing.
+    pub dir: PathBuf,
+}
+
+/// Sendmail mailer configuration, piping every mail to a sendmail compatible
+/// binary.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SendmailMailer {
+    pub enable: bool,
+    /// Path of the binary, `sendmail` from the `PATH` by default.
+    pub command: Option<String>,
+}
+
+/// HTTP API mailer configuration, posting every mail as JSON to an endpoint,
+/// such as the webhook of an email delivery service.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HttpMailer {
+    pub enable: bool,
+    /// URL receiving the `POST` requests.
+    pub url: String,
+    /// Headers of the requests, such as `Authorization`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Request timeout in milliseconds.
+    pub timeout: Option<u64>,
+}
+
 /// Authentication details for the mailer
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MailerAuth {
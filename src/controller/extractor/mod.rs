@@ -1,4 +1,5 @@
 #[cfg(feature = "auth_jwt")]
 pub mod auth;
 pub mod shared_store;
+pub mod upload;
 pub mod validate;
@@ -0,0 +1,651 @@
+//! # Storage Uploads
+//!
+//! The [`Uploads`] extractor reads a `multipart/form-data` request and
+//! streams each uploaded file into the application storage with
+//! [`Storage::upload_stream`], without buffering whole files in memory.
+//!
+//! Files are stored under generated keys, and their content type is sniffed
+//! from the content instead of trusting the type sent by the client. The
+//! limits are set with an [`Uploader`], added to the routes as an
+//! [`axum::Extension`]; without one, the defaults of [`Uploader::new`] apply.
+//!
+//! # Example
+//!
+//! ```rust
+//! use axum::Extension;
+//! use loco_rs::{controller::extractor::upload::{Uploader, Uploads}, prelude::*};
+//!
+//! async fn upload_avatar(uploads: Uploads) -> Result<Response> {
+//!     let Some(avatar) = uploads.file("avatar") else {
+//!         return bad_request("missing avatar");
+//!     };
+//!     format::json(avatar)
+//! }
+//!
+//! pub fn routes() -> Routes {
+//!     Routes::new().prefix("/avatars").add(
+//!         "/",
+//!         post(upload_avatar).layer(Extension(
+//!             Uploader::new()
+//!                 .prefix("avatars")
+//!                 .max_size(2 * 1024 * 1024)
+//!                 .allowed_types(["image/png", "image/jpeg"]),
+//!         )),
+//!     )
+//! }
+//! ```
+//!
+//! The request body limit of the server (`server.middlewares.limit`) applies
+//! before these limits, and must be raised to accept large files.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    extract::{
+        multipart::{Field, MultipartError},
+        FromRequest, Multipart, Request,
+    },
+    http::StatusCode,
+};
+use bytes::{Bytes, BytesMut};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    app::AppContext,
+    controller::ErrorDetail,
+    storage::{stream::BytesStream, Storage},
+    Error, Result,
+};
+
+/// Number of bytes read from the start of a file to sniff its content type.
+const SNIFF_LEN: usize = 512;
+
+/// Content type of files whose type can't be recognized.
+const OCTET_STREAM: &str = "application/octet-stream";
+
+/// Content types sent by clients which are kept when the content is text, as
+/// text formats can't be recognized from their content.
+const TEXT_TYPES: &[&str] = &["application/json", "application/yaml"];
+
+/// Text content types which can run scripts when served to a browser. They
+/// are only kept when the allowed types name them exactly, as they can't be
+/// told apart from harmless text.
+const SCRIPT_TYPES: &[&str] = &[
+    "text/html",
+    "text/javascript",
+    "text/xml",
+    "application/javascript",
+    "application/xhtml+xml",
+    "application/xml",
+    "image/svg+xml",
+];
+
+/// Metadata of a file stored by an [`Uploader`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UploadedFile {
+    /// Name of the form field holding the file.
+    pub field: String,
+    /// File name sent by the client, if any.
+    pub file_name: Option<String>,
+    /// Key of the file in the storage.
+    pub key: PathBuf,
+    /// Content type sniffed from the content.
+    pub content_type: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// SHA-256 digest of the content, hex encoded.
+    pub checksum: String,
+}
+
+/// Files and text fields of a `multipart/form-data` request, with the files
+/// stored in `ctx.storage`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Uploads {
+    /// Stored files, in the order of the request.
+    pub files: Vec<UploadedFile>,
+    /// Text fields of the request.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Uploads {
+    /// Returns the first file uploaded in the given field.
+    #[must_use]
+    pub fn file(&self, field: &str) -> Option<&UploadedFile> {
+        self.files.iter().find(|file| file.field == field)
+    }
+
+    /// Returns the value of a text field.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields.get(name).map(String::as_str)
+    }
+}
+
+impl FromRequest<AppContext> for Uploads {
+    type Rejection = Error;
+
+    async fn from_request(req: Request, ctx: &AppContext) -> Result<Self> {
+        let uploader = req
+            .extensions()
+            .get::<Uploader>()
+            .cloned()
+            .unwrap_or_default();
+        let multipart = Multipart::from_request(req, ctx)
+            .await
+            .map_err(|err| Error::BadRequest(err.body_text()))?;
+        uploader.upload(&ctx.storage, multipart).await
+    }
+}
+
+/// Stores the files of `multipart/form-data` requests, with limits on their
+/// size and content type.
+#[derive(Debug, Clone, Default)]
+pub struct Uploader {
+    prefix: Option<PathBuf>,
+    max_size: Option<u64>,
+    allowed_types: Vec<String>,
+}
+
+impl Uploader {
+    /// Creates an uploader storing files of any size and type at the root of
+    /// the storage.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the prefix of the generated keys.
+    #[must_use]
+    pub fn prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets the maximum size of each file, in bytes. Larger files are
+    /// rejected with `413 Payload Too Large`.
+    #[must_use]
+    pub const fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets the allowed content types, such as `image/png`, or `image/*` for
+    /// all images. Files of other types are rejected with
+    /// `415 Unsupported Media Type`.
+    #[must_use]
+    pub fn allowed_types<I, T>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.allowed_types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Stores the files of a multipart request, and collects its text fields.
+    ///
+    /// # Errors
+    ///
+    /// When the request can't be read, a file is too large or of a type which
+    /// is not allowed, or the storage fails. Files stored before the error
+    /// are kept.
+    pub async fn upload(&self, storage: &Storage, mut multipart: Multipart) -> Result<Uploads> {
+        let mut uploads = Uploads::default();
+        while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+            let Some(name) = field.name().map(ToString::to_string) else {
+                continue;
+            };
+            if field.file_name().is_some() {
+                uploads.files.push(self.store(storage, name, field).await?);
+            } else {
+                let value = field.text().await.map_err(multipart_error)?;
+                uploads.fields.insert(name, value);
+            }
+        }
+        Ok(uploads)
+    }
+
+    async fn store(
+        &self,
+        storage: &Storage,
+        name: String,
+        mut field: Field<'_>,
+    ) -> Result<UploadedFile> {
+        let file_name = field.file_name().map(ToString::to_string);
+        let declared_type = field.content_type().map(ToString::to_string);
+
+        let mut head = BytesMut::new();
+        while head.len() < SNIFF_LEN {
+            match field.chunk().await.map_err(multipart_error)? {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => break,
+            }
+        }
+        let (content_type, extension) = sniff(&head, declared_type.as_deref(), &self.allowed_types);
+        if !self.is_allowed(&content_type) {
+            return Err(Error::CustomError(
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                ErrorDetail::new(
+                    "unsupported_media_type",
+                    format!("files of type `{content_type}` are not allowed"),
+                ),
+            ));
+        }
+        let extension = extension.or_else(|| {
+            file_name
+                .as_deref()
+                .and_then(|file_name| file_name_extension(file_name, &content_type))
+        });
+        let key = self.key(extension.as_deref());
+
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<std::io::Result<Bytes>>(4);
+        let stream = BytesStream::from_body_stream(futures_util::stream::poll_fn(move |cx| {
+            rx.poll_recv(cx)
+        }));
+        let max_size = self.max_size;
+        let pump = async move {
+            let mut size = 0u64;
+            let mut hasher = Sha256::new();
+            let mut chunk = Some(head.freeze());
+            while let Some(bytes) = chunk {
+                size += bytes.len() as u64;
+                hasher.update(&bytes);
+                if max_size.is_some_and(|max_size| size > max_size) {
+                    let _ = tx
+                        .send(Err(std::io::Error::new(
+                            std::io::ErrorKind::Other,
+                            "file too large",
+                        )))
+                        .await;
+                    return Err(too_large(max_size.unwrap_or_default()));
+                }
+                if tx.send(Ok(bytes)).await.is_err() {
+                    // the storage stopped reading, its error is returned
+                    break;
+                }
+                chunk = match field.chunk().await {
+                    Ok(chunk) => chunk,
+                    Err(err) => {
+                        let _ = tx
+                            .send(Err(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                err.body_text(),
+                            )))
+                            .await;
+                        return Err(multipart_error(err));
+                    }
+                };
+            }
+            Ok((size, hex::encode(hasher.finalize())))
+        };
+
+        match tokio::join!(pump, storage.upload_stream(&key, stream)) {
+            (Ok((size, checksum)), Ok(_)) => Ok(UploadedFile {
+                field: name,
+                file_name,
+                key,
+                content_type,
+                size,
+                checksum,
+            }),
+            (Err(err), _) => {
+                let _ = storage.delete(&key).await;
+                Err(err)
+            }
+            (Ok(_), Err(err)) => Err(err.into()),
+        }
+    }
+
+    fn is_allowed(&self, content_type: &str) -> bool {
+        self.allowed_types.is_empty()
+            || self.allowed_types.iter().any(|allowed| {
+                allowed
+                    .strip_suffix("/*")
+                    .map_or(allowed == content_type, |kind| {
+                        content_type
+                            .split_once('/')
+                            .is_some_and(|(content_kind, _)| content_kind == kind)
+                    })
+            })
+    }
+
+    fn key(&self, extension: Option<&str>) -> PathBuf {
+        let mut name = uuid::Uuid::new_v4().to_string();
+        if let Some(extension) = extension {
+            name = format!("{name}.{extension}");
+        }
+        self.prefix
+            .as_deref()
+            .map_or_else(|| PathBuf::from(&name), |prefix| prefix.join(&name))
+    }
+}
+
+/// Returns the content type and extension of a file from the first bytes of
+/// its content.
+///
+/// Binary formats are recognized from their signature. Text can't be, so the
+/// type sent by the client is kept for text content when it is a text type,
+/// and `text/plain` is used otherwise. Types which can run scripts, such as
+/// HTML or SVG, are only kept, whether recognized or declared, when
+/// `allowed_types` names them exactly.
+fn sniff(
+    head: &[u8],
+    declared_type: Option<&str>,
+    allowed_types: &[String],
+) -> (String, Option<String>) {
+    let is_script =
+        |content_type: &str| SCRIPT_TYPES.contains(&content_type) || content_type.ends_with("+xml");
+    let is_allowed =
+        |content_type: &str| allowed_types.iter().any(|allowed| allowed == content_type);
+    if let Some(kind) =
+        infer::get(head).filter(|kind| !is_script(kind.mime_type()) || is_allowed(kind.mime_type()))
+    {
+        return (
+            kind.mime_type().to_string(),
+            Some(kind.extension().to_string()),
+        );
+    }
+    if !is_text(head) {
+        return (OCTET_STREAM.to_string(), None);
+    }
+    let declared_type = declared_type
+        .and_then(|declared| declared.split(';').next())
+        .map(|declared| declared.trim().to_ascii_lowercase())
+        .filter(|declared| {
+            if is_script(declared) {
+                return is_allowed(declared);
+            }
+            declared.starts_with("text/")
+                || TEXT_TYPES.contains(&declared.as_str())
+                || declared.ends_with("+json")
+        });
+    (
+        declared_type.unwrap_or_else(|| "text/plain".to_string()),
+        None,
+    )
+}
+
+/// Returns whether the bytes are UTF-8 text, allowing a character cut at the
+/// end.
+fn is_text(bytes: &[u8]) -> bool {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => !text.contains('\0'),
+        Err(err) => err.error_len().is_none() && !bytes[..err.valid_up_to()].contains(&0),
+    }
+}
+
+/// Returns the extension of a file name sent by the client, when it is a
+/// plain alphanumeric extension of the content type of the file.
+///
+/// Stores without content types serve files with the type guessed from
+/// their extension, so an extension of another type, such as `.html` on
+/// text, would change how the file is served.
+fn file_name_extension(file_name: &str, content_type: &str) -> Option<String> {
+    Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| {
+            extension.len() <= 10 && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        .map(str::to_ascii_lowercase)
+        .filter(|extension| {
+            mime_guess::from_ext(extension)
+                .first()
+                .is_some_and(|guess| guess.essence_str() == content_type)
+        })
+}
+
+fn too_large(max_size: u64) -> Error {
+    Error::CustomError(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        ErrorDetail::new(
+            "payload_too_large",
+            format!("files must be at most {max_size} bytes"),
+        ),
+    )
+}
+
+fn multipart_error(err: MultipartError) -> Error {
+    Error::CustomError(err.status(), ErrorDetail::with_reason(err.body_text()))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, routing::post, Extension, Router};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::storage::drivers;
+
+    const PNG: &[u8] = &[
+        0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0x0D, b'I', b'H', b'D', b'R',
+    ];
+    const BOUNDARY: &str = "loco-boundary";
+
+    fn multipart_body(parts: &[(&str, Option<(&str, &str)>, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, file, content) in parts {
+            body.extend(format!("--{BOUNDARY}\r\n").as_bytes());
+            match file {
+                Some((file_name, content_type)) => body.extend(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{name}\"; \
+                         filename=\"{file_name}\"\r\nContent-Type: {content_type}\r\n\r\n"
+                    )
+                    .as_bytes(),
+                ),
+                None => body.extend(
+                    format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n").as_bytes(),
+                ),
+            }
+            body.extend(*content);
+            body.extend(b"\r\n");
+        }
+        body.extend(format!("--{BOUNDARY}--\r\n").as_bytes());
+        body
+    }
+
+    async fn send(
+        uploader: Uploader,
+        parts: &[(&str, Option<(&str, &str)>, &[u8])],
+    ) -> (StatusCode, serde_json::Value, AppContext) {
+        async fn handler(uploads: Uploads) -> axum::Json<Uploads> {
+            axum::Json(uploads)
+        }
+
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.storage = Storage::single(drivers::mem::new()).into();
+        let router = Router::new()
+            .route("/upload", post(handler).layer(Extension(uploader)))
+            .with_state(ctx.clone());
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/upload")
+            .header(
+                "content-type",
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(multipart_body(parts)))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (
+            status,
+            serde_json::from_slice(&body).unwrap_or_default(),
+            ctx,
+        )
+    }
+
+    #[tokio::test]
+    async fn can_upload_files_and_fields() {
+        let large_text = "loco ".repeat(1000);
+        let (status, body, ctx) = send(
+            Uploader::new().prefix("uploads"),
+            &[
+                ("title", None, b"Holidays"),
+                // the content type is sniffed from the content
+                ("photo", Some(("photo.jpg", "image/jpeg")), PNG),
+                (
+                    "notes",
+                    Some(("notes.md", "text/markdown")),
+                    large_text.as_bytes(),
+                ),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["fields"]["title"], "Holidays");
+
+        let photo = &body["files"][0];
+        assert_eq!(photo["field"], "photo");
+        assert_eq!(photo["file_name"], "photo.jpg");
+        assert_eq!(photo["content_type"], "image/png");
+        assert_eq!(photo["size"], PNG.len());
+        let key = photo["key"].as_str().unwrap();
+        assert!(key.starts_with("uploads/") && key.ends_with(".png"));
+        let content: Vec<u8> = ctx.storage.download(Path::new(key)).await.unwrap();
+        assert_eq!(content, PNG);
+
+        let notes = &body["files"][1];
+        assert_eq!(notes["content_type"], "text/markdown");
+        assert_eq!(notes["size"], large_text.len());
+        assert_eq!(
+            notes["checksum"],
+            crate::storage::checksum::Checksums::of(large_text.as_bytes()).sha256
+        );
+        assert!(notes["key"].as_str().unwrap().ends_with(".md"));
+        let content: String = ctx
+            .storage
+            .download(Path::new(notes["key"].as_str().unwrap()))
+            .await
+            .unwrap();
+        assert_eq!(content, large_text);
+    }
+
+    #[tokio::test]
+    async fn cant_upload_disallowed_type() {
+        let (status, body, _) = send(
+            Uploader::new().allowed_types(["image/*"]),
+            &[(
+                "photo",
+                Some(("photo.png", "image/png")),
+                b"<script></script>",
+            )],
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            body["description"],
+            "files of type `text/plain` are not allowed"
+        );
+    }
+
+    #[tokio::test]
+    async fn cant_upload_svg_as_image() {
+        const SVG: &[u8] = b"<svg xmlns=\"http://www.w3.org/2000/svg\" onload=\"alert(1)\"/>";
+        let (status, body, _) = send(
+            Uploader::new().allowed_types(["image/*"]),
+            &[("photo", Some(("photo.svg", "image/svg+xml")), SVG)],
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(
+            body["description"],
+            "files of type `text/plain` are not allowed"
+        );
+
+        // allowed when named exactly
+        let (status, body, _) = send(
+            Uploader::new().allowed_types(["image/svg+xml"]),
+            &[("logo", Some(("logo.svg", "image/svg+xml")), SVG)],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["files"][0]["content_type"], "image/svg+xml");
+    }
+
+    #[tokio::test]
+    async fn cant_serve_text_as_html() {
+        let (status, body, ctx) = send(
+            Uploader::new(),
+            &[(
+                "page",
+                Some(("a.html", "text/html")),
+                b"<script>alert(1)</script>",
+            )],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let file = &body["files"][0];
+        assert_eq!(file["content_type"], "text/plain");
+        let key = file["key"].as_str().unwrap();
+        assert!(Path::new(key).extension().is_none());
+
+        let router = crate::storage::serve::routes("/files", "")
+            .handlers
+            .into_iter()
+            .fold(Router::new(), |router, handler| {
+                router.route(&format!("/files{}", handler.uri), handler.method)
+            })
+            .with_state(ctx);
+        let request = axum::http::Request::builder()
+            .uri(format!("/files/{key}"))
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_ne!(headers["content-type"], "text/html");
+        assert_eq!(headers["x-content-type-options"], "nosniff");
+    }
+
+    #[test]
+    fn keeps_declared_script_types_only_when_allowed() {
+        let allowed = vec!["application/javascript".to_string()];
+        for declared in ["text/html", "application/xhtml+xml", "application/atom+xml"] {
+            assert_eq!(sniff(b"text", Some(declared), &[]).0, "text/plain");
+        }
+        assert_eq!(
+            sniff(b"alert(1)", Some("application/javascript"), &allowed).0,
+            "application/javascript"
+        );
+        assert_eq!(
+            sniff(b"{}", Some("application/json; charset=utf-8"), &[]).0,
+            "application/json"
+        );
+        assert_eq!(
+            sniff(b"# title", Some("text/markdown"), &[]).0,
+            "text/markdown"
+        );
+    }
+
+    #[tokio::test]
+    async fn cant_upload_too_large_file() {
+        let content = vec![1u8; 64 * 1024];
+        let (status, _, ctx) = send(
+            Uploader::new().prefix("large").max_size(1024),
+            &[("file", Some(("file.bin", OCTET_STREAM)), &content)],
+        )
+        .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let mut entries = ctx.storage.list(Path::new("large"), true).await.unwrap();
+        assert!(futures_util::StreamExt::next(&mut entries).await.is_none());
+    }
+
+    #[test]
+    fn can_match_allowed_types() {
+        let uploader = Uploader::new().allowed_types(["image/*", "application/pdf"]);
+        assert!(uploader.is_allowed("image/png"));
+        assert!(uploader.is_allowed("application/pdf"));
+        assert!(!uploader.is_allowed("application/zip"));
+        assert!(!uploader.is_allowed("imagex/png"));
+        assert!(Uploader::new().is_allowed("application/zip"));
+    }
+}
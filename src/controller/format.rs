@@ -20,11 +20,11 @@
 //!    format::json(Health { ok: true })
 //! }
 //! ```
-use std::convert::TryInto;
+use std::{convert::TryInto, path::Path};
 
 use axum::{
     body::Body,
-    http::{header, response::Builder, HeaderName, HeaderValue, StatusCode},
+    http::{header, response::Builder, HeaderMap, HeaderName, HeaderValue, StatusCode},
     response::{Html, IntoResponse, Redirect, Response},
 };
 use axum_extra::extract::cookie::Cookie;
@@ -37,6 +37,7 @@ use crate::{
         views::{self, ViewRenderer},
         Json,
     },
+    storage::{stream::BytesStream, Storage},
     Result,
 };
 
@@ -281,6 +282,18 @@ impl RenderBuilder {
         Ok(Self { response: res })
     }
 
+    /// Add a `Content-Disposition` header downloading the response as a file
+    /// with the given name, instead of displaying it.
+    #[must_use]
+    pub fn attachment(self, filename: &str) -> Self {
+        Self {
+            response: self.response.header(
+                header::CONTENT_DISPOSITION,
+                crate::storage::serve::attachment_disposition(filename),
+            ),
+        }
+    }
+
     /// Finalize and return a text response
     ///
     /// # Errors
@@ -367,6 +380,37 @@ impl RenderBuilder {
             .body(body)?)
     }
 
+    /// Finalize and return a response streaming the content, without
+    /// loading it in memory
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the content type is illegal
+    pub fn stream(self, stream: BytesStream, content_type: &str) -> Result<Response> {
+        Ok(self
+            .response
+            .header(header::CONTENT_TYPE, HeaderValue::from_str(content_type)?)
+            .body(stream.into_body())?)
+    }
+
+    /// Finalize and return a response streaming a stored file, answering
+    /// the `Range` and `If-None-Match` headers of the request. The content
+    /// type is guessed from the file unless set on this builder. See
+    /// [`crate::storage::serve`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return [`crate::Error::NotFound`] when the file
+    /// doesn't exist, or an error if the storage fails
+    pub async fn file(
+        self,
+        storage: &Storage,
+        path: &Path,
+        request: &HeaderMap,
+    ) -> Result<Response> {
+        crate::storage::serve::respond(self.response, storage, path, request).await
+    }
+
     /// Finalize and redirect request
     ///
     /// # Errors
@@ -652,4 +696,26 @@ mod tests {
         assert_debug_snapshot!(response);
         assert_eq!(response_body_to_string(response).await, String::new());
     }
+
+    #[tokio::test]
+    async fn builder_file_response() {
+        let storage = Storage::single(crate::storage::drivers::mem::new());
+        let path = std::path::Path::new("exports/report.csv");
+        storage
+            .upload(path, &bytes::Bytes::from("id,name\n1,loco\n"))
+            .await
+            .unwrap();
+
+        let response = render()
+            .attachment("report.csv")
+            .file(&storage, path, &HeaderMap::new())
+            .await
+            .unwrap();
+
+        assert_debug_snapshot!(response);
+        assert_eq!(
+            response_body_to_string(response).await,
+            "id,name\n1,loco\n"
+        );
+    }
 }
@@ -17,6 +17,7 @@ pub mod logger;
 pub mod powered_by;
 pub mod remote_ip;
 pub mod request_id;
+pub mod response_cache;
 pub mod secure_headers;
 #[cfg(feature = "embedded_assets")]
 pub mod static_assets_embedded;
@@ -92,6 +93,12 @@ pub fn default_middleware_stack(ctx: &AppContext) -> Vec<Box<dyn MiddlewareLayer
                 .clone()
                 .unwrap_or_else(|| catch_panic::CatchPanic { enable: true }),
         ),
+        // Response cache middleware with a default if none. Applied before the
+        // etag middleware so cached responses can be answered with a 304
+        Box::new(response_cache::new(
+            &middlewares.response_cache.clone().unwrap_or_default(),
+            ctx.cache.clone(),
+        )),
         // Etag middleware with a default if none
         Box::new(
             middlewares
@@ -209,4 +216,7 @@ pub struct Config {
 
     /// Request ID
     pub request_id: Option<request_id::RequestId>,
+
+    /// Caching full responses in the application cache
+    pub response_cache: Option<response_cache::Config>,
 }
@@ -0,0 +1,587 @@
+//! Response Cache Middleware
+//!
+//! This middleware stores full `GET` responses (status, headers and body) in
+//! the application [`Cache`] and serves them from there until they expire,
+//! so public pages can be cached without hand-writing cache logic in each
+//! controller.
+//!
+//! Responses are keyed by method, path, query and the configured `vary`
+//! request headers. Requests with an `Authorization` header, or a `Cookie`
+//! header unless their route opts in with `cookies`, bypass the cache.
+//! Responses varying on a request header other than the configured ones are
+//! not cached. How long a response is kept is decided by, in order:
+//!
+//! * The `Cache-Control` header set by the handler: `no-store`, `no-cache`
+//!   and `private` disable caching, `s-maxage`/`max-age` set the time to live.
+//! * The first matching entry of `routes`.
+//! * The default `ttl`.
+//!
+//! Responses without a time to live are not cached. Stored responses are
+//! given an `ETag` when the handler did not set one, so the [`super::etag`]
+//! middleware can answer repeated requests with `304 Not Modified`.
+//!
+//! Entries are stored under a cache namespace and a generation, and are
+//! purged with [`clear`], which starts a new generation and leaves the
+//! entries of the previous one to expire.
+
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::Duration,
+};
+
+use axum::{
+    body::{to_bytes, Body, HttpBody},
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, CACHE_CONTROL, COOKIE, ETAG, SET_COOKIE, VARY},
+        HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Router as AXRouter,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::json;
+
+use crate::{
+    app::AppContext,
+    cache::{Cache, CacheResult},
+    controller::middleware::MiddlewareLayer,
+    Result,
+};
+
+/// Header telling whether a response was served from the cache.
+const X_CACHE: &str = "x-cache";
+
+/// Response cache middleware configuration
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Config {
+    #[serde(default)]
+    pub enable: bool,
+    /// Time to live in milliseconds of responses not matching any of
+    /// `routes`. When unset, only those routes and responses with a
+    /// `Cache-Control` max age are cached.
+    #[serde(default)]
+    pub ttl: Option<u64>,
+    /// Per-route time to live. The first matching route wins.
+    #[serde(default)]
+    pub routes: Vec<Route>,
+    /// Request headers that are part of the cache key, such as
+    /// `accept-language`.
+    #[serde(default)]
+    pub vary: Vec<String>,
+    /// Responses with a larger (or unknown) body size are not cached.
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Cache namespace of the stored responses.
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+}
+
+/// Time to live of the responses of a route.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Route {
+    /// Path pattern, where `{param}` or `*` match a single segment and a
+    /// trailing `*` matches the rest of the path. For example `/posts/{id}`
+    /// or `/docs/*`.
+    pub path: String,
+    /// Time to live in milliseconds, `0` disables caching of the route.
+    pub ttl: u64,
+    /// Serves and stores responses to requests with a `Cookie` header, for
+    /// routes whose responses don't depend on the session.
+    #[serde(default)]
+    pub cookies: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        serde_json::from_value(json!({})).unwrap()
+    }
+}
+
+fn default_max_body_size() -> usize {
+    1024 * 1024
+}
+
+fn default_namespace() -> String {
+    "response".to_string()
+}
+
+impl Config {
+    /// Returns the first route matching a path.
+    fn route_for(&self, path: &str) -> Option<&Route> {
+        self.routes
+            .iter()
+            .find(|route| path_matches(&route.path, path))
+    }
+
+    /// Returns the configured time to live of a path.
+    fn ttl_for(&self, path: &str) -> Option<Duration> {
+        self.route_for(path)
+            .map(|route| route.ttl)
+            .or(self.ttl)
+            .map(Duration::from_millis)
+    }
+}
+
+/// Purges every response cached in a namespace, by starting a new
+/// generation of its entries. Entries of the previous generation are left to
+/// expire.
+///
+/// # Errors
+///
+/// When the generation could not be incremented
+pub async fn clear(cache: &Cache, namespace: &str) -> CacheResult<()> {
+    cache
+        .increment(&generation_key(namespace), 1, None)
+        .await
+        .map(|_| ())
+}
+
+/// Key of the counter holding the current generation of a namespace.
+fn generation_key(namespace: &str) -> String {
+    format!("{namespace}:generation")
+}
+
+/// [`Middleware`] struct responsible for caching responses.
+#[derive(Serialize)]
+pub struct Middleware {
+    config: Config,
+    #[serde(skip)]
+    cache: Arc<Cache>,
+}
+
+/// Creates a new instance of [`Middleware`] storing responses in the given
+/// cache.
+#[must_use]
+pub fn new(config: &Config, cache: Arc<Cache>) -> Middleware {
+    Middleware {
+        config: config.clone(),
+        cache,
+    }
+}
+
+impl MiddlewareLayer for Middleware {
+    /// Returns the name of the middleware
+    fn name(&self) -> &'static str {
+        "response_cache"
+    }
+
+    /// Returns whether the middleware is enabled or not
+    fn is_enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    fn config(&self) -> serde_json::Result<serde_json::Value> {
+        serde_json::to_value(self)
+    }
+
+    /// Applies the response cache middleware to the application router.
+    fn apply(&self, app: AXRouter<AppContext>) -> Result<AXRouter<AppContext>> {
+        let state = Arc::new(Self {
+            config: self.config.clone(),
+            cache: self.cache.clone(),
+        });
+        Ok(app.layer(axum::middleware::from_fn_with_state(
+            state,
+            response_cache_middleware,
+        )))
+    }
+}
+
+/// A response as stored in the cache.
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// Base64 encoded, so the body stays compact with the JSON encoding.
+    #[serde(serialize_with = "to_base64", deserialize_with = "from_base64")]
+    body: Vec<u8>,
+}
+
+fn to_base64<S: Serializer>(bytes: &[u8], serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+fn from_base64<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+impl IntoResponse for CachedResponse {
+    fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK);
+        let headers = response.headers_mut();
+        for (name, value) in self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(name.as_bytes()),
+                HeaderValue::from_str(&value),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        headers.insert(X_CACHE, HeaderValue::from_static("HIT"));
+        response
+    }
+}
+
+async fn response_cache_middleware(
+    State(middleware): State<Arc<Middleware>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let config = &middleware.config;
+    let path = request.uri().path();
+    let cookies_allowed = config.route_for(path).is_some_and(|route| route.cookies);
+    if request.method() != Method::GET
+        || request.headers().contains_key(AUTHORIZATION)
+        || (request.headers().contains_key(COOKIE) && !cookies_allowed)
+    {
+        return next.run(request).await;
+    }
+
+    let cache = &middleware.cache;
+    let route_ttl = config.ttl_for(path);
+    let generation = match cache
+        .increment(&generation_key(&config.namespace), 0, None)
+        .await
+    {
+        Ok(generation) => generation,
+        Err(err) => {
+            tracing::warn!(error = %err, "could not read response cache generation");
+            return next.run(request).await;
+        }
+    };
+    let key = format!(
+        "{}:{generation}:{}",
+        config.namespace,
+        cache_key(&request, &config.vary)
+    );
+
+    match cache.get::<CachedResponse>(&key).await {
+        Ok(Some(cached)) => return cached.into_response(),
+        Ok(None) => {}
+        Err(err) => tracing::warn!(key, error = %err, "could not read cached response"),
+    }
+
+    let response = next.run(request).await;
+    let Some(ttl) = response_ttl(&response, route_ttl, &config.vary) else {
+        return response;
+    };
+    let (mut parts, body) = response.into_parts();
+    let fits = body
+        .size_hint()
+        .exact()
+        .is_some_and(|size| usize::try_from(size).is_ok_and(|size| size <= config.max_body_size));
+    if !fits {
+        return Response::from_parts(parts, body);
+    }
+    let bytes = match to_bytes(body, config.max_body_size).await {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            tracing::error!(key, error = %err, "could not read response body");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    if !parts.headers.contains_key(ETAG) {
+        if let Ok(etag) = HeaderValue::from_str(&etag_for(&bytes)) {
+            parts.headers.insert(ETAG, etag);
+        }
+    }
+    let headers = parts
+        .headers
+        .iter()
+        .map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect::<Option<Vec<_>>>();
+    // responses with opaque header values are not cached
+    if let Some(headers) = headers {
+        let cached = CachedResponse {
+            status: parts.status.as_u16(),
+            headers,
+            body: bytes.to_vec(),
+        };
+        if let Err(err) = cache.insert_with_expiry(&key, &cached, ttl).await {
+            tracing::warn!(key, error = %err, "could not cache response");
+        }
+    }
+
+    parts
+        .headers
+        .insert(X_CACHE, HeaderValue::from_static("MISS"));
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+/// Builds the cache key of a request out of its method, path, query and the
+/// values of the `vary` headers.
+fn cache_key(request: &Request, vary: &[String]) -> String {
+    let mut key = format!("{} {}", request.method(), request.uri());
+    for name in vary {
+        let value = request
+            .headers()
+            .get(name.as_str())
+            .map(|value| String::from_utf8_lossy(value.as_bytes()))
+            .unwrap_or_default();
+        key.push_str(&format!("|{}={value}", name.to_lowercase()));
+    }
+    key
+}
+
+/// Returns how long a response can be cached for, if at all.
+fn response_ttl(
+    response: &Response,
+    route_ttl: Option<Duration>,
+    vary: &[String],
+) -> Option<Duration> {
+    let headers = response.headers();
+    if response.status() != StatusCode::OK
+        || headers.contains_key(SET_COOKIE)
+        || !varies_on_key_headers(headers, vary)
+    {
+        return None;
+    }
+    cache_control_ttl(headers)?
+        .or(route_ttl)
+        .filter(|ttl| !ttl.is_zero())
+}
+
+/// Checks that every request header named by the `Vary` header of a response
+/// is part of the cache key. `Vary: *` never is.
+fn varies_on_key_headers(headers: &HeaderMap, vary: &[String]) -> bool {
+    headers.get_all(VARY).iter().all(|value| {
+        value.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .all(|name| vary.iter().any(|key| key.eq_ignore_ascii_case(name)))
+        })
+    })
+}
+
+/// Reads the `Cache-Control` header of a response. Returns `None` when the
+/// response must not be cached, and the max age it sets otherwise.
+fn cache_control_ttl(headers: &HeaderMap) -> Option<Option<Duration>> {
+    let mut max_age = None;
+    let mut s_maxage = None;
+    for value in headers.get_all(CACHE_CONTROL) {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        for directive in value.split(',').map(|d| d.trim().to_lowercase()) {
+            match directive.split_once('=') {
+                None if matches!(directive.as_str(), "no-store" | "no-cache" | "private") => {
+                    return None;
+                }
+                Some(("max-age", secs)) => max_age = secs.trim_matches('"').parse().ok(),
+                Some(("s-maxage", secs)) => s_maxage = secs.trim_matches('"').parse().ok(),
+                _ => {}
+            }
+        }
+    }
+    Some(s_maxage.or(max_age).map(Duration::from_secs))
+}
+
+/// Matches a request path against a route pattern.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut patterns = pattern.trim_end_matches('/').split('/').peekable();
+    let mut segments = path.trim_end_matches('/').split('/');
+    loop {
+        match (patterns.next(), segments.next()) {
+            (None, None) => return true,
+            (Some("*"), _) if patterns.peek().is_none() => return true,
+            (Some(pattern), Some(segment))
+                if pattern == segment
+                    || pattern == "*"
+                    || (pattern.starts_with('{') && pattern.ends_with('}')) => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Generates a weak `ETag` out of a response body.
+fn etag_for(body: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("W/\"{:016x}\"", hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{http::header::ACCEPT_ENCODING, routing::get};
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn call(router: &AXRouter, uri: &str, headers: &[(HeaderName, &str)]) -> Response {
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        router
+            .clone()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    fn x_cache(response: &Response) -> &str {
+        response
+            .headers()
+            .get(X_CACHE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    }
+
+    async fn router(config: serde_json::Value) -> (AXRouter, Arc<Cache>, Arc<AtomicUsize>) {
+        let ctx = crate::tests_cfg::app::get_app_context().await;
+        let cache = ctx.cache.clone();
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let counter_vary = calls.clone();
+        let app = AXRouter::new()
+            .route(
+                "/posts",
+                get(move || async move {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    "posts"
+                }),
+            )
+            .route(
+                "/gzip",
+                get(move || async move {
+                    counter_vary.fetch_add(1, Ordering::SeqCst);
+                    ([(VARY, "Accept-Encoding")], "gzip")
+                }),
+            );
+        let mut config: Config = serde_json::from_value(config).unwrap();
+        config.enable = true;
+        let app = new(&config, cache.clone())
+            .apply(app)
+            .unwrap()
+            .with_state(ctx);
+        (app, cache, calls)
+    }
+
+    #[tokio::test]
+    async fn bypasses_requests_with_cookies() {
+        let (app, _, calls) = router(json!({ "ttl": 60000 })).await;
+
+        for _ in 0..2 {
+            let response = call(&app, "/posts", &[(COOKIE, "session=1")]).await;
+            assert_eq!(x_cache(&response), "");
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        assert_eq!(x_cache(&call(&app, "/posts", &[]).await), "MISS");
+        assert_eq!(x_cache(&call(&app, "/posts", &[]).await), "HIT");
+
+        let (app, _, _) = router(json!({
+            "routes": [{ "path": "/posts", "ttl": 60000, "cookies": true }],
+        }))
+        .await;
+        call(&app, "/posts", &[(COOKIE, "session=1")]).await;
+        let response = call(&app, "/posts", &[(COOKIE, "session=2")]).await;
+        assert_eq!(x_cache(&response), "HIT");
+    }
+
+    #[tokio::test]
+    async fn skips_responses_varying_on_other_headers() {
+        let (app, _, calls) = router(json!({ "ttl": 60000 })).await;
+        call(&app, "/gzip", &[(ACCEPT_ENCODING, "gzip")]).await;
+        let response = call(&app, "/gzip", &[(ACCEPT_ENCODING, "br")]).await;
+        assert_eq!(x_cache(&response), "");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let (app, _, calls) = router(json!({ "ttl": 60000, "vary": ["accept-encoding"] })).await;
+        call(&app, "/gzip", &[(ACCEPT_ENCODING, "gzip")]).await;
+        let response = call(&app, "/gzip", &[(ACCEPT_ENCODING, "gzip")]).await;
+        assert_eq!(x_cache(&response), "HIT");
+        let response = call(&app, "/gzip", &[(ACCEPT_ENCODING, "br")]).await;
+        assert_eq!(x_cache(&response), "MISS");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn can_clear_cached_responses() {
+        let (app, cache, calls) = router(json!({ "ttl": 60000 })).await;
+        call(&app, "/posts", &[]).await;
+        assert_eq!(x_cache(&call(&app, "/posts", &[]).await), "HIT");
+
+        clear(&cache, "response").await.unwrap();
+        assert_eq!(x_cache(&call(&app, "/posts", &[]).await), "MISS");
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn stores_body_as_base64() {
+        let cached = CachedResponse {
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            body: b"hello".to_vec(),
+        };
+        let value = serde_json::to_value(&cached).unwrap();
+        assert_eq!(value["body"], json!("aGVsbG8="));
+        let decoded: CachedResponse = serde_json::from_value(value).unwrap();
+        assert_eq!(decoded.body, b"hello");
+    }
+
+    #[rstest]
+    #[case("/posts/{id}", "/posts/1", true)]
+    #[case("/posts/{id}", "/posts/1/", true)]
+    #[case("/posts/{id}", "/posts", false)]
+    #[case("/posts/{id}", "/posts/1/comments", false)]
+    #[case("/posts/*/comments", "/posts/1/comments", true)]
+    #[case("/docs/*", "/docs/guide/intro", true)]
+    #[case("/docs/*", "/blog/intro", false)]
+    #[case("/", "/", true)]
+    #[case("/", "/posts", false)]
+    fn can_match_paths(#[case] pattern: &str, #[case] path: &str, #[case] expected: bool) {
+        assert_eq!(path_matches(pattern, path), expected);
+    }
+
+    #[rstest]
+    #[case(None, Some(None))]
+    #[case(Some("no-store"), None)]
+    #[case(Some("private, max-age=60"), None)]
+    #[case(Some("public, max-age=60"), Some(Some(Duration::from_secs(60))))]
+    #[case(Some("max-age=60, s-maxage=600"), Some(Some(Duration::from_secs(600))))]
+    fn can_read_cache_control(
+        #[case] cache_control: Option<&str>,
+        #[case] expected: Option<Option<Duration>>,
+    ) {
+        let mut headers = HeaderMap::new();
+        if let Some(cache_control) = cache_control {
+            headers.insert(CACHE_CONTROL, HeaderValue::from_str(cache_control).unwrap());
+        }
+        assert_eq!(cache_control_ttl(&headers), expected);
+    }
+
+    #[test]
+    fn can_resolve_route_ttl() {
+        let config: Config = serde_json::from_value(json!({
+            "ttl": 1000,
+            "routes": [
+                { "path": "/posts/{id}", "ttl": 60000 },
+                { "path": "/admin/*", "ttl": 0 },
+            ],
+        }))
+        .unwrap();
+
+        assert_eq!(
+            config.ttl_for("/posts/1"),
+            Some(Duration::from_millis(60_000))
+        );
+        assert_eq!(config.ttl_for("/admin/users"), Some(Duration::ZERO));
+        assert_eq!(config.ttl_for("/"), Some(Duration::from_millis(1000)));
+    }
+}
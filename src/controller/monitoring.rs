@@ -3,7 +3,7 @@
 //! application and its dependencies.
 
 use super::{format, routes::Routes};
-#[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
+#[cfg(any(feature = "cache_inmem", feature = "cache_redis", feature = "with-db"))]
 use crate::config;
 use crate::{app::AppContext, Result};
 use axum::{
@@ -64,7 +64,7 @@ pub async fn readiness(State(ctx): State<AppContext>) -> (StatusCode, Response)
     }
 
     // Check cache connection
-    #[cfg(any(feature = "cache_inmem", feature = "cache_redis"))]
+    #[cfg(any(feature = "cache_inmem", feature = "cache_redis", feature = "with-db"))]
     {
         match ctx.config.cache {
             #[cfg(feature = "cache_inmem")]
@@ -87,6 +87,26 @@ pub async fn readiness(State(ctx): State<AppContext>) -> (StatusCode, Response)
                     );
                 }
             }
+            #[cfg(all(feature = "cache_inmem", feature = "cache_redis"))]
+            config::CacheConfig::Tiered(_) => {
+                if let Err(error) = &ctx.cache.driver.ping().await {
+                    tracing::error!(err.msg = %error, err.detail = ?error, "readiness_cache_ping_error");
+                    return (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        format::json(Health { ok: false }).into_response(),
+                    );
+                }
+            }
+            #[cfg(feature = "with-db")]
+            config::CacheConfig::Database(_) => {
+                if let Err(error) = &ctx.cache.driver.ping().await {
+                    tracing::error!(err.msg = %error, err.detail = ?error, "readiness_cache_ping_error");
+                    return (
+                        StatusCode::SERVICE_UNAVAILABLE,
+                        format::json(Health { ok: false }).into_response(),
+                    );
+                }
+            }
             config::CacheConfig::Null => (),
         }
     }
@@ -304,6 +324,8 @@ mod tests {
         let redis_cache = cache::drivers::redis::new(&config::RedisCacheConfig {
             uri: redis_url,
             max_size: 10,
+            lock: None,
+            encoding: config::CacheEncodingConfig::default(),
         })
         .await
         .expect("Failed to create Redis cache");
@@ -342,11 +364,15 @@ mod tests {
         ctx.config.cache = config::CacheConfig::Redis(loco_rs::config::RedisCacheConfig {
             uri: failour_redis_url.to_string(),
             max_size: 10,
+            lock: None,
+            encoding: config::CacheEncodingConfig::default(),
         });
         // Create Redis cache driver and assign to ctx.cache
         ctx.cache = cache::drivers::redis::new(&config::RedisCacheConfig {
             uri: failour_redis_url.to_string(),
             max_size: 10,
+            lock: None,
+            encoding: config::CacheEncodingConfig::default(),
         })
         .await
         .expect("Failed to create Redis cache")
@@ -0,0 +1,222 @@
+//! This module defines the [`Attachment`] of an [`Email`](super::Email),
+//! either attached to the message or embedded in its HTML body as an inline
+//! image referenced by its content id.
+
+use std::path::PathBuf;
+
+use base64::Engine;
+use lettre::message::{header::ContentType, SinglePart};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::Result;
+use crate::{errors::Error, storage::Storage};
+
+/// The content of an attachment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentContent {
+    /// Raw bytes, base64 encoded when the email is serialized.
+    Bytes(#[serde(with = "base64_bytes")] Vec<u8>),
+    /// Path of a file in `ctx.storage`, read by the [`MailerWorker`](super::MailerWorker)
+    /// when it sends the email, which keeps the jobs small.
+    Storage(PathBuf),
+}
+
+/// A file attached to an email.
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::mailer::Attachment;
+///
+/// let invoice = Attachment::storage("invoice-42.pdf", "invoices/42.pdf");
+/// // referenced as `<img src="cid:logo">` in the HTML body
+/// let logo = Attachment::bytes("logo.png", vec![]).inline("logo");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// File name shown to the recipient.
+    pub filename: String,
+    /// MIME type of the content, guessed from the file name by default.
+    pub content_type: String,
+    pub content: AttachmentContent,
+    /// Content id of an inline image, referenced as `cid:{content_id}` in the
+    /// HTML body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_id: Option<String>,
+}
+
+impl Attachment {
+    /// Creates an attachment of the given bytes.
+    #[must_use]
+    pub fn bytes(filename: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        Self::new(filename.into(), AttachmentContent::Bytes(content.into()))
+    }
+
+    /// Creates an attachment of a file of `ctx.storage`.
+    #[must_use]
+    pub fn storage(filename: impl Into<String>, path: impl Into<PathBuf>) -> Self {
+        Self::new(filename.into(), AttachmentContent::Storage(path.into()))
+    }
+
+    fn new(filename: String, content: AttachmentContent) -> Self {
+        let content_type = mime_guess::from_path(&filename)
+            .first_or_octet_stream()
+            .to_string();
+        Self {
+            filename,
+            content_type,
+            content,
+            content_id: None,
+        }
+    }
+
+    /// Sets the MIME type of the content.
+    #[must_use]
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    /// Embeds the attachment in the HTML body, where it's referenced as
+    /// `cid:{content_id}`, such as `<img src="cid:logo">`.
+    #[must_use]
+    pub fn inline(mut self, content_id: impl Into<String>) -> Self {
+        self.content_id = Some(content_id.into());
+        self
+    }
+
+    /// Whether the attachment is embedded in the HTML body.
+    #[must_use]
+    pub fn is_inline(&self) -> bool {
+        self.content_id.is_some()
+    }
+
+    /// Reads the content of the attachment from the storage when it's a
+    /// storage path.
+    ///
+    /// # Errors
+    ///
+    /// When the file could not be read from the storage
+    pub async fn load(&mut self, storage: &Storage) -> Result<()> {
+        if let AttachmentContent::Storage(path) = &self.content {
+            let content: Vec<u8> = storage.download(path).await?;
+            self.content = AttachmentContent::Bytes(content);
+        }
+        Ok(())
+    }
+
+    /// Builds the MIME part of the attachment.
+    pub(crate) fn to_part(&self) -> Result<SinglePart> {
+        let AttachmentContent::Bytes(content) = &self.content else {
+            return Err(Error::Message(format!(
+                "attachment `{}` must be loaded from the storage before sending",
+                self.filename
+            )));
+        };
+        let content_type = ContentType::parse(&self.content_type).map_err(|err| {
+            Error::Message(format!(
+                "invalid content type of attachment `{}`: {err}",
+                self.filename
+            ))
+        })?;
+        let attachment = match &self.content_id {
+            Some(content_id) => lettre::message::Attachment::new_inline(content_id.clone()),
+            None => lettre::message::Attachment::new(self.filename.clone()),
+        };
+        Ok(attachment.body(content.clone(), content_type))
+    }
+}
+
+mod base64_bytes {
+    use super::{Deserialize, Deserializer, Engine, Serializer};
+
+    pub fn serialize<S: Serializer>(content: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&base64::engine::general_purpose::STANDARD.encode(content))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let content = String::deserialize(deserializer)?;
+        base64::engine::general_purpose::STANDARD
+            .decode(content)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::storage::drivers::mem;
+
+    #[test]
+    fn can_guess_content_type() {
+        assert_eq!(
+            Attachment::bytes("invoice.pdf", vec![]).content_type,
+            "application/pdf"
+        );
+        assert_eq!(
+            Attachment::bytes("data", vec![]).content_type,
+            "application/octet-stream"
+        );
+        assert_eq!(
+            Attachment::bytes("data", vec![])
+                .content_type("text/csv")
+                .content_type,
+            "text/csv"
+        );
+    }
+
+    #[test]
+    fn can_serialize_attachments() {
+        let attachments = vec![
+            Attachment::bytes("logo.png", b"PNG".to_vec()).inline("logo"),
+            Attachment::storage("invoice.pdf", "invoices/42.pdf"),
+        ];
+
+        let json = serde_json::to_value(&attachments).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {
+                    "filename": "logo.png",
+                    "content_type": "image/png",
+                    "content": {"bytes": "UE5H"},
+                    "content_id": "logo",
+                },
+                {
+                    "filename": "invoice.pdf",
+                    "content_type": "application/pdf",
+                    "content": {"storage": "invoices/42.pdf"},
+                },
+            ])
+        );
+        assert_eq!(
+            serde_json::from_value::<Vec<Attachment>>(json).unwrap(),
+            attachments
+        );
+    }
+
+    #[tokio::test]
+    async fn can_load_from_storage() {
+        let storage = Storage::single(mem::new());
+        storage
+            .upload(
+                Path::new("invoices/42.pdf"),
+                &bytes::Bytes::from("%PDF-1.7"),
+            )
+            .await
+            .unwrap();
+
+        let mut attachment = Attachment::storage("invoice.pdf", "invoices/42.pdf");
+        attachment.load(&storage).await.unwrap();
+        assert_eq!(
+            attachment.content,
+            AttachmentContent::Bytes(b"%PDF-1.7".to_vec())
+        );
+
+        let mut missing = Attachment::storage("invoice.pdf", "invoices/43.pdf");
+        assert!(missing.load(&storage).await.is_err());
+    }
+}
@@ -1,15 +1,20 @@
 //! This module defines an [`EmailSender`] responsible for sending emails using
-//! either the SMTP protocol. It includes an asynchronous method `mail` for
-//! sending emails with options like sender, recipient, subject, and content.
+//! either the SMTP protocol, `.eml` files, a sendmail binary or an HTTP API. It
+//! includes an asynchronous method `mail` for sending emails with options like
+//! sender, recipient, subject, and content.
+
+use std::{sync::Arc, time::Duration};
 
 use lettre::{
-    message::{header, MultiPart},
+    message::{header, MultiPart, SinglePart},
     transport::smtp::{authentication::Credentials, extension::ClientId},
-    AsyncTransport, Message, Tokio1Executor, Transport,
+    AsyncFileTransport, AsyncSendmailTransport, AsyncTransport, Message, Tokio1Executor, Transport,
 };
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::Serialize;
 use tracing::error;
 
-use super::{Email, Result, DEFAULT_FROM_SENDER};
+use super::{Attachment, Email, EmailHeaders, Result, DEFAULT_FROM_SENDER};
 use crate::{config, errors::Error};
 
 /// An enumeration representing the possible transport methods for sending
@@ -18,10 +23,102 @@ use crate::{config, errors::Error};
 pub enum EmailTransport {
     /// SMTP (Simple Mail Transfer Protocol) transport.
     Smtp(lettre::AsyncSmtpTransport<lettre::Tokio1Executor>),
+    /// Writes every email to an `.eml` file.
+    File(AsyncFileTransport<Tokio1Executor>),
+    /// Pipes every email to a sendmail compatible binary.
+    Sendmail(Arc<AsyncSendmailTransport<Tokio1Executor>>),
+    /// Posts every email as JSON to an HTTP API.
+    Http(HttpTransport),
     /// Test/stub transport for testing purposes.
     Test(lettre::transport::stub::StubTransport),
 }
 
+/// Transport posting emails as JSON to an HTTP API.
+#[derive(Clone, Debug)]
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+}
+
+/// The JSON body posted by the [`HttpTransport`]: the fields of the
+/// [`Email`] with its sender defaulted, along with the whole message in the
+/// `raw` field for APIs accepting MIME messages.
+#[derive(Serialize)]
+struct HttpEmail<'a> {
+    from: &'a str,
+    to: &'a str,
+    reply_to: Option<&'a str>,
+    subject: &'a str,
+    text: &'a str,
+    html: &'a str,
+    bcc: Option<&'a str>,
+    cc: Option<&'a str>,
+    headers: Option<&'a EmailHeaders>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    attachments: &'a [Attachment],
+    raw: String,
+}
+
+impl HttpTransport {
+    /// Creates a transport from the HTTP mailer configuration.
+    ///
+    /// # Errors
+    ///
+    /// When a header is invalid or the HTTP client could not be built
+    pub fn new(config: &config::HttpMailer) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|err| Error::Message(format!("invalid mailer header `{name}`: {err}")))?;
+            let mut value = HeaderValue::from_str(value)
+                .map_err(|err| Error::Message(format!("invalid mailer header `{name}`: {err}")))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+
+        let mut client = reqwest::Client::builder().default_headers(headers);
+        if let Some(timeout) = config.timeout {
+            client = client.timeout(Duration::from_millis(timeout));
+        }
+        Ok(Self {
+            client: client.build().map_err(Error::wrap)?,
+            url: config.url.clone(),
+        })
+    }
+
+    async fn send(&self, email: &Email, from: &str, message: &Message) -> Result<()> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&HttpEmail {
+                from,
+                to: &email.to,
+                reply_to: email.reply_to.as_deref(),
+                subject: &email.subject,
+                text: &email.text,
+                html: &email.html,
+                bcc: email.bcc.as_deref(),
+                cc: email.cc.as_deref(),
+                headers: email.headers.as_ref(),
+                attachments: &email.attachments,
+                raw: String::from_utf8_lossy(&message.formatted()).into_owned(),
+            })
+            .send()
+            .await
+            .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::Message(format!(
+                "sending email error: {} responded with {status}: {body}",
+                self.url
+            )));
+        }
+        Ok(())
+    }
+}
+
 /// A structure representing the email sender, encapsulating the chosen
 /// transport method.
 #[derive(Clone, Debug)]
@@ -70,6 +167,43 @@ impl EmailSender {
         })
     }
 
+    /// Creates a new `EmailSender` writing emails to `.eml` files in the
+    /// configured directory, which is created when missing.
+    ///
+    /// # Errors
+    ///
+    /// when could not create the directory
+    pub fn file(config: &config::FileMailer) -> Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
+        Ok(Self {
+            transport: EmailTransport::File(AsyncFileTransport::new(&config.dir)),
+        })
+    }
+
+    /// Creates a new `EmailSender` piping emails to the configured sendmail
+    /// binary.
+    #[must_use]
+    pub fn sendmail(config: &config::SendmailMailer) -> Self {
+        let transport = config.command.as_ref().map_or_else(
+            AsyncSendmailTransport::new,
+            AsyncSendmailTransport::new_with_command,
+        );
+        Self {
+            transport: EmailTransport::Sendmail(Arc::new(transport)),
+        }
+    }
+
+    /// Creates a new `EmailSender` posting emails to the configured HTTP API.
+    ///
+    /// # Errors
+    ///
+    /// When a header is invalid or the HTTP client could not be built
+    pub fn http(config: &config::HttpMailer) -> Result<Self> {
+        Ok(Self {
+            transport: EmailTransport::Http(HttpTransport::new(config)?),
+        })
+    }
+
     #[must_use]
     pub fn stub() -> Self {
         Self {
@@ -94,6 +228,35 @@ impl EmailSender {
         Deliveries::default()
     }
 
+    /// Builds the body of the email: the text and HTML alternatives, with the
+    /// HTML related to the inline images, mixed with the attachments.
+    fn content(email: &Email) -> Result<MultiPart> {
+        let (inline, attached): (Vec<_>, Vec<_>) = email
+            .attachments
+            .iter()
+            .partition(|attachment| attachment.is_inline());
+
+        let mut content = if inline.is_empty() {
+            MultiPart::alternative_plain_html(email.text.clone(), email.html.clone())
+        } else {
+            let mut related = MultiPart::related().singlepart(SinglePart::html(email.html.clone()));
+            for attachment in inline {
+                related = related.singlepart(attachment.to_part()?);
+            }
+            MultiPart::alternative()
+                .singlepart(SinglePart::plain(email.text.clone()))
+                .multipart(related)
+        };
+
+        if !attached.is_empty() {
+            content = MultiPart::mixed().multipart(content);
+            for attachment in attached {
+                content = content.singlepart(attachment.to_part()?);
+            }
+        }
+        Ok(content)
+    }
+
     /// Sends an email using the configured transport method.
     ///
     /// # Errors
@@ -101,16 +264,9 @@ impl EmailSender {
     /// When email doesn't send successfully or has an error to build the
     /// message
     pub async fn mail(&self, email: &Email) -> Result<()> {
-        let content = MultiPart::alternative_plain_html(email.text.clone(), email.html.clone());
-        let mut builder = Message::builder()
-            .from(
-                email
-                    .from
-                    .clone()
-                    .unwrap_or_else(|| DEFAULT_FROM_SENDER.to_string())
-                    .parse()?,
-            )
-            .to(email.to.parse()?);
+        let from = email.from.as_deref().unwrap_or(DEFAULT_FROM_SENDER);
+        let content = Self::content(email)?;
+        let mut builder = Message::builder().from(from.parse()?).to(email.to.parse()?);
 
         if let Some(bcc) = &email.bcc {
             builder = builder.bcc(bcc.parse()?);
@@ -148,6 +304,19 @@ impl EmailSender {
             EmailTransport::Smtp(xp) => {
                 xp.send(msg).await?;
             }
+            EmailTransport::File(xp) => {
+                xp.send(msg)
+                    .await
+                    .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
+            }
+            EmailTransport::Sendmail(xp) => {
+                xp.send(msg)
+                    .await
+                    .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
+            }
+            EmailTransport::Http(xp) => {
+                xp.send(email, from, &msg).await?;
+            }
             EmailTransport::Test(xp) => {
                 xp.send(&msg)
                     .map_err(|e| Error::Message(format!("sending email error: {e}")))?;
@@ -164,6 +333,7 @@ mod tests {
     use lettre::transport::stub::StubTransport;
 
     use super::*;
+    use crate::mailer::Attachment;
 
     #[tokio::test]
     async fn can_send_email() {
@@ -190,6 +360,7 @@ mod tests {
             bcc: None,
             cc: None,
             headers: None,
+            attachments: vec![],
         };
         assert!(sender.mail(&data).await.is_ok());
 
@@ -232,6 +403,7 @@ mod tests {
             bcc: None,
             cc: None,
             headers: Some(headers),
+            attachments: vec![],
         };
         assert!(sender.mail(&data).await.is_ok());
 
@@ -242,4 +414,170 @@ mod tests {
             assert_debug_snapshot!(stub.messages());
         });
     }
+
+    fn email() -> Email {
+        Email {
+            from: None,
+            to: "user1@framework.com".to_string(),
+            subject: "Email Subject".to_string(),
+            text: "Welcome".to_string(),
+            html: "<p>Welcome</p>".to_string(),
+            ..Email::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn can_write_email_files() {
+        let tree_fs = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let dir = tree_fs.root.join("mails");
+
+        let sender = EmailSender::file(&config::FileMailer {
+            enable: true,
+            dir: dir.clone(),
+        })
+        .unwrap();
+        sender.mail(&email()).await.unwrap();
+
+        let files = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains("Subject: Email Subject"));
+        assert!(content.contains(&format!("From: {DEFAULT_FROM_SENDER}")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn can_send_email_with_sendmail() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .add(
+                "sendmail",
+                "#!/bin/sh\necho \"$@\" > \"$(dirname \"$0\")/args\"\ncat > \"$(dirname \"$0\")/mail.eml\"\n",
+            )
+            .create()
+            .unwrap();
+        let command = tree_fs.root.join("sendmail");
+        std::fs::set_permissions(&command, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let sender = EmailSender::sendmail(&config::SendmailMailer {
+            enable: true,
+            command: Some(command.display().to_string()),
+        });
+        sender.mail(&email()).await.unwrap();
+
+        let args = std::fs::read_to_string(tree_fs.root.join("args")).unwrap();
+        assert_eq!(
+            args.trim(),
+            "-i -f system@example.com -- user1@framework.com"
+        );
+        let content = std::fs::read_to_string(tree_fs.root.join("mail.eml")).unwrap();
+        assert!(content.contains("Subject: Email Subject"));
+    }
+
+    type Requests = Arc<std::sync::Mutex<Vec<(axum::http::HeaderMap, serde_json::Value)>>>;
+
+    /// Starts a local server standing for an email API, answering with the
+    /// given status.
+    async fn serve_api(status: axum::http::StatusCode) -> (String, Requests) {
+        let requests = Requests::default();
+        let received = requests.clone();
+        let app = axum::Router::new().route(
+            "/emails",
+            axum::routing::post(
+                move |headers: axum::http::HeaderMap,
+                      axum::Json(body): axum::Json<serde_json::Value>| async move {
+                    received.lock().unwrap().push((headers, body));
+                    status
+                },
+            ),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/emails", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (url, requests)
+    }
+
+    fn http_config(url: String) -> config::HttpMailer {
+        config::HttpMailer {
+            enable: true,
+            url,
+            headers: [("Authorization".to_string(), "Bearer secret".to_string())].into(),
+            timeout: Some(5000),
+        }
+    }
+
+    #[tokio::test]
+    async fn can_send_email_with_http_api() {
+        let (url, requests) = serve_api(axum::http::StatusCode::ACCEPTED).await;
+        let sender = EmailSender::http(&http_config(url)).unwrap();
+        sender.mail(&email()).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        let (headers, body) = &requests[0];
+        assert_eq!(headers["authorization"], "Bearer secret");
+        assert_eq!(body["from"], DEFAULT_FROM_SENDER);
+        assert_eq!(body["to"], "user1@framework.com");
+        assert_eq!(body["subject"], "Email Subject");
+        assert_eq!(body["html"], "<p>Welcome</p>");
+        assert!(body["raw"]
+            .as_str()
+            .unwrap()
+            .contains("Subject: Email Subject"));
+    }
+
+    #[tokio::test]
+    async fn http_api_errors_fail_the_delivery() {
+        let (url, _) = serve_api(axum::http::StatusCode::UNAUTHORIZED).await;
+        let sender = EmailSender::http(&http_config(url)).unwrap();
+
+        let err = sender.mail(&email()).await.unwrap_err();
+        assert!(err.to_string().contains("401 Unauthorized"));
+    }
+
+    #[tokio::test]
+    async fn can_send_email_with_attachments() {
+        let stub = StubTransport::new_ok();
+        let sender = EmailSender {
+            transport: EmailTransport::Test(stub.clone()),
+        };
+
+        let data = Email {
+            html: r#"<img src="cid:logo"> Your invoice"#.to_string(),
+            attachments: vec![
+                Attachment::bytes("invoice-42.pdf", b"%PDF-1.7".to_vec()),
+                Attachment::bytes("logo.png", b"PNG".to_vec()).inline("logo"),
+            ],
+            ..email()
+        };
+        sender.mail(&data).await.unwrap();
+
+        let messages = stub.messages();
+        let message = &messages[0].1;
+        assert!(message.contains("Content-Type: multipart/mixed"));
+        assert!(message.contains("Content-Type: multipart/related"));
+        assert!(message.contains("Content-Disposition: attachment; filename=\"invoice-42.pdf\""));
+        assert!(message.contains("Content-Type: application/pdf"));
+        assert!(message.contains("Content-ID: <logo>"));
+        assert!(message.contains("Content-Disposition: inline"));
+        assert!(message.contains("Content-Type: image/png"));
+    }
+
+    #[tokio::test]
+    async fn storage_attachments_must_be_loaded() {
+        let sender = EmailSender::stub();
+        let data = Email {
+            attachments: vec![Attachment::storage("invoice-42.pdf", "invoices/42.pdf")],
+            ..email()
+        };
+
+        let err = sender.mail(&data).await.unwrap_err();
+        assert!(err.to_string().contains("must be loaded from the storage"));
+    }
 }
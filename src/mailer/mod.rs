@@ -2,18 +2,22 @@
 //! trait and its implementation, `Email` structure, and the `MailerWorker` for
 //! asynchronous email processing.
 
+mod attachment;
 mod email_sender;
+mod preview;
 mod template;
 
 use async_trait::async_trait;
+pub use attachment::{Attachment, AttachmentContent};
 pub use email_sender::EmailSender;
 use include_dir::Dir;
+pub use preview::MailerPreviews;
 use serde::{Deserialize, Serialize};
 use tracing::error;
 
 use self::template::Template;
 use super::{app::AppContext, Result};
-use crate::prelude::BackgroundWorker;
+use crate::{prelude::BackgroundWorker, storage::Storage};
 
 pub const DEFAULT_FROM_SENDER: &str = "System <system@example.com>";
 
@@ -35,6 +39,7 @@ pub struct Args {
     pub bcc: Option<String>,
     pub cc: Option<String>,
     pub headers: Option<EmailHeaders>,
+    pub attachments: Vec<Attachment>,
 }
 
 /// The structure representing an email details.
@@ -58,6 +63,24 @@ pub struct Email {
     pub cc: Option<String>,
     /// Custom headers for the email (e.g., References, In-Reply-To, Message-ID)
     pub headers: Option<EmailHeaders>,
+    /// Attached files and inline images
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+impl Email {
+    /// Reads the content of the attachments stored in `storage`, which is
+    /// done by the [`MailerWorker`] before sending the email.
+    ///
+    /// # Errors
+    ///
+    /// When a file could not be read from the storage
+    pub async fn load_attachments(&mut self, storage: &Storage) -> Result<()> {
+        for attachment in &mut self.attachments {
+            attachment.load(storage).await?;
+        }
+        Ok(())
+    }
 }
 
 /// The options struct for configuring the email sender.
@@ -109,6 +132,7 @@ pub trait Mailer {
                 bcc: args.bcc.clone(),
                 cc: args.cc.clone(),
                 headers: args.headers.clone(),
+                attachments: args.attachments,
             },
         )
         .await
@@ -135,9 +159,12 @@ impl BackgroundWorker<Email> for MailerWorker {
 
     /// Performs the email sending operation using the provided [`AppContext`]
     /// and email details.
-    async fn perform(&self, email: Email) -> crate::Result<()> {
+    async fn perform(&self, mut email: Email) -> crate::Result<()> {
         if let Some(mailer) = &self.ctx.mailer {
-            let res = mailer.mail(&email).await;
+            let res = match email.load_attachments(&self.ctx.storage).await {
+                Ok(()) => mailer.mail(&email).await,
+                Err(err) => Err(err),
+            };
             match res {
                 Ok(res) => Ok(res),
                 Err(err) => {
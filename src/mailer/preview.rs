@@ -0,0 +1,331 @@
+//! # Mailer Previews
+//!
+//! Development routes listing the templates of the mailers and rendering
+//! them in the browser, so emails can be designed without triggering the
+//! flows sending them.
+//!
+//! Every directory holding a `subject.t` file under the embedded mailers
+//! directory is a template, named by its path (`auth/welcome`). Its locals
+//! are read at each request from `{fixtures}/{name}.json`, `.yaml` or
+//! `.yml`, `src/fixtures/mailers` by default, so editing a fixture only
+//! needs a page reload. Templates without a fixture are rendered with empty
+//! locals.
+//!
+//! ```rust, ignore
+//! use include_dir::{include_dir, Dir};
+//! use loco_rs::{controller::AppRoutes, mailer::MailerPreviews};
+//!
+//! static MAILERS: Dir<'_> = include_dir!("src/mailers");
+//!
+//! fn routes() -> AppRoutes {
+//!     AppRoutes::with_default_routes().add_route(MailerPreviews::new(&MAILERS).routes("/_mailers"))
+//! }
+//! ```
+//!
+//! The routes answer `404 Not Found` outside of the `development` and
+//! `test` environments.
+use std::path::{Path, PathBuf};
+
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    routing::get,
+};
+use include_dir::Dir;
+use serde::Deserialize;
+
+use super::template::{Content, Template};
+use crate::{
+    app::AppContext,
+    controller::{format, Routes},
+    environment::Environment,
+    Error, Result,
+};
+
+/// File marking a directory as a mailer template.
+const SUBJECT: &str = "subject.t";
+/// Extensions of the fixture files, in lookup order.
+const FIXTURE_EXTENSIONS: &[&str] = &["json", "yaml", "yml"];
+
+/// Previews of the mailer templates embedded in a directory.
+#[derive(Debug, Clone)]
+pub struct MailerPreviews {
+    dir: &'static Dir<'static>,
+    fixtures: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviewQuery {
+    /// `html` or `text` to respond with the rendered part only.
+    part: Option<String>,
+}
+
+impl MailerPreviews {
+    /// Previews of the templates under `dir`, usually
+    /// `include_dir!("src/mailers")`.
+    #[must_use]
+    pub fn new(dir: &'static Dir<'static>) -> Self {
+        Self {
+            dir,
+            fixtures: PathBuf::from("src/fixtures/mailers"),
+        }
+    }
+
+    /// Sets the directory of the fixture files holding the locals of the
+    /// templates.
+    #[must_use]
+    pub fn fixtures(mut self, path: impl Into<PathBuf>) -> Self {
+        self.fixtures = path.into();
+        self
+    }
+
+    /// Names of the templates, sorted.
+    #[must_use]
+    pub fn templates(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        collect_templates(self.dir, &mut names);
+        names.sort();
+        names
+    }
+
+    /// Reads the locals of a template from its fixture file.
+    ///
+    /// # Errors
+    ///
+    /// When the fixture file could not be read or parsed
+    pub fn locals(&self, name: &str) -> Result<serde_json::Value> {
+        for extension in FIXTURE_EXTENSIONS {
+            let path = self.fixtures.join(format!("{name}.{extension}"));
+            if !path.exists() {
+                continue;
+            }
+            let content = std::fs::read_to_string(&path)?;
+            return Ok(if *extension == "json" {
+                serde_json::from_str(&content)?
+            } else {
+                serde_yaml::from_str(&content)?
+            });
+        }
+        Ok(serde_json::json!({}))
+    }
+
+    /// Renders a template with the locals of its fixture.
+    ///
+    /// # Errors
+    ///
+    /// When the template does not exist, or could not be rendered
+    pub fn render(&self, name: &str) -> Result<Content> {
+        let dir = self
+            .template_dir(name)
+            .ok_or_else(|| Error::Message(format!("no mailer template found {name}")))?;
+        Template::new(dir).render(&self.locals(name)?)
+    }
+
+    /// Routes listing the templates at `prefix`, and rendering each of them
+    /// at `{prefix}/{name}`. Adding `?part=html` or `?part=text` responds with
+    /// the rendered part only.
+    #[must_use]
+    pub fn routes(self, prefix: &str) -> Routes {
+        let index_previews = self.clone();
+        // the index is served without a trailing slash, so its links are
+        // relative to the last segment of the prefix
+        let base = prefix
+            .trim_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        Routes::new()
+            .prefix(prefix)
+            .add(
+                "/",
+                get(move |State(ctx): State<AppContext>| async move {
+                    ensure_development(&ctx)?;
+                    format::html(&index_previews.index_page(&base))
+                }),
+            )
+            .add(
+                "/{*name}",
+                get(
+                    move |State(ctx): State<AppContext>,
+                          UrlPath(name): UrlPath<String>,
+                          Query(query): Query<PreviewQuery>| async move {
+                        ensure_development(&ctx)?;
+                        self.respond(name.trim_matches('/'), query.part.as_deref())
+                    },
+                ),
+            )
+    }
+
+    fn template_dir(&self, name: &str) -> Option<&'static Dir<'static>> {
+        if name.split('/').any(|segment| segment == "..") {
+            return None;
+        }
+        let dir = self.dir.get_dir(self.dir.path().join(name))?;
+        dir.get_file(dir.path().join(SUBJECT)).map(|_| dir)
+    }
+
+    fn respond(&self, name: &str, part: Option<&str>) -> Result<Response> {
+        if self.template_dir(name).is_none() {
+            return Err(Error::NotFound);
+        }
+        let content = match self.render(name) {
+            Ok(content) => content,
+            Err(err) => {
+                let page = page(
+                    name,
+                    &format!("<pre class=\"error\">{}</pre>", escape(&err.to_string())),
+                );
+                return Ok((StatusCode::INTERNAL_SERVER_ERROR, Html(page)).into_response());
+            }
+        };
+        match part {
+            Some("html") => format::html(&content.html),
+            Some("text") => format::text(&content.text),
+            Some(_) => Err(Error::BadRequest(
+                "part must be `html` or `text`".to_string(),
+            )),
+            None => format::html(&page(
+                name,
+                &format!(
+                    "<dl><dt>Subject</dt><dd>{subject}</dd></dl>\
+                     <h2>HTML</h2><iframe src=\"?part=html\"></iframe>\
+                     <h2>Text</h2><pre>{text}</pre>",
+                    subject = escape(&content.subject),
+                    text = escape(&content.text),
+                ),
+            )),
+        }
+    }
+
+    fn index_page(&self, base: &str) -> String {
+        let base = escape(base);
+        let items = self
+            .templates()
+            .iter()
+            .map(|name| {
+                let name = escape(name);
+                format!("<li><a href=\"{base}/{name}\">{name}</a></li>")
+            })
+            .collect::<String>();
+        page("Mailers", &format!("<ul>{items}</ul>"))
+    }
+}
+
+fn collect_templates(dir: &Dir<'_>, names: &mut Vec<String>) {
+    if dir.get_file(dir.path().join(SUBJECT)).is_some() {
+        names.push(template_name(dir.path()));
+    }
+    for sub in dir.dirs() {
+        collect_templates(sub, names);
+    }
+}
+
+fn template_name(path: &Path) -> String {
+    path.components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn ensure_development(ctx: &AppContext) -> Result<()> {
+    match ctx.environment {
+        Environment::Development | Environment::Test => Ok(()),
+        _ => Err(Error::NotFound),
+    }
+}
+
+fn escape(value: &str) -> String {
+    ::tera::escape_html(value)
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>{title}</title>\
+         <style>body{{font-family:sans-serif;margin:2em}}\
+         iframe{{width:100%;height:60vh;border:1px solid #ccc}}\
+         pre{{background:#f6f6f6;padding:1em;white-space:pre-wrap}}\
+         .error{{color:#b00}}</style></head>\
+         <body><h1>{title}</h1>{body}</body></html>",
+        title = escape(title),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{body::Body, http::Request};
+    use include_dir::include_dir;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    static TEMPLATES: Dir<'_> = include_dir!("tests/fixtures/email_template");
+
+    fn previews() -> MailerPreviews {
+        MailerPreviews::new(&TEMPLATES).fixtures("tests/fixtures/mailer_previews")
+    }
+
+    async fn get_page(environment: Environment, uri: &str) -> (StatusCode, String) {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.environment = environment;
+        let router = previews()
+            .routes("/_mailers")
+            .handlers
+            .into_iter()
+            .fold(axum::Router::new(), |router, handler| {
+                let uri = format!("/_mailers{}", handler.uri);
+                router.route(uri.trim_end_matches('/'), handler.method)
+            })
+            .with_state(ctx);
+        let response = router
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn can_list_templates() {
+        assert_eq!(previews().templates(), vec!["test".to_string()]);
+    }
+
+    #[test]
+    fn can_render_with_fixture_locals() {
+        let content = previews().render("test").unwrap();
+        assert_eq!(content.subject.trim(), "Test Jane <Doe>");
+        assert!(content.html.contains("/verify/preview-token"));
+        assert!(previews().render("missing").is_err());
+    }
+
+    #[tokio::test]
+    async fn can_serve_previews() {
+        let (status, body) = get_page(Environment::Development, "/_mailers").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("<a href=\"_mailers/test\">test</a>"));
+
+        let (status, body) = get_page(Environment::Development, "/_mailers/test").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Test Jane &lt;Doe&gt;"));
+        assert!(body.contains("<iframe src=\"?part=html\">"));
+
+        let (status, body) = get_page(Environment::Development, "/_mailers/test?part=html").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("/verify/preview-token"));
+
+        let (status, _) = get_page(Environment::Development, "/_mailers/missing").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn hides_previews_in_production() {
+        let (status, _) = get_page(Environment::Production, "/_mailers").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get_page(Environment::Production, "/_mailers/test").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
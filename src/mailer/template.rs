@@ -26,10 +26,11 @@ const HTML: &str = "html.t";
 const TEXT: &str = "text.t";
 
 /// Reads an embedded file from the provided directory and returns its content
-/// as a string.
+/// as a string. Entries are looked up by their path from the embedded root,
+/// so `dir` can also be a subdirectory of it.
 fn embedded_file(dir: &Dir<'_>, name: &str) -> Result<String> {
     Ok(String::from_utf8_lossy(
-        dir.get_file(name)
+        dir.get_file(dir.path().join(name))
             .ok_or_else(|| Error::Message(format!("no mailer template file found {name}")))?
             .contents(),
     )
@@ -95,4 +96,17 @@ mod tests {
             Template::new(&include_dir!("tests/fixtures/email_template/test")).render(&args)
         );
     }
+
+    #[test]
+    fn can_render_template_in_subdirectory() {
+        static TEMPLATES: Dir<'_> = include_dir!("tests/fixtures/email_template");
+        let args = serde_json::json!({
+            "verifyToken": "1111-2222-3333-4444",
+            "name": "nested",
+        });
+        let content = Template::new(TEMPLATES.get_dir("test").unwrap())
+            .render(&args)
+            .unwrap();
+        assert_eq!(content.subject.trim(), "Test nested");
+    }
 }
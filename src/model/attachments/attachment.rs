@@ -0,0 +1,38 @@
+//! Entity of the `loco_attachments` table, attaching a blob to a record of
+//! any model under a name.
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize)]
+#[sea_orm(table_name = "loco_attachments")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Name of the attachment, such as `avatar`.
+    pub name: String,
+    /// Table of the record.
+    pub record_type: String,
+    /// Primary key of the record.
+    pub record_id: String,
+    pub blob_id: i32,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::blob::Entity",
+        from = "Column::BlobId",
+        to = "super::blob::Column::Id",
+        on_delete = "Cascade"
+    )]
+    Blob,
+}
+
+impl Related<super::blob::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Blob.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,60 @@
+//! Entity of the `loco_blobs` table, describing a file stored in
+//! `ctx.storage`.
+use std::{path::Path, time::Duration};
+
+use sea_orm::entity::prelude::*;
+use serde::Serialize;
+
+use crate::storage::{drivers::PresignedRequest, Storage, StorageResult};
+
+#[derive(Clone, Debug, PartialEq, Eq, DeriveEntityModel, Serialize)]
+#[sea_orm(table_name = "loco_blobs")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    /// Key of the file in the storage, shared by the blobs of identical
+    /// files.
+    pub key: String,
+    /// File name given by the user, if any.
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub byte_size: i64,
+    pub checksum: Option<String>,
+    pub created_at: DateTimeWithTimeZone,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::attachment::Entity")]
+    Attachment,
+}
+
+impl Related<super::attachment::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Attachment.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
+
+impl Model {
+    /// Returns a request downloading the file, valid for the given duration.
+    /// See [`Storage::presigned_get`].
+    ///
+    /// # Errors
+    ///
+    /// When the storage can't presign requests and has no URL signer.
+    pub async fn url(
+        &self,
+        storage: &Storage,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage.presigned_get(self.path(), expires).await
+    }
+
+    /// Returns the key of the file in the storage, as a path.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        Path::new(&self.key)
+    }
+}
@@ -0,0 +1,680 @@
+//! # Attachments
+//!
+//! Associates the records of any `sea_orm` model with files stored in
+//! `ctx.storage`. Each file is described by a [`blob`] row, attached to a
+//! record under a name (such as `avatar`) by an [`attachment`] row. Blobs
+//! which are no longer attached are deleted from the storage with their row.
+//!
+//! Identical files share one stored file: attaching a file whose key or
+//! checksum is already recorded, such as a file stored with
+//! [`Storage::upload_content_addressed`], records a blob with the key of the
+//! recorded file, keeping its own file name and content type. The stored file
+//! is only deleted once no blob has its key anymore.
+//!
+//! The tables are created by a migration calling
+//! [`crate::schema::create_attachments_tables`]:
+//!
+//! ```rust,ignore
+//! async fn up(&self, m: &SchemaManager) -> Result<(), DbErr> {
+//!     create_attachments_tables(m).await
+//! }
+//! ```
+//!
+//! Every model gets the methods of [`HasAttachments`]:
+//!
+//! ```rust,ignore
+//! use loco_rs::model::attachments::{HasAttachments, NewBlob};
+//!
+//! async fn update_avatar(
+//!     State(ctx): State<AppContext>,
+//!     auth: auth::JWT,
+//!     uploads: Uploads,
+//! ) -> Result<Response> {
+//!     let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+//!     let avatar = uploads.file("avatar").ok_or(Error::BadRequest("missing avatar".into()))?;
+//!     let blob = user
+//!         .replace_attachment(&ctx, "avatar", NewBlob::from(avatar.clone()))
+//!         .await?;
+//!     format::json(blob.url(&ctx.storage, Duration::from_secs(3600)).await?.uri)
+//! }
+//! ```
+//!
+//! Attachments are not deleted with their record: call
+//! [`HasAttachments::purge_attachments`] before deleting a record, and
+//! [`purge_unattached`] periodically to delete the blobs uploaded but never
+//! attached.
+pub mod attachment;
+pub mod blob;
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use sea_orm::{
+    sea_query::{Query, Value},
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DatabaseConnection,
+    EntityName, EntityTrait, Iterable, ModelTrait, PrimaryKeyToColumn, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
+
+use super::{ModelError, ModelResult};
+use crate::{
+    app::AppContext,
+    controller::extractor::upload::UploadedFile,
+    storage::{checksum::Checksums, Storage, StorageResult},
+};
+
+/// A file stored in the storage, to be recorded as a [`blob`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewBlob {
+    pub key: PathBuf,
+    pub filename: Option<String>,
+    pub content_type: Option<String>,
+    pub byte_size: u64,
+    pub checksum: Option<String>,
+}
+
+impl NewBlob {
+    /// Stores the content under a generated key, keeping the extension of
+    /// the file name.
+    ///
+    /// # Errors
+    ///
+    /// When the content can't be stored.
+    pub async fn upload(
+        storage: &Storage,
+        filename: Option<&str>,
+        content_type: Option<&str>,
+        content: Bytes,
+    ) -> StorageResult<Self> {
+        let mut key = uuid::Uuid::new_v4().to_string();
+        if let Some(extension) = filename
+            .and_then(|filename| Path::new(filename).extension())
+            .and_then(|extension| extension.to_str())
+        {
+            key = format!("{key}.{extension}");
+        }
+        let key = PathBuf::from(key);
+        storage.upload(&key, &content).await?;
+        Ok(Self {
+            key,
+            filename: filename.map(ToString::to_string),
+            content_type: content_type.map(ToString::to_string),
+            byte_size: content.len() as u64,
+            checksum: Some(Checksums::of(&content).sha256),
+        })
+    }
+}
+
+impl From<UploadedFile> for NewBlob {
+    fn from(file: UploadedFile) -> Self {
+        Self {
+            key: file.key,
+            filename: file.file_name,
+            content_type: Some(file.content_type),
+            byte_size: file.size,
+            checksum: Some(file.checksum),
+        }
+    }
+}
+
+/// Methods managing the files attached to the records of a model,
+/// implemented for every model.
+#[async_trait]
+pub trait HasAttachments: ModelTrait + Sync {
+    /// Returns the table and primary key identifying the record in the
+    /// attachments table.
+    ///
+    /// # Errors
+    ///
+    /// When the primary key has a type which can't be recorded.
+    fn attachment_record(&self) -> ModelResult<(String, String)> {
+        let ids = <<Self::Entity as EntityTrait>::PrimaryKey as Iterable>::iter()
+            .map(|key| record_id(self.get(key.into_column())))
+            .collect::<ModelResult<Vec<_>>>()?;
+        Ok((
+            Self::Entity::default().table_name().to_string(),
+            ids.join(","),
+        ))
+    }
+
+    /// Returns the blobs attached under the given name, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// When the query fails.
+    async fn attachments(
+        &self,
+        db: &DatabaseConnection,
+        name: &str,
+    ) -> ModelResult<Vec<blob::Model>> {
+        let (record_type, record_id) = self.attachment_record()?;
+        Ok(blob::Entity::find()
+            .inner_join(attachment::Entity)
+            .filter(attachment::Column::RecordType.eq(record_type))
+            .filter(attachment::Column::RecordId.eq(record_id))
+            .filter(attachment::Column::Name.eq(name))
+            .order_by_asc(attachment::Column::Id)
+            .all(db)
+            .await?)
+    }
+
+    /// Returns the latest blob attached under the given name.
+    ///
+    /// # Errors
+    ///
+    /// When the query fails.
+    async fn attachment(
+        &self,
+        db: &DatabaseConnection,
+        name: &str,
+    ) -> ModelResult<Option<blob::Model>> {
+        Ok(self.attachments(db, name).await?.pop())
+    }
+
+    /// Records a stored file and attaches it under the given name, next to
+    /// the blobs already attached. When an identical file is already
+    /// recorded, the blob gets its key, and the stored file is deleted if it
+    /// was stored under another key.
+    ///
+    /// # Errors
+    ///
+    /// When the blob or attachment can't be recorded.
+    async fn attach(
+        &self,
+        ctx: &AppContext,
+        name: &str,
+        blob: NewBlob,
+    ) -> ModelResult<blob::Model> {
+        let (record_type, record_id) = self.attachment_record()?;
+        let txn = ctx.db.begin().await?;
+        let (blob, duplicate) = create_blob(&txn, blob).await?;
+        attachment::ActiveModel {
+            name: ActiveValue::set(name.to_string()),
+            record_type: ActiveValue::set(record_type),
+            record_id: ActiveValue::set(record_id),
+            blob_id: ActiveValue::set(blob.id),
+            created_at: ActiveValue::set(chrono::Utc::now().into()),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+        txn.commit().await?;
+
+        // the file is a copy of a recorded one, whose key the blob got
+        if let Some(duplicate) = duplicate {
+            if let Err(err) = ctx.storage.delete(&duplicate).await {
+                tracing::warn!(
+                    key = %duplicate.display(),
+                    err = %err,
+                    "could not delete the file of a duplicate blob"
+                );
+            }
+        }
+        Ok(blob)
+    }
+
+    /// Attaches a stored file under the given name, and purges the blobs
+    /// previously attached under this name.
+    ///
+    /// # Errors
+    ///
+    /// When the blob can't be attached or the previous blobs can't be
+    /// purged.
+    async fn replace_attachment(
+        &self,
+        ctx: &AppContext,
+        name: &str,
+        blob: NewBlob,
+    ) -> ModelResult<blob::Model> {
+        let previous = self.attachments(&ctx.db, name).await?;
+        let blob = self.attach(ctx, name, blob).await?;
+        detach(ctx, self, Some(name), previous.iter().map(|blob| blob.id)).await?;
+        Ok(blob)
+    }
+
+    /// Detaches the blobs attached under the given name, or all the blobs of
+    /// the record when no name is given, and purges those no longer
+    /// attached. Returns the number of purged blobs.
+    ///
+    /// # Errors
+    ///
+    /// When the attachments can't be deleted.
+    async fn purge_attachments(&self, ctx: &AppContext, name: Option<&str>) -> ModelResult<u64> {
+        let (record_type, record_id) = self.attachment_record()?;
+        let mut query = attachment::Entity::find()
+            .filter(attachment::Column::RecordType.eq(record_type))
+            .filter(attachment::Column::RecordId.eq(record_id));
+        if let Some(name) = name {
+            query = query.filter(attachment::Column::Name.eq(name));
+        }
+        let blob_ids = query
+            .all(&ctx.db)
+            .await?
+            .into_iter()
+            .map(|attachment| attachment.blob_id);
+        detach(ctx, self, name, blob_ids).await
+    }
+}
+
+impl<M: ModelTrait + Sync> HasAttachments for M {}
+
+/// Records a blob for a stored file. When the key or an identical file is
+/// already recorded, the blob gets the recorded key, and the key of the
+/// stored file is returned when it is a copy to delete once the transaction
+/// is committed.
+///
+/// The recorded blobs are locked until the transaction ends, so their file
+/// can't be purged before the blob sharing it is committed.
+async fn create_blob<C: ConnectionTrait>(
+    db: &C,
+    new_blob: NewBlob,
+) -> ModelResult<(blob::Model, Option<PathBuf>)> {
+    let mut key = new_blob.key.display().to_string();
+    let byte_size = i64::try_from(new_blob.byte_size).map_err(ModelError::wrap)?;
+    let mut identical = Condition::any().add(blob::Column::Key.eq(&key));
+    if let Some(checksum) = &new_blob.checksum {
+        identical = identical.add(
+            Condition::all()
+                .add(blob::Column::Checksum.eq(checksum))
+                .add(blob::Column::ByteSize.eq(byte_size)),
+        );
+    }
+    let recorded = blob::Entity::find()
+        .filter(identical)
+        .order_by_asc(blob::Column::Id)
+        .lock_exclusive()
+        .all(db)
+        .await?;
+    let mut duplicate = None;
+    if !recorded.iter().any(|blob| blob.key == key) {
+        if let Some(recorded) = recorded.into_iter().next() {
+            duplicate = Some(new_blob.key);
+            key = recorded.key;
+        }
+    }
+
+    let blob = blob::ActiveModel {
+        key: ActiveValue::set(key),
+        filename: ActiveValue::set(new_blob.filename),
+        content_type: ActiveValue::set(new_blob.content_type),
+        byte_size: ActiveValue::set(byte_size),
+        checksum: ActiveValue::set(new_blob.checksum),
+        created_at: ActiveValue::set(chrono::Utc::now().into()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok((blob, duplicate))
+}
+
+/// Deletes the attachments of the record to the given blobs, then purges the
+/// blobs which are no longer attached.
+async fn detach<M: HasAttachments>(
+    ctx: &AppContext,
+    record: &M,
+    name: Option<&str>,
+    blob_ids: impl Iterator<Item = i32> + Send,
+) -> ModelResult<u64> {
+    let blob_ids = blob_ids.collect::<Vec<_>>();
+    if blob_ids.is_empty() {
+        return Ok(0);
+    }
+    let (record_type, record_id) = record.attachment_record()?;
+    let mut delete = attachment::Entity::delete_many()
+        .filter(attachment::Column::RecordType.eq(record_type))
+        .filter(attachment::Column::RecordId.eq(record_id))
+        .filter(attachment::Column::BlobId.is_in(blob_ids.clone()));
+    if let Some(name) = name {
+        delete = delete.filter(attachment::Column::Name.eq(name));
+    }
+    delete.exec(&ctx.db).await?;
+
+    let unattached = blob::Entity::find()
+        .filter(blob::Column::Id.is_in(blob_ids))
+        .filter(blob::Column::Id.not_in_subquery(attached_blob_ids()))
+        .all(&ctx.db)
+        .await?;
+    purge(ctx, unattached).await
+}
+
+/// Purges the blobs which are not attached to any record and were created
+/// more than `older_than` ago, such as files uploaded with a form which was
+/// never submitted. Returns the number of purged blobs.
+///
+/// # Errors
+///
+/// When the blobs can't be queried or deleted.
+pub async fn purge_unattached(ctx: &AppContext, older_than: chrono::Duration) -> ModelResult<u64> {
+    let unattached = blob::Entity::find()
+        .filter(blob::Column::CreatedAt.lt(chrono::Utc::now() - older_than))
+        .filter(blob::Column::Id.not_in_subquery(attached_blob_ids()))
+        .all(&ctx.db)
+        .await?;
+    purge(ctx, unattached).await
+}
+
+fn attached_blob_ids() -> sea_orm::sea_query::SelectStatement {
+    Query::select()
+        .column(attachment::Column::BlobId)
+        .from(attachment::Entity)
+        .to_owned()
+}
+
+/// Deletes the rows of the blobs, then the files whose key no other blob
+/// has. Files which can't be deleted are logged and left in the storage.
+async fn purge(ctx: &AppContext, blobs: Vec<blob::Model>) -> ModelResult<u64> {
+    if blobs.is_empty() {
+        return Ok(0);
+    }
+    let deleted = blob::Entity::delete_many()
+        .filter(blob::Column::Id.is_in(blobs.iter().map(|blob| blob.id)))
+        .exec(&ctx.db)
+        .await?
+        .rows_affected;
+    let mut keys = blobs
+        .into_iter()
+        .map(|blob| blob.key)
+        .collect::<BTreeSet<_>>();
+    let shared = blob::Entity::find()
+        .filter(blob::Column::Key.is_in(keys.iter().cloned()))
+        .all(&ctx.db)
+        .await?;
+    for blob in shared {
+        keys.remove(&blob.key);
+    }
+    for key in keys {
+        if let Err(err) = ctx.storage.delete(Path::new(&key)).await {
+            tracing::warn!(key, err = %err, "could not delete the file of a purged blob");
+        }
+    }
+    Ok(deleted)
+}
+
+fn record_id(value: Value) -> ModelResult<String> {
+    match value {
+        Value::TinyInt(Some(id)) => Ok(id.to_string()),
+        Value::SmallInt(Some(id)) => Ok(id.to_string()),
+        Value::Int(Some(id)) => Ok(id.to_string()),
+        Value::BigInt(Some(id)) => Ok(id.to_string()),
+        Value::TinyUnsigned(Some(id)) => Ok(id.to_string()),
+        Value::SmallUnsigned(Some(id)) => Ok(id.to_string()),
+        Value::Unsigned(Some(id)) => Ok(id.to_string()),
+        Value::BigUnsigned(Some(id)) => Ok(id.to_string()),
+        Value::String(Some(id)) => Ok(*id),
+        Value::Uuid(Some(id)) => Ok(id.to_string()),
+        value => Err(ModelError::Message(format!(
+            "can't attach files to records with primary key {value:?}"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+    use sea_orm::{ConnectionTrait, Schema};
+    use sea_orm_migration::SchemaManager;
+
+    use super::*;
+    use crate::{storage::drivers, tests_cfg::db::test_db};
+
+    async fn context() -> AppContext {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.db = sea_orm::Database::connect("sqlite::memory:").await.unwrap();
+        ctx.storage = Storage::single(drivers::mem::new()).into();
+        crate::schema::create_attachments_tables(&SchemaManager::new(&ctx.db))
+            .await
+            .unwrap();
+        let backend = ctx.db.get_database_backend();
+        ctx.db
+            .execute(backend.build(&Schema::new(backend).create_table_from_entity(test_db::Entity)))
+            .await
+            .unwrap();
+        ctx
+    }
+
+    fn record(id: i32) -> test_db::Model {
+        test_db::Model {
+            id,
+            name: format!("record {id}"),
+            created_at: chrono::Utc::now().naive_utc(),
+            updated_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    async fn upload(ctx: &AppContext, content: &'static str) -> NewBlob {
+        NewBlob::upload(
+            &ctx.storage,
+            Some("file.txt"),
+            Some("text/plain"),
+            Bytes::from(content),
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_attach_and_replace() {
+        let ctx = context().await;
+        let record = record(1);
+        assert_eq!(
+            record.attachment_record().unwrap(),
+            ("loco".to_string(), "1".to_string())
+        );
+
+        let first = record
+            .attach(&ctx, "avatar", upload(&ctx, "first").await)
+            .await
+            .unwrap();
+        assert_eq!(first.filename.as_deref(), Some("file.txt"));
+        assert_eq!(first.byte_size, 5);
+        assert!(first.key.ends_with(".txt"));
+        // attachments are scoped to the record and name
+        record
+            .attach(&ctx, "document", upload(&ctx, "document").await)
+            .await
+            .unwrap();
+        self::record(2)
+            .attach(&ctx, "avatar", upload(&ctx, "other").await)
+            .await
+            .unwrap();
+        assert_eq!(
+            record.attachments(&ctx.db, "avatar").await.unwrap(),
+            vec![first.clone()]
+        );
+
+        let second = record
+            .replace_attachment(&ctx, "avatar", upload(&ctx, "second").await)
+            .await
+            .unwrap();
+        assert_eq!(
+            record.attachment(&ctx.db, "avatar").await.unwrap(),
+            Some(second.clone())
+        );
+        assert_eq!(
+            record.attachments(&ctx.db, "avatar").await.unwrap().len(),
+            1
+        );
+        let content: String = ctx.storage.download(second.path()).await.unwrap();
+        assert_eq!(content, "second");
+        // the replaced blob is purged with its file
+        assert!(blob::Entity::find_by_id(first.id)
+            .one(&ctx.db)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(ctx.storage.download::<String>(first.path()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_purge_attachments() {
+        let ctx = context().await;
+        let record = record(1);
+        let avatar = record
+            .attach(&ctx, "avatar", upload(&ctx, "avatar").await)
+            .await
+            .unwrap();
+        let document = record
+            .attach(&ctx, "document", upload(&ctx, "document").await)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            record
+                .purge_attachments(&ctx, Some("avatar"))
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(ctx.storage.download::<String>(avatar.path()).await.is_err());
+        assert_eq!(
+            record.attachment(&ctx.db, "document").await.unwrap(),
+            Some(document.clone())
+        );
+
+        assert_eq!(record.purge_attachments(&ctx, None).await.unwrap(), 1);
+        assert!(ctx
+            .storage
+            .download::<String>(document.path())
+            .await
+            .is_err());
+        assert_eq!(attachment::Entity::find().all(&ctx.db).await.unwrap(), []);
+    }
+
+    #[tokio::test]
+    async fn shares_files_of_identical_blobs() {
+        let ctx = context().await;
+        let content = Bytes::from("shared");
+        let key = ctx
+            .storage
+            .upload_content_addressed(Path::new("blobs"), &content)
+            .await
+            .unwrap();
+        let new_blob = NewBlob {
+            key: key.clone(),
+            filename: Some("shared.txt".to_string()),
+            content_type: None,
+            byte_size: content.len() as u64,
+            checksum: Some(Checksums::of(&content).sha256),
+        };
+        let first = record(1)
+            .attach(&ctx, "document", new_blob.clone())
+            .await
+            .unwrap();
+        let second = record(2)
+            .attach(
+                &ctx,
+                "document",
+                NewBlob {
+                    filename: Some("other.txt".to_string()),
+                    ..new_blob
+                },
+            )
+            .await
+            .unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(second.key, first.key);
+        assert_eq!(second.filename.as_deref(), Some("other.txt"));
+        // an identical file stored under another key
+        let copy = record(3)
+            .attach(&ctx, "document", upload(&ctx, "shared").await)
+            .await
+            .unwrap();
+        assert_eq!(copy.key, first.key);
+        assert_eq!(copy.filename.as_deref(), Some("file.txt"));
+        assert_eq!(copy.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(blob::Entity::find().all(&ctx.db).await.unwrap().len(), 3);
+        assert_eq!(
+            ctx.storage
+                .list(Path::new(""), true)
+                .await
+                .unwrap()
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // attached again under the same name
+        let replaced = record(1)
+            .replace_attachment(&ctx, "document", upload(&ctx, "shared").await)
+            .await
+            .unwrap();
+        assert_eq!(replaced.key, first.key);
+        assert_eq!(
+            record(1).attachments(&ctx.db, "document").await.unwrap(),
+            vec![replaced]
+        );
+
+        // the file is kept until no blob has its key
+        for id in [1, 2] {
+            assert_eq!(record(id).purge_attachments(&ctx, None).await.unwrap(), 1);
+        }
+        let stored: String = ctx.storage.download(&key).await.unwrap();
+        assert_eq!(stored, "shared");
+        assert_eq!(record(3).purge_attachments(&ctx, None).await.unwrap(), 1);
+        assert!(ctx.storage.download::<String>(&key).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn keeps_no_blob_when_attaching_fails() {
+        let ctx = context().await;
+        ctx.db
+            .execute_unprepared("DROP TABLE loco_attachments")
+            .await
+            .unwrap();
+
+        let new_blob = upload(&ctx, "document").await;
+        assert!(record(1)
+            .attach(&ctx, "document", new_blob.clone())
+            .await
+            .is_err());
+        assert_eq!(blob::Entity::find().all(&ctx.db).await.unwrap(), []);
+        // the file is kept for the caller
+        let stored: String = ctx.storage.download(&new_blob.key).await.unwrap();
+        assert_eq!(stored, "document");
+    }
+
+    #[tokio::test]
+    async fn can_purge_unattached_blobs() {
+        let ctx = context().await;
+        let attached = record(1)
+            .attach(&ctx, "avatar", upload(&ctx, "attached").await)
+            .await
+            .unwrap();
+        let unattached = upload(&ctx, "unattached").await;
+        let unattached = blob::ActiveModel {
+            key: ActiveValue::set(unattached.key.display().to_string()),
+            byte_size: ActiveValue::set(10),
+            created_at: ActiveValue::set((chrono::Utc::now() - chrono::Duration::hours(2)).into()),
+            ..Default::default()
+        }
+        .insert(&ctx.db)
+        .await
+        .unwrap();
+
+        assert_eq!(
+            purge_unattached(&ctx, chrono::Duration::hours(3))
+                .await
+                .unwrap(),
+            0
+        );
+        assert_eq!(
+            purge_unattached(&ctx, chrono::Duration::hours(1))
+                .await
+                .unwrap(),
+            1
+        );
+        assert!(ctx
+            .storage
+            .download::<String>(unattached.path())
+            .await
+            .is_err());
+        let content: String = ctx.storage.download(attached.path()).await.unwrap();
+        assert_eq!(content, "attached");
+    }
+}
@@ -5,10 +5,13 @@ use std::{
     collections::HashMap,
     fmt, io,
     path::{Path, PathBuf},
-    sync::OnceLock,
+    str::FromStr,
+    sync::{Arc, OnceLock},
     time::{Duration, Instant},
 };
 
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use tokio_cron_scheduler::{JobScheduler, JobSchedulerError};
@@ -40,6 +43,9 @@ pub enum Error {
     #[error("Invalid cron {cron}. err: '{}'", error.as_display())]
     InvalidCronSyntax { cron: String, error: String },
 
+    #[error("Invalid timezone {timezone}. err: '{}'", error.as_display())]
+    InvalidTimezone { timezone: String, error: String },
+
     #[error(transparent)]
     Question(#[from] JobSchedulerError),
 
@@ -83,6 +89,33 @@ pub struct Job {
     pub tags: Option<Vec<String>>,
     /// Output settings for the job.
     pub output: Option<Output>,
+    /// The IANA timezone the schedule is evaluated in (e.g. `Europe/Berlin`).
+    /// Defaults to UTC.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// What to do when the job is triggered while a previous run is still in
+    /// progress.
+    #[serde(default)]
+    pub overlap: Overlap,
+    /// Maximum run time in milliseconds, after which the spawned command is
+    /// killed.
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+/// Policy applied when a job fires while its previous run has not finished.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Overlap {
+    /// Drop the new run.
+    #[serde(rename = "skip")]
+    Skip,
+    /// Wait for the previous run to finish, then run.
+    #[serde(rename = "queue")]
+    Queue,
+    /// Run concurrently with the previous run.
+    #[default]
+    #[serde(rename = "allow")]
+    Allow,
 }
 
 impl fmt::Display for Scheduler {
@@ -153,6 +186,8 @@ pub struct JobDescription {
     pub output: Output,
     /// The environment in which the job will run.
     pub environment: Environment,
+    /// Kill the command when it runs longer than this duration.
+    pub timeout: Option<Duration>,
 }
 
 impl Job {
@@ -182,8 +217,64 @@ impl Job {
                 .clone()
                 .unwrap_or_else(|| default_output.clone()),
             environment: environment.clone(),
+            timeout: self.timeout.map(Duration::from_millis),
         }
     }
+
+    /// Resolves the job's schedule into cron syntax, converting English
+    /// expressions (e.g. `every 15 seconds`) when needed.
+    ///
+    /// # Errors
+    ///
+    /// When the English expression could not be converted.
+    pub fn cron_syntax(&self) -> Result<String> {
+        if get_re_is_cron_syntax().is_match(&self.cron) {
+            Ok(self.cron.clone())
+        } else {
+            english_to_cron::str_cron_syntax(&self.cron).map_err(|err| Error::InvalidCronSyntax {
+                cron: self.cron.clone(),
+                error: err.to_string(),
+            })
+        }
+    }
+
+    /// Returns the next `count` fire times of the job after the given time,
+    /// in the job's timezone.
+    ///
+    /// # Errors
+    ///
+    /// When the schedule or the timezone is invalid.
+    pub fn upcoming(&self, after: &DateTime<Utc>, count: usize) -> Result<Vec<DateTime<Tz>>> {
+        let cron_syntax = self.cron_syntax()?;
+        let schedule =
+            cron::Schedule::from_str(&cron_syntax).map_err(|err| Error::InvalidCronSyntax {
+                cron: cron_syntax.clone(),
+                error: err.to_string(),
+            })?;
+        let timezone = self.tz()?;
+
+        Ok(schedule
+            .after(&after.with_timezone(&timezone))
+            .take(count)
+            .collect())
+    }
+
+    /// Resolves the timezone the job's schedule is evaluated in, defaulting
+    /// to UTC.
+    ///
+    /// # Errors
+    ///
+    /// When the configured timezone is not a valid IANA timezone name.
+    pub fn tz(&self) -> Result<Tz> {
+        self.timezone.as_ref().map_or(Ok(Tz::UTC), |timezone| {
+            timezone
+                .parse::<Tz>()
+                .map_err(|error| Error::InvalidTimezone {
+                    timezone: timezone.clone(),
+                    error: error.to_string(),
+                })
+        })
+    }
 }
 
 impl JobDescription {
@@ -191,7 +282,10 @@ impl JobDescription {
     ///
     /// # Errors
     ///
-    /// In addition to all the IO errors possible
+    /// In addition to all the IO errors possible, returns
+    /// [`io::ErrorKind::TimedOut`] when the command was killed after
+    /// exceeding its timeout. On Unix, the command runs in its own process
+    /// group, so the processes it started are killed along with it.
     pub fn run(&self) -> io::Result<std::process::Output> {
         tracing::info!(command = &self.command, "execute job command");
         let mut exec_job =
@@ -201,7 +295,86 @@ impl JobDescription {
             Output::STDOUT => exec_job,
         };
 
-        exec_job.run()
+        let Some(timeout) = self.timeout else {
+            return exec_job.run();
+        };
+
+        #[cfg(unix)]
+        {
+            exec_job = exec_job.before_spawn(|command| {
+                std::os::unix::process::CommandExt::process_group(command, 0);
+                Ok(())
+            });
+        }
+        let handle = exec_job.start()?;
+        if handle.wait_timeout(timeout)?.is_some() {
+            return handle.into_output();
+        }
+
+        #[cfg(unix)]
+        for pid in handle.pids() {
+            let Ok(pid) = libc::pid_t::try_from(pid) else {
+                continue;
+            };
+            // SAFETY: `kill` has no memory safety requirements. The process
+            // is a group leader which is not reaped yet, so its id still
+            // names the group of the job.
+            unsafe {
+                libc::kill(-pid, libc::SIGKILL);
+            }
+        }
+        handle.kill()?;
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            format!("job command exceeded timeout of {timeout:?}"),
+        ))
+    }
+}
+
+/// Executes a job according to its [`Overlap`] policy.
+#[derive(Clone)]
+struct JobRunner {
+    name: String,
+    description: JobDescription,
+    overlap: Overlap,
+    running: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl JobRunner {
+    fn new(name: &str, description: JobDescription, overlap: Overlap) -> Self {
+        Self {
+            name: name.to_string(),
+            description,
+            overlap,
+            running: Arc::new(tokio::sync::Mutex::new(())),
+        }
+    }
+
+    async fn execute(&self, uuid: Uuid) {
+        let _guard = match self.overlap {
+            Overlap::Allow => None,
+            Overlap::Queue => Some(self.running.clone().lock_owned().await),
+            Overlap::Skip => {
+                if let Ok(guard) = self.running.clone().try_lock_owned() {
+                    Some(guard)
+                } else {
+                    tracing::warn!(
+                        job_name = self.name,
+                        job_id = ?uuid,
+                        "previous run still in progress, skipping scheduler job"
+                    );
+                    return;
+                }
+            }
+        };
+
+        let name = self.name.clone();
+        let description = self.description.clone();
+        if let Err(err) =
+            tokio::task::spawn_blocking(move || execute_job(&name, uuid, &description)).await
+        {
+            tracing::error!(error = %err, "scheduler job task panicked");
+        }
     }
 }
 
@@ -238,6 +411,7 @@ impl Scheduler {
 
         let mut jobs = HashMap::new();
         for (job_name, job) in &data.jobs {
+            job.tz()?;
             if job.shell {
                 jobs.insert(job_name.clone(), job.clone());
             } else {
@@ -286,6 +460,23 @@ impl Scheduler {
         Self { jobs, ..self }
     }
 
+    /// Computes the next `count` fire times of every job after the given
+    /// time, sorted by job name. Invalid schedules are reported per job.
+    #[must_use]
+    pub fn upcoming(
+        &self,
+        after: &DateTime<Utc>,
+        count: usize,
+    ) -> Vec<(String, Result<Vec<DateTime<Tz>>>)> {
+        let mut job_names: Vec<&String> = self.jobs.keys().collect();
+        job_names.sort();
+
+        job_names
+            .into_iter()
+            .map(|job_name| (job_name.clone(), self.jobs[job_name].upcoming(after, count)))
+            .collect()
+    }
+
     /// Runs the scheduled jobs according to their cron expressions.
     ///
     /// # Errors
@@ -297,44 +488,34 @@ impl Scheduler {
         for (job_name, job) in &self.jobs {
             let job_description =
                 job.prepare_command(&self.binary_path, &self.default_output, &self.environment);
+            let runner = JobRunner::new(job_name, job_description, job.overlap);
+            let timezone = job.tz()?;
 
-            let cron_syntax = if get_re_is_cron_syntax().is_match(&job.cron) {
-                job.cron.clone()
-            } else {
-                english_to_cron::str_cron_syntax(&job.cron).map_err(|err| {
-                    Error::InvalidCronSyntax {
-                        cron: job.cron.clone(),
-                        error: err.to_string(),
-                    }
-                })?
-            };
+            let cron_syntax = job.cron_syntax()?;
 
             if job.run_on_start {
-                let job_description = job_description.clone();
-                let job_name = job_name.clone();
+                let runner = runner.clone();
                 sched
                     .add(tokio_cron_scheduler::Job::new_one_shot_async(
                         Duration::from_secs(0),
                         move |uuid, _l| {
-                            let job_description = job_description.clone();
-                            let job_name = job_name.clone();
+                            let runner = runner.clone();
                             Box::pin(async move {
-                                execute_job(job_name.as_str(), uuid, &job_description);
+                                runner.execute(uuid).await;
                             })
                         },
                     )?)
                     .await?;
             }
 
-            let job_name = job_name.clone();
             sched
-                .add(tokio_cron_scheduler::Job::new_async(
+                .add(tokio_cron_scheduler::Job::new_async_tz(
                     cron_syntax.as_str(),
+                    timezone,
                     move |uuid, mut _l| {
-                        let job_description = job_description.clone();
-                        let job_name = job_name.clone();
+                        let runner = runner.clone();
                         Box::pin(async move {
-                            execute_job(job_name.as_str(), uuid, &job_description);
+                            runner.execute(uuid).await;
                         })
                     },
                 )?)
@@ -500,6 +681,9 @@ jobs:
             cron: "*/5 * * * * *".to_string(),
             tags: None,
             output: None,
+            timezone: None,
+            overlap: Overlap::Allow,
+            timeout: None,
         };
 
         let prepare_command = job.prepare_command(
@@ -513,6 +697,176 @@ jobs:
         );
     }
 
+    #[test]
+    pub fn can_load_job_with_timezone_overlap_and_timeout() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+jobs:
+  report:
+    run: "echo report"
+    shell: true
+    schedule: "0 0 2 * * *"
+    timezone: Europe/Berlin
+    overlap: skip
+    timeout: 60000
+"#,
+        )
+        .unwrap();
+
+        let job = config.jobs.get("report").unwrap();
+        assert_eq!(job.tz().unwrap(), chrono_tz::Europe::Berlin);
+        assert_eq!(job.overlap, Overlap::Skip);
+        assert_eq!(
+            job.prepare_command(Path::new("[BIN_PATH]"), &Output::STDOUT, &Environment::Test)
+                .timeout,
+            Some(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    pub fn cant_load_job_with_invalid_timezone() {
+        let config: Config = serde_yaml::from_str(
+            r#"
+jobs:
+  report:
+    run: "echo report"
+    shell: true
+    schedule: "0 0 2 * * *"
+    timezone: Mars/Olympus
+"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            Scheduler::new::<AppHook>(&config, &Environment::Test),
+            Err(Error::InvalidTimezone { .. })
+        ));
+    }
+
+    #[test]
+    pub fn can_kill_job_after_timeout() {
+        let job_description = JobDescription {
+            command: "sleep 5".to_string(),
+            output: Output::Silent,
+            environment: Environment::Test,
+            timeout: Some(Duration::from_millis(100)),
+        };
+
+        let start = Instant::now();
+        let err = job_description.run().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn can_kill_processes_started_by_job_after_timeout() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let marker = tree.root.join("marker");
+        let job_description = JobDescription {
+            // the subshell is a child process of the shell running the job
+            command: format!("(sleep 0.5 && touch {}); true", marker.display()),
+            output: Output::Silent,
+            environment: Environment::Test,
+            timeout: Some(Duration::from_millis(100)),
+        };
+
+        let err = job_description.run().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        std::thread::sleep(Duration::from_secs(1));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    pub fn can_list_upcoming_fire_times() {
+        let (scheduler, _tree) = setup_scheduler_config();
+        let mut scheduler = scheduler.by_spec(&Spec {
+            name: Some("run_on_start_task".to_string()),
+            tag: None,
+        });
+        if let Some(job) = scheduler.jobs.get_mut("run_on_start_task") {
+            job.cron = "at 2:00 am".to_string();
+            job.timezone = Some("Europe/Berlin".to_string());
+        }
+
+        let after = DateTime::parse_from_rfc3339("2025-01-10T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let upcoming = scheduler.upcoming(&after, 3);
+
+        assert_eq!(upcoming.len(), 1);
+        let (job_name, times) = &upcoming[0];
+        assert_eq!(job_name, "run_on_start_task");
+        assert_eq!(
+            times
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|time| time.to_rfc3339())
+                .collect::<Vec<_>>(),
+            vec![
+                "2025-01-11T02:00:00+01:00",
+                "2025-01-12T02:00:00+01:00",
+                "2025-01-13T02:00:00+01:00",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn can_flag_invalid_schedule_in_upcoming() {
+        let (mut scheduler, _tree) = setup_scheduler_config();
+        if let Some(job) = scheduler.jobs.get_mut("print_task") {
+            job.cron = "* * *".to_string();
+        }
+
+        let upcoming = scheduler.upcoming(&Utc::now(), 2);
+
+        assert_eq!(upcoming.len(), 3);
+        for (job_name, times) in upcoming {
+            if job_name == "print_task" {
+                assert!(matches!(times, Err(Error::InvalidCronSyntax { .. })));
+            } else {
+                assert_eq!(times.unwrap().len(), 2);
+            }
+        }
+    }
+
+    #[tokio::test]
+    pub async fn can_skip_overlapping_runs() {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .drop(true)
+            .add("overlap.txt", "")
+            .create()
+            .unwrap();
+
+        let runner = JobRunner::new(
+            "overlap",
+            JobDescription {
+                command: format!(
+                    "echo loco >> {} && sleep 1",
+                    tree_fs.root.join("overlap.txt").display()
+                ),
+                output: Output::Silent,
+                environment: Environment::Test,
+                timeout: None,
+            },
+            Overlap::Skip,
+        );
+
+        tokio::join!(
+            runner.execute(Uuid::new_v4()),
+            runner.execute(Uuid::new_v4())
+        );
+
+        assert_eq!(
+            std::fs::read_to_string(tree_fs.root.join("overlap.txt"))
+                .unwrap()
+                .lines()
+                .count(),
+            1
+        );
+    }
+
     #[tokio::test]
     pub async fn can_run() {
         let (mut scheduler, _config_tree) = setup_scheduler_config();
@@ -560,6 +914,9 @@ jobs:
                     cron: "run every 1 second".to_string(),
                     tags: None,
                     output: None,
+                    timezone: None,
+                    overlap: Overlap::Allow,
+                    timeout: None,
                 },
             ),
             (
@@ -574,6 +931,9 @@ jobs:
                     cron: "* * * * * ? *".to_string(),
                     tags: None,
                     output: None,
+                    timezone: None,
+                    overlap: Overlap::Allow,
+                    timeout: None,
                 },
             ),
             (
@@ -588,6 +948,9 @@ jobs:
                     cron: "0 0 * * * * *".to_string(),
                     tags: None,
                     output: None,
+                    timezone: None,
+                    overlap: Overlap::Allow,
+                    timeout: None,
                 },
             ),
         ]);
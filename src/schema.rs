@@ -15,6 +15,28 @@ enum GeneralIds {
     UpdatedAt,
 }
 
+#[derive(Iden)]
+enum LocoCache {
+    Table,
+    CacheKey,
+    Value,
+    ExpiresAt,
+}
+
+#[derive(Iden)]
+enum LocoCacheTags {
+    Table,
+    Tag,
+    CacheKey,
+}
+
+#[derive(Iden)]
+enum LocoCacheStats {
+    Table,
+    Name,
+    Count,
+}
+
 /// Alter table
 pub fn alter<T: IntoIden + 'static>(name: T) -> TableAlterStatement {
     Table::alter().table(name).take()
@@ -880,6 +902,186 @@ pub async fn drop_table(m: &SchemaManager<'_>, table: &str) -> Result<(), DbErr>
         .await
 }
 
+///
+/// Create the tables of [`crate::model::attachments`], holding the blobs
+/// stored in `ctx.storage` and their attachments to models.
+/// ```ignore
+/// create_attachments_tables(m).await;
+/// ```
+///
+/// # Errors
+/// fails when it fails
+pub async fn create_attachments_tables(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    use crate::model::attachments::{attachment, blob};
+
+    m.create_table(
+        Table::create()
+            .table(blob::Entity)
+            .if_not_exists()
+            .col(pk_auto(blob::Column::Id))
+            .col(string(blob::Column::Key))
+            .col(string_null(blob::Column::Filename))
+            .col(string_null(blob::Column::ContentType))
+            .col(big_integer(blob::Column::ByteSize))
+            .col(string_null(blob::Column::Checksum))
+            .col(
+                timestamp_with_time_zone(blob::Column::CreatedAt)
+                    .default(Expr::current_timestamp()),
+            )
+            .to_owned(),
+    )
+    .await?;
+    m.create_table(
+        Table::create()
+            .table(attachment::Entity)
+            .if_not_exists()
+            .col(pk_auto(attachment::Column::Id))
+            .col(string(attachment::Column::Name))
+            .col(string(attachment::Column::RecordType))
+            .col(string(attachment::Column::RecordId))
+            .col(integer(attachment::Column::BlobId))
+            .col(
+                timestamp_with_time_zone(attachment::Column::CreatedAt)
+                    .default(Expr::current_timestamp()),
+            )
+            .foreign_key(
+                sea_query::ForeignKey::create()
+                    .from(attachment::Entity, attachment::Column::BlobId)
+                    .to(blob::Entity, blob::Column::Id)
+                    .on_delete(ForeignKeyAction::Cascade),
+            )
+            .to_owned(),
+    )
+    .await?;
+    m.create_index(
+        Index::create()
+            .name("idx_loco_blobs_key")
+            .table(blob::Entity)
+            .col(blob::Column::Key)
+            .if_not_exists()
+            .to_owned(),
+    )
+    .await?;
+    m.create_index(
+        Index::create()
+            .name("idx_loco_blobs_checksum")
+            .table(blob::Entity)
+            .col(blob::Column::Checksum)
+            .if_not_exists()
+            .to_owned(),
+    )
+    .await?;
+    m.create_index(
+        Index::create()
+            .name("idx_loco_attachments_record")
+            .table(attachment::Entity)
+            .col(attachment::Column::RecordType)
+            .col(attachment::Column::RecordId)
+            .col(attachment::Column::Name)
+            .if_not_exists()
+            .to_owned(),
+    )
+    .await
+}
+
+///
+/// Drop the tables created by [`create_attachments_tables`]. The stored files
+/// are kept.
+/// ```ignore
+/// drop_attachments_tables(m).await;
+/// ```
+///
+/// # Errors
+/// fails when it fails
+pub async fn drop_attachments_tables(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    use crate::model::attachments::{attachment, blob};
+
+    m.drop_table(
+        Table::drop()
+            .table(attachment::Entity)
+            .if_exists()
+            .to_owned(),
+    )
+    .await?;
+    m.drop_table(Table::drop().table(blob::Entity).if_exists().to_owned())
+        .await
+}
+
+///
+/// Create the tables of the database cache driver
+/// ([`crate::cache::drivers::database`]), holding the entries, their tags
+/// and the shared hit and miss counters.
+/// ```ignore
+/// create_cache_tables(m).await;
+/// ```
+///
+/// # Errors
+/// fails when it fails
+pub async fn create_cache_tables(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    m.create_table(
+        Table::create()
+            .table(LocoCache::Table)
+            .if_not_exists()
+            .col(text(LocoCache::CacheKey).primary_key())
+            .col(blob(LocoCache::Value))
+            .col(big_integer_null(LocoCache::ExpiresAt))
+            .to_owned(),
+    )
+    .await?;
+    m.create_index(
+        Index::create()
+            .name("idx_loco_cache_expires_at")
+            .table(LocoCache::Table)
+            .col(LocoCache::ExpiresAt)
+            .if_not_exists()
+            .to_owned(),
+    )
+    .await?;
+    m.create_table(
+        Table::create()
+            .table(LocoCacheTags::Table)
+            .if_not_exists()
+            .col(text(LocoCacheTags::Tag))
+            .col(text(LocoCacheTags::CacheKey))
+            .primary_key(
+                Index::create()
+                    .col(LocoCacheTags::Tag)
+                    .col(LocoCacheTags::CacheKey),
+            )
+            .to_owned(),
+    )
+    .await?;
+    m.create_table(
+        Table::create()
+            .table(LocoCacheStats::Table)
+            .if_not_exists()
+            .col(text(LocoCacheStats::Name).primary_key())
+            .col(big_integer(LocoCacheStats::Count))
+            .to_owned(),
+    )
+    .await
+}
+
+///
+/// Drop the tables created by [`create_cache_tables`].
+/// ```ignore
+/// drop_cache_tables(m).await;
+/// ```
+///
+/// # Errors
+/// fails when it fails
+pub async fn drop_cache_tables(m: &SchemaManager<'_>) -> Result<(), DbErr> {
+    for table in [
+        LocoCacheStats::Table.into_iden(),
+        LocoCacheTags::Table.into_iden(),
+        LocoCache::Table.into_iden(),
+    ] {
+        m.drop_table(Table::drop().table(table).if_exists().to_owned())
+            .await?;
+    }
+    Ok(())
+}
+
 ///
 /// Add enum values to an existing enum type
 /// ```ignore
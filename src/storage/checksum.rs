@@ -0,0 +1,159 @@
+//! Checksums of the content written to a store, returned in
+//! [`UploadResponse`](super::drivers::UploadResponse) and used to verify
+//! uploads and to store content under its digest.
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use futures_util::StreamExt;
+use md5::Md5;
+use sha2::{Digest, Sha256};
+
+use super::stream::BytesStream;
+
+/// Checksums of a content, hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Checksums {
+    pub sha256: String,
+    pub md5: String,
+}
+
+impl Checksums {
+    /// Computes the checksums of a content.
+    #[must_use]
+    pub fn of(content: &[u8]) -> Self {
+        let mut hasher = Hasher::default();
+        hasher.update(content);
+        hasher.finalize()
+    }
+
+    /// Whether these checksums match the expected checksum, regardless of the
+    /// case of its hex encoding.
+    #[must_use]
+    pub fn matches(&self, expected: &Checksum) -> bool {
+        match expected {
+            Checksum::Sha256(sha256) => self.sha256.eq_ignore_ascii_case(sha256),
+            Checksum::Md5(md5) => self.md5.eq_ignore_ascii_case(md5),
+        }
+    }
+
+    /// Returns the checksum of the algorithm of the expected checksum.
+    #[must_use]
+    pub fn get(&self, expected: &Checksum) -> Checksum {
+        match expected {
+            Checksum::Sha256(_) => Checksum::Sha256(self.sha256.clone()),
+            Checksum::Md5(_) => Checksum::Md5(self.md5.clone()),
+        }
+    }
+}
+
+/// A checksum expected by the caller, hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Checksum {
+    Sha256(String),
+    Md5(String),
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sha256(sha256) => write!(f, "sha256:{sha256}"),
+            Self::Md5(md5) => write!(f, "md5:{md5}"),
+        }
+    }
+}
+
+/// Computes checksums incrementally.
+#[derive(Clone, Default)]
+pub(crate) struct Hasher {
+    sha256: Sha256,
+    md5: Md5,
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, content: &[u8]) {
+        self.sha256.update(content);
+        self.md5.update(content);
+    }
+
+    pub(crate) fn finalize(self) -> Checksums {
+        Checksums {
+            sha256: hex::encode(self.sha256.finalize()),
+            md5: hex::encode(self.md5.finalize()),
+        }
+    }
+}
+
+/// Checksums of the content read from a stream returned by [`track`].
+#[derive(Clone)]
+pub(crate) struct Tracker(Arc<Mutex<Hasher>>);
+
+impl Tracker {
+    /// Returns the checksums of the content read so far.
+    pub(crate) fn checksums(&self) -> Checksums {
+        self.0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .clone()
+            .finalize()
+    }
+}
+
+/// Returns a stream computing the checksums of the content read from
+/// `stream`.
+pub(crate) fn track(stream: BytesStream) -> (BytesStream, Tracker) {
+    let tracker = Tracker(Arc::default());
+    let hasher = tracker.clone();
+    let stream = stream.map(move |chunk| {
+        if let Ok(chunk) = &chunk {
+            hasher
+                .0
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .update(chunk);
+        }
+        chunk
+    });
+    (BytesStream::from_body_stream(stream), tracker)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+
+    #[test]
+    fn can_compute_checksums() {
+        let checksums = Checksums::of(b"Loco!");
+        assert_eq!(
+            checksums,
+            Checksums {
+                sha256: "2eb1870e3f1bb2c57a6054b1f7ab76838eccec12e41c000b959ccbfc813faf67"
+                    .to_string(),
+                md5: "0bd5b958a5e4e11611834b0e8eff652d".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn can_match_checksums() {
+        let checksums = Checksums::of(b"Loco!");
+        assert!(checksums.matches(&Checksum::Sha256(checksums.sha256.to_uppercase())));
+        assert!(checksums.matches(&Checksum::Md5(checksums.md5.clone())));
+        assert!(!checksums.matches(&Checksum::Md5(checksums.sha256.clone())));
+    }
+
+    #[tokio::test]
+    async fn can_track_stream() {
+        let chunks: Vec<std::io::Result<Bytes>> =
+            vec![Ok(Bytes::from("Lo")), Ok(Bytes::from("co!"))];
+        let (stream, tracker) = track(BytesStream::from_body_stream(futures_util::stream::iter(
+            chunks,
+        )));
+
+        assert_eq!(stream.collect().await.unwrap(), Bytes::from("Loco!"));
+        assert_eq!(tracker.checksums(), Checksums::of(b"Loco!"));
+    }
+}
@@ -0,0 +1,908 @@
+//! A store encrypting the content with AES-256-GCM before writing it to
+//! another store, and decrypting it when reading, so the inner store (such as
+//! S3) only ever holds ciphertext encrypted with the application's keys.
+//!
+//! The content is sealed in chunks of 64 KiB, each authenticated on its own,
+//! so uploads, downloads and range reads are streamed without holding the
+//! whole content in memory. Every object is sealed with its own subkey,
+//! derived with HKDF-SHA256 from the configured key and a random 256 bits
+//! salt stored in the header, so nonces never repeat under a key however
+//! many objects are written. The nonce of a chunk is made of the chunk index
+//! and a flag marking the last chunk, which detects reordered, dropped or
+//! truncated chunks.
+//!
+//! Stored objects start with a header naming the key they are encrypted
+//! with. The first configured key encrypts new content while all keys
+//! decrypt, so keys are rotated by adding a new key first in the list, then
+//! re-encrypting existing objects with [`Encrypted::rotate`] before removing
+//! the old key.
+//!
+//! Presigned requests are not supported since clients would download the
+//! ciphertext, the application's signed URLs serve decrypted content
+//! instead.
+use std::{
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    Aes256Gcm, Nonce,
+};
+use async_trait::async_trait;
+use base64::Engine;
+use bytes::{Bytes, BytesMut};
+use futures_util::{StreamExt, TryStreamExt};
+use hkdf::Hkdf;
+use sha2::{Digest, Sha256};
+
+use super::{GetResponse, ListStream, ObjectMetadata, StoreDriver, UploadResponse};
+use crate::storage::{checksum, stream::BytesStream, StorageError, StorageResult};
+
+const MAGIC: &[u8; 4] = b"LENC";
+const VERSION: u8 = 1;
+const TAG_LEN: usize = 8;
+const SALT_LEN: usize = 32;
+const HEADER_LEN: usize = MAGIC.len() + 1 + TAG_LEN + SALT_LEN;
+/// Context of the derivation of the per object subkeys.
+const SUBKEY_INFO: &[u8] = b"loco storage encryption v1";
+/// Size of the plaintext of a chunk, only the last chunk can be smaller.
+const CHUNK_LEN: usize = 64 * 1024;
+const AUTH_TAG_LEN: usize = 16;
+const SEALED_CHUNK_LEN: usize = CHUNK_LEN + AUTH_TAG_LEN;
+
+/// A 256 bits key encrypting the content of an [`Encrypted`] store.
+#[derive(Clone)]
+pub struct EncryptionKey {
+    id: String,
+    key: [u8; 32],
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EncryptionKey")
+            .field("id", &self.id)
+            .finish_non_exhaustive()
+    }
+}
+
+impl EncryptionKey {
+    /// Creates a key. The id is written in the stored objects to find the
+    /// key decrypting them, it must not change once content is encrypted.
+    #[must_use]
+    pub fn new(id: &str, key: [u8; 32]) -> Self {
+        Self {
+            id: id.to_string(),
+            key,
+        }
+    }
+
+    /// Creates a key from its base64 encoding.
+    ///
+    /// # Errors
+    ///
+    /// When the key is not valid base64 or is not 32 bytes long.
+    pub fn from_base64(id: &str, key: &str) -> StorageResult<Self> {
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(key.trim())
+            .map_err(|err| StorageError::Any(format!("encryption key `{id}`: {err}").into()))?;
+        let key = key.try_into().map_err(|_| {
+            StorageError::Any(format!("encryption key `{id}` must be 32 bytes long").into())
+        })?;
+        Ok(Self::new(id, key))
+    }
+
+    /// Generates a random key, base64 encoded.
+    #[must_use]
+    pub fn generate() -> String {
+        base64::engine::general_purpose::STANDARD.encode(rand::random::<[u8; 32]>())
+    }
+
+    /// Tag identifying the key in the header of the stored objects.
+    fn tag(&self) -> [u8; TAG_LEN] {
+        let digest = Sha256::digest(self.id.as_bytes());
+        let mut tag = [0; TAG_LEN];
+        tag.copy_from_slice(&digest[..TAG_LEN]);
+        tag
+    }
+}
+
+/// A configured key, deriving the subkeys sealing the objects.
+#[derive(Clone)]
+struct Cipher {
+    tag: [u8; TAG_LEN],
+    key: [u8; 32],
+}
+
+/// Header of a stored object, authenticated with every chunk.
+#[derive(Clone, Copy)]
+struct Header([u8; HEADER_LEN]);
+
+impl Header {
+    fn new(tag: [u8; TAG_LEN]) -> Self {
+        let mut header = [0; HEADER_LEN];
+        header[..MAGIC.len()].copy_from_slice(MAGIC);
+        header[MAGIC.len()] = VERSION;
+        header[MAGIC.len() + 1..MAGIC.len() + 1 + TAG_LEN].copy_from_slice(&tag);
+        header[HEADER_LEN - SALT_LEN..].copy_from_slice(&rand::random::<[u8; SALT_LEN]>());
+        Self(header)
+    }
+
+    fn parse(bytes: &[u8]) -> io::Result<Self> {
+        if bytes.len() < HEADER_LEN
+            || &bytes[..MAGIC.len()] != MAGIC
+            || bytes[MAGIC.len()] != VERSION
+        {
+            return Err(invalid_data("content is not encrypted by the store"));
+        }
+        let mut header = [0; HEADER_LEN];
+        header.copy_from_slice(&bytes[..HEADER_LEN]);
+        Ok(Self(header))
+    }
+
+    fn tag(&self) -> &[u8] {
+        &self.0[MAGIC.len() + 1..MAGIC.len() + 1 + TAG_LEN]
+    }
+
+    /// Subkey sealing the chunks of the object, derived from the key and
+    /// the salt of the object.
+    fn subkey(&self, cipher: &Cipher) -> io::Result<[u8; 32]> {
+        let mut subkey = [0; 32];
+        Hkdf::<Sha256>::new(Some(&self.0[HEADER_LEN - SALT_LEN..]), &cipher.key)
+            .expand(SUBKEY_INFO, &mut subkey)
+            .map_err(|_| invalid_data("could not derive the encryption key"))?;
+        Ok(subkey)
+    }
+
+    fn aead(&self, cipher: &Cipher) -> io::Result<Aes256Gcm> {
+        Ok(Aes256Gcm::new(&self.subkey(cipher)?.into()))
+    }
+}
+
+/// Nonce of a chunk, unique within an object. Since every object has its own
+/// subkey, nonces don't need to be random.
+fn nonce(index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0; 12];
+    nonce[7..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = u8::from(last);
+    nonce
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn seal(
+    aead: &Aes256Gcm,
+    header: &Header,
+    index: u32,
+    last: bool,
+    chunk: &[u8],
+) -> io::Result<Bytes> {
+    aead.encrypt(
+        Nonce::from_slice(&nonce(index, last)),
+        Payload {
+            msg: chunk,
+            aad: &header.0,
+        },
+    )
+    .map(Bytes::from)
+    .map_err(|_| invalid_data("could not encrypt the content"))
+}
+
+fn open(
+    aead: &Aes256Gcm,
+    header: &Header,
+    index: u32,
+    last: bool,
+    chunk: &[u8],
+) -> io::Result<Bytes> {
+    aead.decrypt(
+        Nonce::from_slice(&nonce(index, last)),
+        Payload {
+            msg: chunk,
+            aad: &header.0,
+        },
+    )
+    .map(Bytes::from)
+    .map_err(|_| invalid_data("content could not be decrypted, it was altered or truncated"))
+}
+
+fn next_index(index: u32) -> io::Result<u32> {
+    index
+        .checked_add(1)
+        .ok_or_else(|| invalid_data("content is too large to be encrypted"))
+}
+
+/// Number of chunks sealing content of the given size, empty content is
+/// sealed in one empty chunk.
+fn chunk_count(size: u64, chunk_len: usize) -> u64 {
+    let chunk_len = chunk_len as u64;
+    ((size + chunk_len - 1) / chunk_len).max(1)
+}
+
+/// Size of the plaintext of an object of the given stored size.
+fn plaintext_size(size: u64) -> Option<u64> {
+    let body = size.checked_sub(HEADER_LEN as u64)?;
+    body.checked_sub(chunk_count(body, SEALED_CHUNK_LEN) * AUTH_TAG_LEN as u64)
+}
+
+/// Size of the stored object of the given plaintext size.
+fn stored_size(size: u64) -> u64 {
+    HEADER_LEN as u64 + size + chunk_count(size, CHUNK_LEN) * AUTH_TAG_LEN as u64
+}
+
+/// Encrypts a stream, writing the header then the sealed chunks.
+struct Sealer {
+    stream: BytesStream,
+    aead: Aes256Gcm,
+    header: Header,
+    index: u32,
+    buffer: BytesMut,
+    started: bool,
+    done: bool,
+}
+
+impl Sealer {
+    fn new(stream: BytesStream, cipher: &Cipher) -> io::Result<Self> {
+        let header = Header::new(cipher.tag);
+        Ok(Self {
+            stream,
+            aead: header.aead(cipher)?,
+            header,
+            index: 0,
+            buffer: BytesMut::new(),
+            started: false,
+            done: false,
+        })
+    }
+
+    async fn next_chunk(&mut self) -> Option<io::Result<Bytes>> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            return Some(Ok(Bytes::copy_from_slice(&self.header.0)));
+        }
+        let result = self.seal_next().await;
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+
+    async fn seal_next(&mut self) -> io::Result<Option<Bytes>> {
+        // A chunk is only sealed once more content follows it, the last chunk
+        // is sealed differently when the stream ends
+        while self.buffer.len() <= CHUNK_LEN {
+            match self.stream.next().await {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk?),
+                None => {
+                    self.done = true;
+                    let chunk = self.buffer.split();
+                    return seal(&self.aead, &self.header, self.index, true, &chunk).map(Some);
+                }
+            }
+        }
+        let chunk = self.buffer.split_to(CHUNK_LEN);
+        let sealed = seal(&self.aead, &self.header, self.index, false, &chunk)?;
+        self.index = next_index(self.index)?;
+        Ok(Some(sealed))
+    }
+}
+
+/// Decrypts a stream of sealed chunks, starting at the chunk `index`.
+struct Opener {
+    stream: BytesStream,
+    keys: Vec<Cipher>,
+    aead: Option<(Aes256Gcm, Header)>,
+    index: u32,
+    /// Whether the stream ends with the last chunk of the object, or only
+    /// reads a range of its chunks.
+    ends_with_last: bool,
+    buffer: BytesMut,
+    done: bool,
+}
+
+impl Opener {
+    fn new(stream: BytesStream, keys: Vec<Cipher>) -> Self {
+        Self {
+            stream,
+            keys,
+            aead: None,
+            index: 0,
+            ends_with_last: true,
+            buffer: BytesMut::new(),
+            done: false,
+        }
+    }
+
+    fn with_header(
+        stream: BytesStream,
+        aead: Aes256Gcm,
+        header: Header,
+        index: u32,
+        ends_with_last: bool,
+    ) -> Self {
+        Self {
+            stream,
+            keys: vec![],
+            aead: Some((aead, header)),
+            index,
+            ends_with_last,
+            buffer: BytesMut::new(),
+            done: false,
+        }
+    }
+
+    async fn next_chunk(&mut self) -> Option<io::Result<Bytes>> {
+        if self.done {
+            return None;
+        }
+        let result = self.open_next().await;
+        if !matches!(result, Ok(Some(_))) {
+            self.done = true;
+        }
+        result.transpose()
+    }
+
+    async fn open_next(&mut self) -> io::Result<Option<Bytes>> {
+        if self.aead.is_none() {
+            while self.buffer.len() < HEADER_LEN {
+                match self.stream.next().await {
+                    Some(chunk) => self.buffer.extend_from_slice(&chunk?),
+                    None => return Err(invalid_data("content is not encrypted by the store")),
+                }
+            }
+            let header = Header::parse(&self.buffer.split_to(HEADER_LEN))?;
+            self.aead = Some((header.aead(find_cipher(&self.keys, &header)?)?, header));
+        }
+        let Some((aead, header)) = &self.aead else {
+            return Ok(None);
+        };
+
+        while self.buffer.len() <= SEALED_CHUNK_LEN {
+            match self.stream.next().await {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk?),
+                None if self.buffer.is_empty() => {
+                    return Err(invalid_data("encrypted content is truncated"));
+                }
+                None => {
+                    self.done = true;
+                    let chunk = self.buffer.split();
+                    let last = self.ends_with_last;
+                    return open(aead, header, self.index, last, &chunk).map(Some);
+                }
+            }
+        }
+        let chunk = self.buffer.split_to(SEALED_CHUNK_LEN);
+        let opened = open(aead, header, self.index, false, &chunk)?;
+        self.index = next_index(self.index)?;
+        Ok(Some(opened))
+    }
+}
+
+fn find_cipher<'a>(keys: &'a [Cipher], header: &Header) -> io::Result<&'a Cipher> {
+    keys.iter()
+        .find(|cipher| cipher.tag == header.tag())
+        .ok_or_else(|| invalid_data("content is encrypted with an unknown key"))
+}
+
+fn into_storage_error(err: io::Error) -> StorageError {
+    StorageError::Any(Box::new(err))
+}
+
+/// A store encrypting the content written to an inner store.
+pub struct Encrypted {
+    inner: Box<dyn StoreDriver>,
+    /// Keys by order of preference, the first one encrypts.
+    keys: Vec<Cipher>,
+}
+
+impl Encrypted {
+    /// Creates a store encrypting the content of `inner`. The first key
+    /// encrypts new content, all keys decrypt existing content.
+    ///
+    /// # Errors
+    ///
+    /// When no key is given, or two keys have the same id.
+    pub fn new(inner: Box<dyn StoreDriver>, keys: &[EncryptionKey]) -> StorageResult<Self> {
+        if keys.is_empty() {
+            return Err(StorageError::Any(
+                "an encrypted store requires at least one key".into(),
+            ));
+        }
+        let mut ciphers: Vec<Cipher> = Vec::with_capacity(keys.len());
+        for key in keys {
+            let tag = key.tag();
+            if ciphers.iter().any(|cipher| cipher.tag == tag) {
+                return Err(StorageError::Any(
+                    format!("encryption key `{}` is configured twice", key.id).into(),
+                ));
+            }
+            ciphers.push(Cipher { tag, key: key.key });
+        }
+        Ok(Self {
+            inner,
+            keys: ciphers,
+        })
+    }
+
+    /// Creates the store described by an encrypted store configuration.
+    ///
+    /// # Errors
+    ///
+    /// When the inner store could not be created, or the keys are invalid.
+    pub fn from_config(config: &crate::config::EncryptedStoreConfig) -> StorageResult<Self> {
+        let keys = config
+            .keys
+            .iter()
+            .map(|key| EncryptionKey::from_base64(&key.id, &key.key))
+            .collect::<StorageResult<Vec<_>>>()?;
+        Self::new(super::from_config(&config.store)?, &keys)
+    }
+
+    fn primary(&self) -> &Cipher {
+        &self.keys[0]
+    }
+
+    async fn header(&self, path: &Path) -> StorageResult<Header> {
+        let bytes = self
+            .inner
+            .get_range_stream(path, 0..HEADER_LEN as u64)
+            .await?
+            .collect()
+            .await
+            .map_err(into_storage_error)?;
+        Header::parse(&bytes).map_err(into_storage_error)
+    }
+
+    /// Re-encrypts with the first key the objects under `prefix` which are
+    /// encrypted with another key, so older keys can be removed from the
+    /// configuration. Returns the number of re-encrypted objects.
+    ///
+    /// Objects are re-encrypted into a temporary object then renamed over
+    /// the original one, rotation can be run again if interrupted.
+    ///
+    /// # Errors
+    ///
+    /// When listing the store fails or an object can't be re-encrypted.
+    pub async fn rotate(&self, prefix: &Path) -> StorageResult<u64> {
+        let mut entries = self.inner.list(prefix, true).await?;
+        let mut rotated = 0;
+        while let Some(entry) = entries.next().await {
+            let entry = entry?;
+            if entry.is_dir {
+                continue;
+            }
+            let header = self.header(&entry.path).await?;
+            if header.tag() == self.primary().tag {
+                continue;
+            }
+
+            let mut temp = entry.path.clone().into_os_string();
+            temp.push(format!(".{}.rotating", uuid::Uuid::new_v4()));
+            let temp = PathBuf::from(temp);
+            let content = self.get_stream(&entry.path).await?;
+            if let Err(err) = self.upload_stream(&temp, content).await {
+                if let Err(err) = self.inner.delete(&temp).await {
+                    tracing::warn!(path = %temp.display(), error = %err, "could not delete partial rotated object");
+                }
+                return Err(err);
+            }
+            self.inner.rename(&temp, &entry.path).await?;
+            rotated += 1;
+        }
+        Ok(rotated)
+    }
+}
+
+/// Creates a store encrypting the content of `inner`. The first key encrypts
+/// new content, all keys decrypt existing content.
+///
+/// # Examples
+///```
+/// use loco_rs::storage::drivers::{encrypted::{self, EncryptionKey}, mem};
+/// let key = EncryptionKey::from_base64("2024-06", &EncryptionKey::generate()).unwrap();
+/// let storage = encrypted::new(mem::new(), &[key]).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// When no key is given, or two keys have the same id.
+pub fn new(
+    inner: Box<dyn StoreDriver>,
+    keys: &[EncryptionKey],
+) -> StorageResult<Box<dyn StoreDriver>> {
+    Ok(Box::new(Encrypted::new(inner, keys)?))
+}
+
+/// Creates the store described by an encrypted store configuration.
+///
+/// # Errors
+///
+/// When the inner store could not be created, or the keys are invalid.
+pub fn from_config(
+    config: &crate::config::EncryptedStoreConfig,
+) -> StorageResult<Box<dyn StoreDriver>> {
+    Ok(Box::new(Encrypted::from_config(config)?))
+}
+
+#[async_trait]
+impl StoreDriver for Encrypted {
+    async fn upload(&self, path: &Path, content: &Bytes) -> StorageResult<UploadResponse> {
+        let content = content.clone();
+        let stream =
+            BytesStream::from_body_stream(futures_util::stream::once(async { Ok(content) }));
+        self.upload_stream(path, stream).await
+    }
+
+    async fn get(&self, path: &Path) -> StorageResult<GetResponse> {
+        let content = self
+            .get_stream(path)
+            .await?
+            .collect()
+            .await
+            .map_err(into_storage_error)?;
+        Ok(GetResponse::from_bytes(content))
+    }
+
+    async fn delete(&self, path: &Path) -> StorageResult<()> {
+        self.inner.delete(path).await
+    }
+
+    async fn rename(&self, from: &Path, to: &Path) -> StorageResult<()> {
+        self.inner.rename(from, to).await
+    }
+
+    async fn copy(&self, from: &Path, to: &Path) -> StorageResult<()> {
+        self.inner.copy(from, to).await
+    }
+
+    async fn exists(&self, path: &Path) -> StorageResult<bool> {
+        self.inner.exists(path).await
+    }
+
+    async fn get_stream(&self, path: &Path) -> StorageResult<BytesStream> {
+        let opener = Opener::new(self.inner.get_stream(path).await?, self.keys.clone());
+        Ok(BytesStream::from_body_stream(futures_util::stream::unfold(
+            opener,
+            |mut opener| async move { opener.next_chunk().await.map(|chunk| (chunk, opener)) },
+        )))
+    }
+
+    async fn get_range_stream(&self, path: &Path, range: Range<u64>) -> StorageResult<BytesStream> {
+        let size = self.head(path).await?.size;
+        let range = range.start.min(size)..range.end.min(size);
+        if range.is_empty() {
+            return Ok(BytesStream::from_body_stream(futures_util::stream::empty()));
+        }
+
+        let header = self.header(path).await?;
+        let aead = find_cipher(&self.keys, &header)
+            .and_then(|cipher| header.aead(cipher))
+            .map_err(into_storage_error)?;
+        let chunk_len = CHUNK_LEN as u64;
+        let first = range.start / chunk_len;
+        let last = (range.end - 1) / chunk_len;
+        let sealed = HEADER_LEN as u64 + first * SEALED_CHUNK_LEN as u64
+            ..(HEADER_LEN as u64 + (last + 1) * SEALED_CHUNK_LEN as u64).min(stored_size(size));
+
+        let index = u32::try_from(first)
+            .map_err(|_| into_storage_error(invalid_data("content is too large")))?;
+        let opener = Opener::with_header(
+            self.inner.get_range_stream(path, sealed).await?,
+            aead,
+            header,
+            index,
+            last + 1 == chunk_count(size, CHUNK_LEN),
+        );
+        let offset = first * chunk_len;
+        Ok(BytesStream::from_body_stream(futures_util::stream::unfold(
+            opener,
+            |mut opener| async move { opener.next_chunk().await.map(|chunk| (chunk, opener)) },
+        ))
+        .slice(range.start - offset..range.end - offset))
+    }
+
+    async fn upload_stream(
+        &self,
+        path: &Path,
+        stream: BytesStream,
+    ) -> StorageResult<UploadResponse> {
+        // checksums of the plaintext rather than of the stored ciphertext
+        let (stream, tracker) = checksum::track(stream);
+        let sealer = Sealer::new(stream, self.primary()).map_err(into_storage_error)?;
+        let sealed = futures_util::stream::unfold(sealer, |mut sealer| async move {
+            sealer.next_chunk().await.map(|chunk| (chunk, sealer))
+        });
+        let mut response = self
+            .inner
+            .upload_stream(path, BytesStream::from_body_stream(sealed))
+            .await?;
+        response.checksums = Some(tracker.checksums());
+        Ok(response)
+    }
+
+    async fn list(&self, prefix: &Path, recursive: bool) -> StorageResult<ListStream> {
+        let entries = self.inner.list(prefix, recursive).await?;
+        Ok(Box::pin(entries.map_ok(|mut entry| {
+            // Stores may not return the size when listing
+            if !entry.is_dir && entry.metadata.size > 0 {
+                entry.metadata.size = plaintext_size(entry.metadata.size).unwrap_or_default();
+            }
+            entry
+        })))
+    }
+
+    async fn head(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        let mut metadata = self.inner.head(path).await?;
+        metadata.size = plaintext_size(metadata.size).ok_or_else(|| {
+            into_storage_error(invalid_data("content is not encrypted by the store"))
+        })?;
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::storage::{checksum::Checksums, drivers::local};
+
+    fn key(id: &str, byte: u8) -> EncryptionKey {
+        EncryptionKey::new(id, [byte; 32])
+    }
+
+    fn content(len: usize) -> Bytes {
+        #[allow(clippy::cast_possible_truncation)]
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn chunked(content: &Bytes, size: usize) -> BytesStream {
+        let chunks: Vec<io::Result<Bytes>> = content
+            .chunks(size)
+            .map(|chunk| Ok(Bytes::copy_from_slice(chunk)))
+            .collect();
+        BytesStream::from_body_stream(futures_util::stream::iter(chunks))
+    }
+
+    fn stores(tree: &tree_fs::Tree, keys: &[EncryptionKey]) -> (Encrypted, Box<dyn StoreDriver>) {
+        (
+            Encrypted::new(local::new_with_prefix(&tree.root).unwrap(), keys).unwrap(),
+            local::new_with_prefix(&tree.root).unwrap(),
+        )
+    }
+
+    #[rstest::rstest]
+    #[case::empty(0)]
+    #[case::small(5)]
+    #[case::one_chunk(CHUNK_LEN)]
+    #[case::chunks(3 * CHUNK_LEN + 7)]
+    #[tokio::test]
+    async fn can_upload_and_get(#[case] len: usize) {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let (store, inner) = stores(&tree, &[key("k1", 1)]);
+        let content = content(len);
+        let path = Path::new("docs/file.bin");
+
+        let response = store.upload(path, &content).await.unwrap();
+        assert_eq!(response.checksums, Some(Checksums::of(&content)));
+
+        let stored = inner.get(path).await.unwrap().bytes().await.unwrap();
+        assert_eq!(stored.len() as u64, stored_size(len as u64));
+        assert!(len < 16 || !stored.windows(16).any(|w| w == &content[..16]));
+        assert_eq!(store.head(path).await.unwrap().size, len as u64);
+        assert_eq!(
+            store.get(path).await.unwrap().bytes().await.unwrap(),
+            content
+        );
+        assert_eq!(
+            store
+                .get_stream(path)
+                .await
+                .unwrap()
+                .collect()
+                .await
+                .unwrap(),
+            content
+        );
+    }
+
+    #[tokio::test]
+    async fn can_upload_stream() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let (store, _) = stores(&tree, &[key("k1", 1)]);
+        let content = content(2 * CHUNK_LEN + 100);
+        let path = Path::new("file.bin");
+
+        store
+            .upload_stream(path, chunked(&content, 1000))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.get(path).await.unwrap().bytes().await.unwrap(),
+            content
+        );
+    }
+
+    #[rstest::rstest]
+    #[case::start(0..10)]
+    #[case::inside_chunk(100..2000)]
+    #[case::across_chunks(CHUNK_LEN as u64 - 10..2 * CHUNK_LEN as u64 + 10)]
+    #[case::last_chunk(3 * CHUNK_LEN as u64..3 * CHUNK_LEN as u64 + 7)]
+    #[case::past_end(3 * CHUNK_LEN as u64 + 5..4 * CHUNK_LEN as u64)]
+    #[case::empty(10..10)]
+    #[tokio::test]
+    async fn can_get_range(#[case] range: Range<u64>) {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let (store, _) = stores(&tree, &[key("k1", 1)]);
+        let content = content(3 * CHUNK_LEN + 7);
+        let path = Path::new("file.bin");
+        store.upload(path, &content).await.unwrap();
+
+        let bytes = store
+            .get_range_stream(path, range.clone())
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+
+        #[allow(clippy::cast_possible_truncation)]
+        let expected = content.slice(
+            (range.start as usize).min(content.len())..(range.end as usize).min(content.len()),
+        );
+        assert_eq!(bytes, expected);
+    }
+
+    #[rstest::rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(CHUNK_LEN as u64)]
+    #[case(CHUNK_LEN as u64 + 1)]
+    #[case(5 * CHUNK_LEN as u64 - 1)]
+    fn computes_plaintext_size(#[case] size: u64) {
+        assert_eq!(plaintext_size(stored_size(size)), Some(size));
+        assert_eq!(plaintext_size(HEADER_LEN as u64 + 10), None);
+    }
+
+    #[tokio::test]
+    async fn detects_altered_content() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let (store, inner) = stores(&tree, &[key("k1", 1)]);
+        let path = Path::new("file.bin");
+        store.upload(path, &content(2 * CHUNK_LEN)).await.unwrap();
+        let stored = inner.get(path).await.unwrap().bytes().await.unwrap();
+
+        let mut altered = stored.to_vec();
+        altered[HEADER_LEN + 3] ^= 1;
+        inner.upload(path, &Bytes::from(altered)).await.unwrap();
+        assert!(store.get(path).await.is_err());
+
+        // dropping the last chunk
+        let truncated = stored.slice(..HEADER_LEN + SEALED_CHUNK_LEN);
+        inner.upload(path, &truncated).await.unwrap();
+        assert!(store.get(path).await.is_err());
+
+        inner
+            .upload(path, &Bytes::from("not encrypted"))
+            .await
+            .unwrap();
+        assert!(store.get(path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn seals_every_object_with_its_own_subkey() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let (store, inner) = stores(&tree, &[key("k1", 1)]);
+        let content = content(100);
+        let mut subkeys = std::collections::HashSet::new();
+        for i in 0..50 {
+            let path = PathBuf::from(format!("file-{i}.bin"));
+            store.upload(&path, &content).await.unwrap();
+            let stored = inner.get(&path).await.unwrap().bytes().await.unwrap();
+            let header = Header::parse(&stored).unwrap();
+            // chunk nonces only depend on the index, so distinct subkeys
+            // mean no two objects share a key and nonce
+            assert!(subkeys.insert(header.subkey(store.primary()).unwrap()));
+            assert_ne!(header.subkey(store.primary()).unwrap(), [1; 32]);
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_key() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let path = Path::new("file.bin");
+        let (store, _) = stores(&tree, &[key("k1", 1)]);
+        store.upload(path, &content(10)).await.unwrap();
+
+        let (other, _) = stores(&tree, &[key("k2", 2)]);
+        assert!(other.get(path).await.is_err());
+
+        // same id with another key
+        let (other, _) = stores(&tree, &[key("k1", 2)]);
+        assert!(other.get(path).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_rotate_keys() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let (old, inner) = stores(&tree, &[key("k1", 1)]);
+        let content = content(CHUNK_LEN + 10);
+        for path in ["a.bin", "docs/b.bin"] {
+            old.upload(Path::new(path), &content).await.unwrap();
+        }
+
+        let (store, _) = stores(&tree, &[key("k2", 2), key("k1", 1)]);
+        store.upload(Path::new("c.bin"), &content).await.unwrap();
+        assert_eq!(store.rotate(Path::new("")).await.unwrap(), 2);
+        assert_eq!(store.rotate(Path::new("")).await.unwrap(), 0);
+
+        let mut paths: Vec<String> = inner
+            .list(Path::new(""), true)
+            .await
+            .unwrap()
+            .map_ok(|entry| entry.path.display().to_string())
+            .try_collect()
+            .await
+            .unwrap();
+        paths.sort();
+        assert_eq!(paths, vec!["a.bin", "c.bin", "docs/b.bin"]);
+
+        let (new, _) = stores(&tree, &[key("k2", 2)]);
+        for path in ["a.bin", "docs/b.bin", "c.bin"] {
+            assert_eq!(
+                new.get(Path::new(path))
+                    .await
+                    .unwrap()
+                    .bytes()
+                    .await
+                    .unwrap(),
+                content
+            );
+        }
+    }
+
+    #[test]
+    fn validates_keys() {
+        assert!(Encrypted::new(crate::storage::drivers::mem::new(), &[]).is_err());
+        assert!(Encrypted::new(
+            crate::storage::drivers::mem::new(),
+            &[key("k1", 1), key("k1", 2)]
+        )
+        .is_err());
+        assert!(EncryptionKey::from_base64("k1", "c2hvcnQ=").is_err());
+        assert!(EncryptionKey::from_base64("k1", "not base64!").is_err());
+        assert!(EncryptionKey::from_base64("k1", &EncryptionKey::generate()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn can_create_from_config() {
+        let config: crate::config::StoreConfig = serde_yaml::from_str(&format!(
+            "kind: Encrypted\nstore:\n  kind: Mem\nkeys:\n  - id: k1\n    key: {}\n",
+            EncryptionKey::generate()
+        ))
+        .unwrap();
+        let store = super::super::from_config(&config).unwrap();
+
+        store
+            .upload(Path::new("file.txt"), &Bytes::from("Loco!"))
+            .await
+            .unwrap();
+        assert_eq!(
+            store
+                .get(Path::new("file.txt"))
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap(),
+            Bytes::from("Loco!")
+        );
+    }
+}
@@ -1,13 +1,23 @@
-use std::path::Path;
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+    pin::Pin,
+    time::Duration,
+};
 
 use async_trait::async_trait;
+use axum::http::{HeaderMap, Method};
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
 use opendal::Reader;
 
 #[cfg(feature = "storage_aws_s3")]
 pub mod aws;
 #[cfg(feature = "storage_azure")]
 pub mod azure;
+#[cfg(feature = "storage_encryption")]
+pub mod encrypted;
 #[cfg(feature = "storage_gcp")]
 pub mod gcp;
 pub mod local;
@@ -15,12 +25,127 @@ pub mod mem;
 pub mod null;
 pub mod opendal_adapter;
 
-use super::{stream::BytesStream, StorageResult};
+use super::{checksum::Checksums, stream::BytesStream, StorageError, StorageResult};
 
-#[derive(Debug)]
+/// Creates the store described by a store configuration.
+///
+/// # Errors
+///
+/// When the store could not be initialized, or an S3 endpoint is configured
+/// without credentials.
+pub fn from_config(config: &crate::config::StoreConfig) -> StorageResult<Box<dyn StoreDriver>> {
+    use crate::config::StoreConfig;
+
+    match config {
+        StoreConfig::Local(local) => match &local.root {
+            Some(root) => local::new_with_prefix(root),
+            None => Ok(local::new()),
+        },
+        StoreConfig::Mem => Ok(mem::new()),
+        #[cfg(feature = "storage_aws_s3")]
+        StoreConfig::S3(s3) => {
+            let credentials = s3.credentials.as_ref().map(|credentials| aws::Credential {
+                key_id: credentials.key_id.clone(),
+                secret_key: credentials.secret_key.clone(),
+                token: credentials.token.clone(),
+            });
+            match (&s3.endpoint, credentials) {
+                (Some(endpoint), Some(credentials)) => aws::with_credentials_and_endpoint(
+                    &s3.bucket,
+                    &s3.region,
+                    endpoint,
+                    credentials,
+                ),
+                (Some(_), None) => Err(StorageError::Any(
+                    "an S3 endpoint requires credentials".into(),
+                )),
+                (None, Some(credentials)) => {
+                    aws::with_credentials(&s3.bucket, &s3.region, credentials)
+                }
+                (None, None) => aws::new(&s3.bucket, &s3.region),
+            }
+        }
+        #[cfg(feature = "storage_gcp")]
+        StoreConfig::Gcs(gcs) => gcp::new(&gcs.bucket, &gcs.credential_path),
+        #[cfg(feature = "storage_azure")]
+        StoreConfig::Azure(azure) => azure::new(
+            &azure.container,
+            &azure.account_name,
+            &azure.access_key,
+            &azure.endpoint,
+        ),
+        #[cfg(feature = "storage_encryption")]
+        StoreConfig::Encrypted(encrypted) => encrypted::from_config(encrypted),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct UploadResponse {
     pub e_tag: Option<String>,
     pub version: Option<String>,
+    /// Checksums of the uploaded content, when computed by the store.
+    pub checksums: Option<Checksums>,
+}
+
+/// Metadata of an object in a store.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObjectMetadata {
+    /// Size of the content in bytes.
+    pub size: u64,
+    /// Content type, when the store records it.
+    pub content_type: Option<String>,
+    /// Entity tag of the content, when the store records it.
+    pub e_tag: Option<String>,
+    /// Time of the last modification, when the store records it.
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl From<&opendal::Metadata> for ObjectMetadata {
+    fn from(metadata: &opendal::Metadata) -> Self {
+        Self {
+            size: metadata.content_length(),
+            content_type: metadata.content_type().map(ToString::to_string),
+            e_tag: metadata.etag().map(ToString::to_string),
+            last_modified: metadata.last_modified(),
+        }
+    }
+}
+
+/// An object or directory returned by [`StoreDriver::list`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListEntry {
+    /// Path of the entry. Directories end with a `/`.
+    pub path: PathBuf,
+    /// Whether the entry is a directory.
+    pub is_dir: bool,
+    /// Metadata returned by the listing. Depending on the store, it may only
+    /// be complete after calling [`StoreDriver::head`].
+    pub metadata: ObjectMetadata,
+}
+
+/// A stream of the entries returned by [`StoreDriver::list`].
+pub type ListStream = Pin<Box<dyn Stream<Item = StorageResult<ListEntry>> + Send>>;
+
+/// A request which can be sent by a client, without credentials, to read or
+/// write an object until the request expires.
+#[derive(Debug, Clone)]
+pub struct PresignedRequest {
+    /// The HTTP method of the request.
+    pub method: Method,
+    /// The signed URL of the request.
+    pub uri: String,
+    /// Headers the client must send with the request.
+    pub headers: HeaderMap,
+}
+
+impl From<opendal::raw::PresignedRequest> for PresignedRequest {
+    fn from(request: opendal::raw::PresignedRequest) -> Self {
+        Self {
+            method: request.method().clone(),
+            uri: request.uri().to_string(),
+            headers: request.header().clone(),
+        }
+    }
 }
 
 /// TODO: Add more methods to `GetResponse` to read the content in different
@@ -28,12 +153,29 @@ pub struct UploadResponse {
 ///
 /// For example, we can read a specific range of bytes from the stream.
 pub struct GetResponse {
-    stream: Reader,
+    content: GetContent,
+}
+
+/// Content of a [`GetResponse`], read from the store or already in memory
+/// for drivers transforming the stored content.
+enum GetContent {
+    Reader(Reader),
+    #[cfg_attr(not(feature = "storage_encryption"), allow(dead_code))]
+    Bytes(Bytes),
 }
 
 impl GetResponse {
     pub(crate) fn new(stream: Reader) -> Self {
-        Self { stream }
+        Self {
+            content: GetContent::Reader(stream),
+        }
+    }
+
+    #[cfg_attr(not(feature = "storage_encryption"), allow(dead_code))]
+    pub(crate) fn from_bytes(bytes: Bytes) -> Self {
+        Self {
+            content: GetContent::Bytes(bytes),
+        }
     }
 
     /// Read all content from the stream and return as `Bytes`.
@@ -42,7 +184,10 @@ impl GetResponse {
     ///
     /// Returns a `StorageError` with the reason for the failure.
     pub async fn bytes(&self) -> StorageResult<Bytes> {
-        Ok(self.stream.read(..).await?.to_bytes())
+        match &self.content {
+            GetContent::Reader(reader) => Ok(reader.read(..).await?.to_bytes()),
+            GetContent::Bytes(bytes) => Ok(bytes.clone()),
+        }
     }
 
     /// Convert the response into a streaming bytes reader.
@@ -54,7 +199,12 @@ impl GetResponse {
     ///
     /// Returns a `StorageError` if the stream cannot be created.
     pub async fn into_stream(self) -> StorageResult<BytesStream> {
-        BytesStream::from_reader(self.stream).await
+        match self.content {
+            GetContent::Reader(reader) => BytesStream::from_reader(reader).await,
+            GetContent::Bytes(bytes) => Ok(BytesStream::from_body_stream(
+                futures_util::stream::once(async { Ok(bytes) }),
+            )),
+        }
     }
 }
 
@@ -125,6 +275,22 @@ pub trait StoreDriver: Sync + Send {
         response.into_stream().await
     }
 
+    /// Gets the given range of bytes of the content at the specified path as
+    /// a stream, such as for HTTP range requests.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation reads the content with `get_stream()` and
+    /// drops the bytes outside of the range. Storage drivers that support
+    /// range reads should override this method.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the streaming response.
+    async fn get_range_stream(&self, path: &Path, range: Range<u64>) -> StorageResult<BytesStream> {
+        Ok(self.get_stream(path).await?.slice(range))
+    }
+
     /// Uploads content from a stream to the specified path.
     /// This method is more memory-efficient than `upload()` for large files
     /// as it doesn't require loading the entire content into memory.
@@ -149,4 +315,81 @@ pub trait StoreDriver: Sync + Send {
             .map_err(|e| super::StorageError::Any(Box::new(e)))?;
         self.upload(path, &bytes).await
     }
+
+    /// Lists the entries under the given prefix, which is a directory path
+    /// such as `users/` (an empty path lists the whole store). When
+    /// `recursive` is set, the entries of all subdirectories are returned
+    /// instead of the subdirectories themselves.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation returns an error, stores supporting listing
+    /// must override it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the stream of entries. Errors while
+    /// listing are returned by the stream.
+    async fn list(&self, _prefix: &Path, _recursive: bool) -> StorageResult<ListStream> {
+        Err(StorageError::Any(
+            "listing is not supported by the store".into(),
+        ))
+    }
+
+    /// Returns the metadata of the content at the specified path.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation returns an error, stores supporting
+    /// metadata must override it.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the metadata, or an error if the content
+    /// doesn't exist.
+    async fn head(&self, _path: &Path) -> StorageResult<ObjectMetadata> {
+        Err(StorageError::Any(
+            "metadata is not supported by the store".into(),
+        ))
+    }
+
+    /// Creates a request which lets a client download the content at the
+    /// specified path directly from the store, until `expires` has passed.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation returns
+    /// [`StorageError::PresignUnsupported`], in which case
+    /// [`super::Storage`] falls back to its own signed URLs when configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the presigned request.
+    async fn presigned_get(
+        &self,
+        _path: &Path,
+        _expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        Err(StorageError::PresignUnsupported)
+    }
+
+    /// Creates a request which lets a client upload content to the specified
+    /// path directly into the store, until `expires` has passed.
+    ///
+    /// # Default Implementation
+    ///
+    /// The default implementation returns
+    /// [`StorageError::PresignUnsupported`], in which case
+    /// [`super::Storage`] falls back to its own signed URLs when configured.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the presigned request.
+    async fn presigned_put(
+        &self,
+        _path: &Path,
+        _expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        Err(StorageError::PresignUnsupported)
+    }
 }
@@ -9,7 +9,7 @@ use std::path::Path;
 use async_trait::async_trait;
 use bytes::Bytes;
 
-use super::{GetResponse, StorageResult, StoreDriver, UploadResponse};
+use super::{GetResponse, ListStream, ObjectMetadata, StorageResult, StoreDriver, UploadResponse};
 use crate::storage::StorageError;
 
 pub struct NullStorage {}
@@ -92,4 +92,26 @@ impl StoreDriver for NullStorage {
             "Operation not supported by null storage".into(),
         ))
     }
+
+    /// Lists the entries under the given prefix.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the stream of entries.
+    async fn list(&self, _prefix: &Path, _recursive: bool) -> StorageResult<ListStream> {
+        Err(StorageError::Any(
+            "Operation not supported by null storage".into(),
+        ))
+    }
+
+    /// Returns the metadata of the content at the specified path.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the metadata.
+    async fn head(&self, _path: &Path) -> StorageResult<ObjectMetadata> {
+        Err(StorageError::Any(
+            "Operation not supported by null storage".into(),
+        ))
+    }
 }
@@ -1,12 +1,19 @@
-use std::path::Path;
+use std::{ops::Range, path::Path, time::Duration};
 
 use async_trait::async_trait;
 use bytes::Bytes;
 use futures_util::{SinkExt, StreamExt};
 use opendal::{layers::RetryLayer, Operator};
 
-use super::{GetResponse, StoreDriver, UploadResponse};
-use crate::storage::{stream::BytesStream, StorageError, StorageResult};
+use super::{
+    GetResponse, ListEntry, ListStream, ObjectMetadata, PresignedRequest, StoreDriver,
+    UploadResponse,
+};
+use crate::storage::{
+    checksum::{Checksums, Hasher},
+    stream::BytesStream,
+    StorageError, StorageResult,
+};
 
 pub struct OpendalAdapter {
     opendal_impl: Operator,
@@ -39,6 +46,7 @@ impl StoreDriver for OpendalAdapter {
         Ok(UploadResponse {
             e_tag: None,
             version: None,
+            checksums: Some(Checksums::of(content)),
         })
     }
 
@@ -152,6 +160,15 @@ impl StoreDriver for OpendalAdapter {
         BytesStream::from_reader(reader).await
     }
 
+    /// Native range reads for `OpenDAL`, only fetching the requested bytes.
+    async fn get_range_stream(&self, path: &Path, range: Range<u64>) -> StorageResult<BytesStream> {
+        let reader = self
+            .opendal_impl
+            .reader(&path.display().to_string())
+            .await?;
+        BytesStream::from_reader_range(reader, range).await
+    }
+
     /// Native streaming upload for `OpenDAL`.
     /// This uses `OpenDAL`'s writer to stream data directly without buffering.
     async fn upload_stream(
@@ -166,8 +183,10 @@ impl StoreDriver for OpendalAdapter {
 
         // Stream data directly to the writer using native write method
         let mut stream = Box::pin(stream);
+        let mut hasher = Hasher::default();
         while let Some(chunk) = stream.next().await {
             let chunk = chunk.map_err(|e| StorageError::Any(Box::new(e)))?;
+            hasher.update(&chunk);
             // Use the native write method which handles the data more efficiently
             writer.write(chunk).await?;
         }
@@ -177,6 +196,159 @@ impl StoreDriver for OpendalAdapter {
         Ok(UploadResponse {
             e_tag: meta.etag().map(std::string::ToString::to_string),
             version: meta.version().map(std::string::ToString::to_string),
+            checksums: Some(hasher.finalize()),
         })
     }
+
+    /// Lists the entries under the prefix with `OpenDAL`'s lister, which
+    /// returns the metadata available in the listing of the service.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the stream of entries.
+    async fn list(&self, prefix: &Path, recursive: bool) -> StorageResult<ListStream> {
+        let mut prefix = prefix.display().to_string();
+        if !prefix.is_empty() && !prefix.ends_with('/') {
+            prefix.push('/');
+        }
+        let lister = self
+            .opendal_impl
+            .lister_with(&prefix)
+            .recursive(recursive)
+            .await?;
+
+        let listed = prefix.trim_start_matches('/').to_string();
+        let entries = lister.filter_map(move |entry| {
+            let entry = match entry {
+                // the listed directory itself, and the subdirectories whose
+                // entries are listed recursively
+                Ok(entry)
+                    if entry.path().trim_start_matches('/') == listed
+                        || (recursive && entry.metadata().is_dir()) =>
+                {
+                    None
+                }
+                Ok(entry) => Some(Ok(ListEntry {
+                    path: entry.path().into(),
+                    is_dir: entry.metadata().is_dir(),
+                    metadata: entry.metadata().into(),
+                })),
+                Err(err) => Some(Err(err.into())),
+            };
+            std::future::ready(entry)
+        });
+        Ok(Box::pin(entries))
+    }
+
+    /// Returns the metadata of the content from `OpenDAL`'s stat.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `StorageResult` with the metadata.
+    async fn head(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        let metadata = self.opendal_impl.stat(&path.display().to_string()).await?;
+        Ok((&metadata).into())
+    }
+
+    /// Presigns a read request when the service supports it, such as S3, GCS
+    /// and Azure Blob Storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::PresignUnsupported`] when the service cannot
+    /// presign requests.
+    async fn presigned_get(
+        &self,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        if !self.opendal_impl.info().full_capability().presign_read {
+            return Err(StorageError::PresignUnsupported);
+        }
+        let request = self
+            .opendal_impl
+            .presign_read(&path.display().to_string(), expires)
+            .await?;
+        Ok(request.into())
+    }
+
+    /// Presigns a write request when the service supports it, such as S3,
+    /// GCS and Azure Blob Storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StorageError::PresignUnsupported`] when the service cannot
+    /// presign requests.
+    async fn presigned_put(
+        &self,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        if !self.opendal_impl.info().full_capability().presign_write {
+            return Err(StorageError::PresignUnsupported);
+        }
+        let request = self
+            .opendal_impl
+            .presign_write(&path.display().to_string(), expires)
+            .await?;
+        Ok(request.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::TryStreamExt;
+
+    use super::*;
+    use crate::storage::drivers::{local, mem};
+
+    async fn list_paths(store: &dyn StoreDriver, prefix: &str, recursive: bool) -> Vec<String> {
+        let mut paths: Vec<String> = store
+            .list(Path::new(prefix), recursive)
+            .await
+            .unwrap()
+            .map_ok(|entry| entry.path.display().to_string())
+            .try_collect()
+            .await
+            .unwrap();
+        paths.sort();
+        paths
+    }
+
+    async fn can_list_and_head(store: &dyn StoreDriver) {
+        for path in ["users/1.txt", "users/avatars/1.png", "posts/1.txt"] {
+            store
+                .upload(Path::new(path), &Bytes::from("Loco!"))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            list_paths(store, "users", false).await,
+            vec!["users/1.txt", "users/avatars/"]
+        );
+        assert_eq!(
+            list_paths(store, "users/", true).await,
+            vec!["users/1.txt", "users/avatars/1.png"]
+        );
+
+        let metadata = store.head(Path::new("users/1.txt")).await.unwrap();
+        assert_eq!(metadata.size, 5);
+        assert!(store.head(Path::new("users/2.txt")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_list_and_head_mem() {
+        can_list_and_head(&*mem::new()).await;
+    }
+
+    #[tokio::test]
+    async fn can_list_and_head_local() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let store = local::new_with_prefix(&tree.root).unwrap();
+        can_list_and_head(&*store).await;
+
+        let metadata = store.head(Path::new("users/1.txt")).await.unwrap();
+        assert!(metadata.last_modified.is_some());
+    }
 }
@@ -0,0 +1,425 @@
+//! # Image Variants
+//!
+//! Derives resized and converted variants of the images stored in a
+//! [`Storage`], and stores them back under deterministic keys so each
+//! variant is only generated once:
+//!
+//! ```text
+//! products/42.jpg  ->  variants/products/42.jpg/200x200-cover.webp
+//! ```
+//!
+//! Variants are generated lazily with [`variant`] (or [`variant_url`]) the
+//! first time they are requested, or eagerly in the background with the
+//! [`ImageVariantsWorker`].
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::time::Duration;
+//!
+//! use loco_rs::{
+//!     prelude::*,
+//!     storage::images::{self, Fit, Format, Variant},
+//! };
+//!
+//! const THUMBNAIL: Variant = Variant::new(200, 200)
+//!     .fit(Fit::Cover)
+//!     .format(Format::WebP);
+//!
+//! async fn thumbnail(State(ctx): State<AppContext>, Path(id): Path<i32>) -> Result<Response> {
+//!     let image = format!("products/{id}.jpg");
+//!     let url = images::variant_url(
+//!         &ctx.storage,
+//!         std::path::Path::new(&image),
+//!         &THUMBNAIL,
+//!         Duration::from_secs(3600),
+//!     )
+//!     .await?;
+//!     format::redirect(&url.uri)
+//! }
+//! ```
+use std::{
+    io::Cursor,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use futures_util::StreamExt;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+
+use super::{drivers::PresignedRequest, Storage, StorageError, StorageResult};
+use crate::{app::AppContext, bgworker::BackgroundWorker};
+
+/// Prefix of the keys of the variants.
+pub const VARIANTS_PREFIX: &str = "variants";
+
+/// How an image is resized to the dimensions of a [`Variant`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Fit {
+    /// Keeps the aspect ratio, fitting the image within the dimensions.
+    #[default]
+    Contain,
+    /// Keeps the aspect ratio, filling the dimensions and cropping the
+    /// overflow from the center.
+    Cover,
+    /// Stretches the image to the exact dimensions.
+    Exact,
+}
+
+impl Fit {
+    const fn name(self) -> &'static str {
+        match self {
+            Self::Contain => "contain",
+            Self::Cover => "cover",
+            Self::Exact => "exact",
+        }
+    }
+}
+
+/// Format of a [`Variant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Format {
+    Png,
+    Jpeg,
+    /// Lossless `WebP`
+    WebP,
+    Gif,
+}
+
+impl Format {
+    const fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::WebP => "webp",
+            Self::Gif => "gif",
+        }
+    }
+
+    const fn image_format(self) -> ImageFormat {
+        match self {
+            Self::Png => ImageFormat::Png,
+            Self::Jpeg => ImageFormat::Jpeg,
+            Self::WebP => ImageFormat::WebP,
+            Self::Gif => ImageFormat::Gif,
+        }
+    }
+
+    fn from_path(path: &Path) -> Option<Self> {
+        match ImageFormat::from_path(path).ok()? {
+            ImageFormat::Png => Some(Self::Png),
+            ImageFormat::Jpeg => Some(Self::Jpeg),
+            ImageFormat::WebP => Some(Self::WebP),
+            ImageFormat::Gif => Some(Self::Gif),
+            _ => None,
+        }
+    }
+}
+
+/// Dimensions and format of an image variant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Variant {
+    pub width: u32,
+    pub height: u32,
+    pub fit: Fit,
+    /// Format of the variant, the format of the original image when not set
+    /// (or PNG when the original format can't be written).
+    pub format: Option<Format>,
+}
+
+impl Variant {
+    /// Creates a variant fitting within the given dimensions, in the format
+    /// of the original image.
+    #[must_use]
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            fit: Fit::Contain,
+            format: None,
+        }
+    }
+
+    /// Sets how the image is resized.
+    #[must_use]
+    pub const fn fit(mut self, fit: Fit) -> Self {
+        self.fit = fit;
+        self
+    }
+
+    /// Sets the format of the variant.
+    #[must_use]
+    pub const fn format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    fn format_for(&self, original: &Path) -> Format {
+        self.format
+            .or_else(|| Format::from_path(original))
+            .unwrap_or(Format::Png)
+    }
+
+    /// Resizes and encodes an image.
+    fn process(&self, content: &[u8], format: Format) -> StorageResult<Vec<u8>> {
+        let image = image::load_from_memory(content).map_err(image_error)?;
+        let image = match self.fit {
+            Fit::Contain => image.resize(self.width, self.height, FilterType::Lanczos3),
+            Fit::Cover => image.resize_to_fill(self.width, self.height, FilterType::Lanczos3),
+            Fit::Exact => image.resize_exact(self.width, self.height, FilterType::Lanczos3),
+        };
+        // JPEG has no alpha channel
+        let image = match format {
+            Format::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8()),
+            _ => image,
+        };
+        let mut encoded = Cursor::new(Vec::new());
+        image
+            .write_to(&mut encoded, format.image_format())
+            .map_err(image_error)?;
+        Ok(encoded.into_inner())
+    }
+}
+
+fn image_error(err: image::ImageError) -> StorageError {
+    StorageError::Any(Box::new(err))
+}
+
+/// Returns the key under which a variant of an image is stored.
+#[must_use]
+pub fn variant_key(original: &Path, variant: &Variant) -> PathBuf {
+    variants_prefix(original).join(format!(
+        "{}x{}-{}.{}",
+        variant.width,
+        variant.height,
+        variant.fit.name(),
+        variant.format_for(original).extension()
+    ))
+}
+
+fn variants_prefix(original: &Path) -> PathBuf {
+    Path::new(VARIANTS_PREFIX).join(original.strip_prefix("/").unwrap_or(original))
+}
+
+/// Returns the key of a variant of an image, generating and storing the
+/// variant when it doesn't exist yet.
+///
+/// # Errors
+///
+/// When the original image can't be read or decoded, or the variant can't
+/// be stored.
+pub async fn variant(
+    storage: &Storage,
+    original: &Path,
+    variant: &Variant,
+) -> StorageResult<PathBuf> {
+    let key = variant_key(original, variant);
+    match storage.head(&key).await {
+        Ok(_) => Ok(key),
+        Err(StorageError::Store(err)) if err.kind() == opendal::ErrorKind::NotFound => {
+            generate(storage, original, variant).await
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns a request downloading a variant of an image, generating the
+/// variant when it doesn't exist yet. See [`Storage::presigned_get`].
+///
+/// # Errors
+///
+/// When the variant can't be generated, or the storage can't presign
+/// requests.
+pub async fn variant_url(
+    storage: &Storage,
+    original: &Path,
+    variant: &Variant,
+    expires: Duration,
+) -> StorageResult<PresignedRequest> {
+    let key = self::variant(storage, original, variant).await?;
+    storage.presigned_get(&key, expires).await
+}
+
+/// Generates a variant of an image and stores it, replacing a previously
+/// stored one. Returns the key of the variant.
+///
+/// # Errors
+///
+/// When the original image can't be read or decoded, or the variant can't
+/// be stored.
+pub async fn generate(
+    storage: &Storage,
+    original: &Path,
+    variant: &Variant,
+) -> StorageResult<PathBuf> {
+    let content: Vec<u8> = storage.download(original).await?;
+    let format = variant.format_for(original);
+    let variant_spec = *variant;
+    let encoded = tokio::task::spawn_blocking(move || variant_spec.process(&content, format))
+        .await
+        .map_err(|err| StorageError::Any(Box::new(err)))??;
+    let key = variant_key(original, variant);
+    storage.upload(&key, &Bytes::from(encoded)).await?;
+    Ok(key)
+}
+
+/// Deletes the stored variants of an image, to call when the image is
+/// deleted or replaced.
+///
+/// # Errors
+///
+/// When the variants can't be listed or deleted.
+pub async fn purge_variants(storage: &Storage, original: &Path) -> StorageResult<()> {
+    let mut entries = storage.list(&variants_prefix(original), true).await?;
+    while let Some(entry) = entries.next().await {
+        storage.delete(&entry?.path).await?;
+    }
+    Ok(())
+}
+
+/// Arguments of the [`ImageVariantsWorker`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageVariantsArgs {
+    /// Key of the original image.
+    pub path: PathBuf,
+    pub variants: Vec<Variant>,
+}
+
+/// Worker generating the variants of an image ahead of their first request.
+///
+/// Register it in `Hooks::connect_workers`, then enqueue the variants once
+/// an image is stored:
+///
+/// ```rust,ignore
+/// queue.register(ImageVariantsWorker::build(ctx)).await?;
+///
+/// ImageVariantsWorker::perform_later(
+///     &ctx,
+///     ImageVariantsArgs { path: "products/42.jpg".into(), variants: vec![THUMBNAIL] },
+/// )
+/// .await?;
+/// ```
+pub struct ImageVariantsWorker {
+    pub ctx: AppContext,
+}
+
+#[async_trait]
+impl BackgroundWorker<ImageVariantsArgs> for ImageVariantsWorker {
+    fn build(ctx: &AppContext) -> Self {
+        Self { ctx: ctx.clone() }
+    }
+
+    async fn perform(&self, args: ImageVariantsArgs) -> crate::Result<()> {
+        for variant in &args.variants {
+            generate(&self.ctx.storage, &args.path, variant).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{GenericImageView, RgbaImage};
+
+    use super::*;
+    use crate::storage::drivers;
+
+    fn png(width: u32, height: u32) -> Bytes {
+        let mut encoded = Cursor::new(Vec::new());
+        DynamicImage::ImageRgba8(RgbaImage::new(width, height))
+            .write_to(&mut encoded, ImageFormat::Png)
+            .unwrap();
+        Bytes::from(encoded.into_inner())
+    }
+
+    async fn dimensions(storage: &Storage, key: &Path) -> (u32, u32) {
+        let content: Vec<u8> = storage.download(key).await.unwrap();
+        image::load_from_memory(&content).unwrap().dimensions()
+    }
+
+    #[rstest::rstest]
+    #[case(Fit::Contain, (100, 50))]
+    #[case(Fit::Cover, (100, 100))]
+    #[case(Fit::Exact, (100, 100))]
+    #[tokio::test]
+    async fn can_generate_variant(#[case] fit: Fit, #[case] expected: (u32, u32)) {
+        let storage = Storage::single(drivers::mem::new());
+        let original = Path::new("products/1.png");
+        storage.upload(original, &png(400, 200)).await.unwrap();
+
+        let key = variant(&storage, original, &Variant::new(100, 100).fit(fit))
+            .await
+            .unwrap();
+        assert_eq!(
+            key,
+            PathBuf::from(format!(
+                "variants/products/1.png/100x100-{}.png",
+                fit.name()
+            ))
+        );
+        assert_eq!(dimensions(&storage, &key).await, expected);
+    }
+
+    #[tokio::test]
+    async fn can_reuse_stored_variant() {
+        let storage = Storage::single(drivers::mem::new());
+        let original = Path::new("products/1.png");
+        storage.upload(original, &png(400, 200)).await.unwrap();
+        let thumbnail = Variant::new(40, 40).format(Format::Jpeg);
+
+        let key = variant(&storage, original, &thumbnail).await.unwrap();
+        assert_eq!(
+            key,
+            PathBuf::from("variants/products/1.png/40x40-contain.jpg")
+        );
+        let content: Vec<u8> = storage.download(&key).await.unwrap();
+        assert_eq!(image::guess_format(&content).unwrap(), ImageFormat::Jpeg);
+
+        // the stored variant is returned without reading the original
+        storage.delete(original).await.unwrap();
+        assert_eq!(variant(&storage, original, &thumbnail).await.unwrap(), key);
+        assert!(generate(&storage, original, &thumbnail).await.is_err());
+
+        purge_variants(&storage, original).await.unwrap();
+        assert!(storage.download::<Vec<u8>>(&key).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn can_generate_variants_in_worker() {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.storage = Storage::single(drivers::mem::new()).into();
+        let original = Path::new("products/1.png");
+        ctx.storage.upload(original, &png(400, 200)).await.unwrap();
+        let variants = vec![Variant::new(100, 100), Variant::new(20, 20).fit(Fit::Cover)];
+
+        ImageVariantsWorker::build(&ctx)
+            .perform(ImageVariantsArgs {
+                path: original.to_path_buf(),
+                variants: variants.clone(),
+            })
+            .await
+            .unwrap();
+        for (variant, expected) in variants.iter().zip([(100, 50), (20, 20)]) {
+            assert_eq!(
+                dimensions(&ctx.storage, &variant_key(original, variant)).await,
+                expected
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cant_generate_variant_of_invalid_image() {
+        let storage = Storage::single(drivers::mem::new());
+        let original = Path::new("notes.txt");
+        storage
+            .upload(original, &Bytes::from("not an image"))
+            .await
+            .unwrap();
+        assert!(variant(&storage, original, &Variant::new(10, 10))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,724 @@
+//! # Expiry of Stored Objects
+//!
+//! Deletes stored objects once they expire, such as temporary exports, from
+//! every store of the storage strategy since objects are deleted with
+//! [`Storage::delete`].
+//!
+//! Objects expire either:
+//! - individually, when uploaded with [`Storage::upload_with_expiry`] or
+//!   given an expiry with [`Storage::set_expiry`]. The expiry is recorded in
+//!   a marker object under `.expiry/`, next to the object, so it works with
+//!   every store, and hidden from listings. The marker follows the object
+//!   when it's renamed and is removed when it's deleted. It records the
+//!   version of the object it was set on, so an object uploaded again at the
+//!   same path doesn't inherit the expiry of the previous one.
+//! - by prefix, with the [`LifecycleRule`]s of the `storage.lifecycle`
+//!   configuration, once they are older than the expiry of the rule.
+//!
+//! Expired objects are deleted by [`purge_expired`], or by the
+//! [`PurgeExpired`] task, which can run periodically from the scheduler:
+//!
+//! ```yaml
+//! scheduler:
+//!   jobs:
+//!     purge_expired_storage:
+//!       run: "storage:purge_expired"
+//!       schedule: every 1 hour
+//! ```
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    checksum::Hasher, drivers::UploadResponse, is_not_found, strategies::StorageStrategy, Storage,
+    StorageError, StorageResult,
+};
+use crate::{
+    app::AppContext,
+    config::LifecycleRule,
+    task::{Task, TaskInfo, Vars},
+};
+
+/// Directory of the markers recording the expiry of objects.
+const MARKERS_PREFIX: &str = ".expiry";
+
+fn marker_path(path: &Path) -> PathBuf {
+    Path::new(MARKERS_PREFIX).join(path)
+}
+
+/// Whether the path is the expiry marker of an object, which listings and
+/// served routes hide.
+pub(crate) fn is_marker(path: &Path) -> bool {
+    path.starts_with(MARKERS_PREFIX)
+}
+
+const UNKNOWN: u8 = 0;
+const UNUSED: u8 = 1;
+const IN_USE: u8 = 2;
+
+/// Whether objects of a storage may have an expiry, so deleting and renaming
+/// objects only look for their marker when expiries are in use.
+///
+/// It's checked once by listing the markers, then set by
+/// [`Storage::set_expiry`]. An expiry set later by another process is missed,
+/// the version recorded in the marker still keeps it from applying to an
+/// object uploaded again at the same path.
+#[derive(Debug, Default)]
+pub(super) struct ExpiryMarkers(AtomicU8);
+
+impl ExpiryMarkers {
+    async fn in_use(
+        &self,
+        storage: &Storage,
+        strategy: &dyn StorageStrategy,
+    ) -> StorageResult<bool> {
+        match self.0.load(Ordering::Acquire) {
+            UNKNOWN => {}
+            state => return Ok(state == IN_USE),
+        }
+        let found = match strategy
+            .list(storage, Path::new(&format!("{MARKERS_PREFIX}/")), false)
+            .await
+        {
+            Ok(mut markers) => markers.try_next().await?.is_some(),
+            Err(err) if is_not_found(&err) => false,
+            Err(err) => return Err(err),
+        };
+        let state = if found { IN_USE } else { UNUSED };
+        // an expiry set during the listing wins
+        Ok(
+            match self
+                .0
+                .compare_exchange(UNKNOWN, state, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => found,
+                Err(current) => current == IN_USE,
+            },
+        )
+    }
+
+    fn set_in_use(&self) {
+        self.0.store(IN_USE, Ordering::Release);
+    }
+}
+
+/// Version of an object, recorded with its expiry so an object uploaded again
+/// at the same path doesn't inherit the expiry of the previous one.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Version {
+    size: u64,
+    e_tag: Option<String>,
+    last_modified: Option<DateTime<Utc>>,
+    /// Digest of the content, only computed for stores which return neither
+    /// an etag nor a modification time.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sha256: Option<String>,
+}
+
+impl Version {
+    async fn of(storage: &Storage, path: &Path) -> StorageResult<Self> {
+        let metadata = storage.head(path).await?;
+        let sha256 = if metadata.e_tag.is_none() && metadata.last_modified.is_none() {
+            let mut stream = storage.download_stream(path).await?;
+            let mut hasher = Hasher::default();
+            while let Some(chunk) = stream
+                .try_next()
+                .await
+                .map_err(|err| StorageError::Any(Box::new(err)))?
+            {
+                hasher.update(&chunk);
+            }
+            Some(hasher.finalize().sha256)
+        } else {
+            None
+        };
+        Ok(Self {
+            size: metadata.size,
+            e_tag: metadata.e_tag,
+            last_modified: metadata.last_modified,
+            sha256,
+        })
+    }
+}
+
+/// Content of an expiry marker.
+#[derive(Debug, Serialize, Deserialize)]
+struct Marker {
+    expires_at: DateTime<Utc>,
+    #[serde(flatten)]
+    version: Version,
+}
+
+impl Marker {
+    fn encode(&self) -> StorageResult<Bytes> {
+        serde_json::to_vec(self)
+            .map(Bytes::from)
+            .map_err(|err| StorageError::Any(err.into()))
+    }
+
+    async fn read(
+        storage: &Storage,
+        path: &Path,
+        strategy: &dyn StorageStrategy,
+    ) -> StorageResult<Option<Self>> {
+        let content = match storage
+            .download_with_policy::<Vec<u8>>(&marker_path(path), strategy)
+            .await
+        {
+            Ok(content) => content,
+            Err(err) if is_not_found(&err) => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        serde_json::from_slice(&content)
+            .map(Some)
+            .map_err(|err| StorageError::Any(err.into()))
+    }
+}
+
+/// Deletes the expiry marker of a deleted object.
+pub(super) async fn clear_expiry(
+    storage: &Storage,
+    path: &Path,
+    strategy: &dyn StorageStrategy,
+) -> StorageResult<()> {
+    if is_marker(path) || !storage.expiry.in_use(storage, strategy).await? {
+        return Ok(());
+    }
+    match strategy.delete(storage, &marker_path(path)).await {
+        Err(err) if !is_not_found(&err) => Err(err),
+        _ => Ok(()),
+    }
+}
+
+/// Expiry of an object about to be renamed, when it still applies to the
+/// object and must follow it to its new path.
+pub(super) async fn expiry_to_move(
+    storage: &Storage,
+    from: &Path,
+    strategy: &dyn StorageStrategy,
+) -> StorageResult<Option<DateTime<Utc>>> {
+    if is_marker(from) || !storage.expiry.in_use(storage, strategy).await? {
+        return Ok(None);
+    }
+    let Some(marker) = Marker::read(storage, from, strategy).await? else {
+        return Ok(None);
+    };
+    let current = Version::of(storage, from).await?;
+    Ok((marker.version == current).then_some(marker.expires_at))
+}
+
+/// Moves the expiry marker of a renamed object to its new path.
+pub(super) async fn move_expiry(
+    storage: &Storage,
+    from: &Path,
+    to: &Path,
+    expires_at: Option<DateTime<Utc>>,
+    strategy: &dyn StorageStrategy,
+) -> StorageResult<()> {
+    if let Some(expires_at) = expires_at {
+        // the store may give the renamed object a new etag or modification
+        // time
+        let marker = Marker {
+            expires_at,
+            version: Version::of(storage, to).await?,
+        };
+        strategy
+            .upload(storage, &marker_path(to), &marker.encode()?)
+            .await?;
+    }
+    clear_expiry(storage, from, strategy).await
+}
+
+impl Storage {
+    /// Sets the time at which the object at the path expires, after which
+    /// it's deleted by [`purge_expired`]. The expiry applies to the current
+    /// version of the object: it's ignored once the object is replaced.
+    ///
+    /// # Errors
+    ///
+    /// When the object doesn't exist, or the expiry could not be stored.
+    pub async fn set_expiry(&self, path: &Path, expires_at: DateTime<Utc>) -> StorageResult<()> {
+        let marker = Marker {
+            expires_at,
+            version: Version::of(self, path).await?,
+        };
+        self.expiry.set_in_use();
+        self.upload(&marker_path(path), &marker.encode()?).await?;
+        Ok(())
+    }
+
+    /// Uploads content which expires after the given duration, after which
+    /// it's deleted by [`purge_expired`].
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::{path::Path, time::Duration};
+    /// use bytes::Bytes;
+    /// pub async fn export(content: Bytes) {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("tmp/exports/users.csv");
+    ///     storage
+    ///         .upload_with_expiry(path, &content, Duration::from_secs(24 * 3600))
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// When the content or its expiry could not be stored.
+    pub async fn upload_with_expiry(
+        &self,
+        path: &Path,
+        content: &Bytes,
+        expires_in: Duration,
+    ) -> StorageResult<UploadResponse> {
+        let expires_in =
+            chrono::Duration::from_std(expires_in).map_err(|err| StorageError::Any(err.into()))?;
+        let response = self.upload(path, content).await?;
+        self.set_expiry(path, Utc::now() + expires_in).await?;
+        Ok(response)
+    }
+}
+
+/// Result of [`purge_expired`].
+#[derive(Debug, Default, Serialize)]
+pub struct PurgeReport {
+    /// Deleted objects.
+    pub deleted: Vec<PathBuf>,
+    /// Objects which could not be deleted, with the reason.
+    pub failed: BTreeMap<PathBuf, String>,
+}
+
+impl PurgeReport {
+    fn delete_result(&mut self, path: &Path, result: StorageResult<()>) {
+        match result {
+            Ok(()) => self.deleted.push(path.to_path_buf()),
+            Err(err) => {
+                self.failed.insert(path.to_path_buf(), err.to_string());
+            }
+        }
+    }
+}
+
+/// Deletes the objects whose expiry has passed, and the objects matching the
+/// lifecycle rules which are older than the expiry of their rule.
+///
+/// A failure to delete an object doesn't stop the purge, failed objects are
+/// returned in [`PurgeReport::failed`] and retried on the next purge.
+///
+/// # Errors
+///
+/// When the objects could not be listed.
+pub async fn purge_expired(
+    storage: &Storage,
+    rules: &[LifecycleRule],
+) -> StorageResult<PurgeReport> {
+    purge_expired_at(storage, rules, Utc::now()).await
+}
+
+async fn purge_expired_at(
+    storage: &Storage,
+    rules: &[LifecycleRule],
+    now: DateTime<Utc>,
+) -> StorageResult<PurgeReport> {
+    let mut report = PurgeReport::default();
+    purge_markers(storage, now, &mut report).await?;
+    for rule in rules {
+        purge_rule(storage, rule, now, &mut report).await?;
+    }
+    Ok(report)
+}
+
+async fn purge_markers(
+    storage: &Storage,
+    now: DateTime<Utc>,
+    report: &mut PurgeReport,
+) -> StorageResult<()> {
+    let markers: Vec<PathBuf> = storage
+        .list(Path::new(&format!("{MARKERS_PREFIX}/")), true)
+        .await?
+        .try_filter_map(|entry| async move { Ok((!entry.is_dir).then_some(entry.path)) })
+        .try_collect()
+        .await?;
+
+    for marker in markers {
+        let Ok(path) = marker.strip_prefix(MARKERS_PREFIX) else {
+            continue;
+        };
+        let content = match storage.download::<Vec<u8>>(&marker).await {
+            Ok(content) => content,
+            Err(err) => {
+                report.failed.insert(path.to_path_buf(), err.to_string());
+                continue;
+            }
+        };
+        let expiry = match serde_json::from_slice::<Marker>(&content) {
+            Ok(expiry) if expiry.expires_at > now => continue,
+            Ok(expiry) => expiry,
+            Err(err) => {
+                tracing::warn!(marker = %marker.display(), error = %err, "invalid expiry marker");
+                continue;
+            }
+        };
+
+        let current = match Version::of(storage, path).await {
+            Ok(version) => Some(version),
+            Err(err) if is_not_found(&err) => None,
+            Err(err) => {
+                report.failed.insert(path.to_path_buf(), err.to_string());
+                continue;
+            }
+        };
+        match current {
+            // deleting the object deletes its marker
+            Some(version) if version == expiry.version => {
+                let result = storage.delete(path).await;
+                report.delete_result(path, result);
+            }
+            _ => {
+                // the object was deleted or replaced since its expiry was set
+                if let Err(err) = storage.delete(&marker).await {
+                    report.failed.insert(path.to_path_buf(), err.to_string());
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn purge_rule(
+    storage: &Storage,
+    rule: &LifecycleRule,
+    now: DateTime<Utc>,
+    report: &mut PurgeReport,
+) -> StorageResult<()> {
+    let Some(expire_after) = i64::try_from(rule.expire_after)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+    else {
+        // too far away to ever expire
+        return Ok(());
+    };
+    let entries: Vec<_> = storage
+        .list(Path::new(&rule.prefix), true)
+        .await?
+        .try_filter(|entry| futures_util::future::ready(!entry.is_dir))
+        .try_collect()
+        .await?;
+
+    for entry in entries {
+        // listings don't return the modification time for every store
+        let last_modified = match entry.metadata.last_modified {
+            Some(last_modified) => Some(last_modified),
+            None => match storage.head(&entry.path).await {
+                Ok(metadata) => metadata.last_modified,
+                Err(err) => {
+                    report.failed.insert(entry.path, err.to_string());
+                    continue;
+                }
+            },
+        };
+        let Some(last_modified) = last_modified else {
+            tracing::warn!(path = %entry.path.display(), "the store doesn't return the modification time, the object can't expire");
+            continue;
+        };
+        if last_modified
+            .checked_add_signed(expire_after)
+            .is_some_and(|expires_at| expires_at <= now)
+        {
+            let result = storage.delete(&entry.path).await;
+            report.delete_result(&entry.path, result);
+        }
+    }
+    Ok(())
+}
+
+/// Task deleting the expired objects of `ctx.storage`, with the lifecycle
+/// rules of the storage configuration. Register it in `register_tasks`:
+///
+/// ```rust,ignore
+/// fn register_tasks(tasks: &mut Tasks) {
+///     tasks.register(loco_rs::storage::lifecycle::PurgeExpired);
+/// }
+/// ```
+pub struct PurgeExpired;
+
+#[async_trait]
+impl Task for PurgeExpired {
+    fn task(&self) -> TaskInfo {
+        TaskInfo {
+            name: "storage:purge_expired".to_string(),
+            detail: "Delete the expired objects of the storage".to_string(),
+        }
+    }
+
+    async fn run(&self, app_context: &AppContext, _vars: &Vars) -> crate::Result<()> {
+        let rules = app_context
+            .config
+            .storage
+            .as_ref()
+            .map(|storage| storage.lifecycle.as_slice())
+            .unwrap_or_default();
+        let report = purge_expired(&app_context.storage, rules).await?;
+        for (path, err) in &report.failed {
+            tracing::error!(path = %path.display(), error = %err, "could not delete expired object");
+        }
+        tracing::info!(
+            deleted = report.deleted.len(),
+            failed = report.failed.len(),
+            "purged expired storage objects"
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{
+        drivers,
+        strategies::mirror::{FailureMode, MirrorStrategy},
+    };
+
+    async fn exists(storage: &Storage, path: &str) -> bool {
+        match storage.head(Path::new(path)).await {
+            Ok(_) => true,
+            Err(err) if is_not_found(&err) => false,
+            Err(err) => panic!("{err}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn can_purge_expired_objects() {
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("primary".to_string(), drivers::mem::new()),
+                ("mirror".to_string(), drivers::mem::new()),
+            ]),
+            Box::new(MirrorStrategy::new(
+                "primary",
+                Some(vec!["mirror".to_string()]),
+                FailureMode::MirrorAll,
+            )),
+        );
+        let content = Bytes::from("Loco!");
+        storage
+            .upload_with_expiry(
+                Path::new("tmp/short.csv"),
+                &content,
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        storage
+            .upload_with_expiry(
+                Path::new("tmp/long.csv"),
+                &content,
+                Duration::from_secs(3600),
+            )
+            .await
+            .unwrap();
+        storage
+            .upload(Path::new("tmp/kept.csv"), &content)
+            .await
+            .unwrap();
+        // deleted before it expires
+        storage
+            .upload_with_expiry(Path::new("gone.csv"), &content, Duration::from_secs(60))
+            .await
+            .unwrap();
+        storage.delete(Path::new("gone.csv")).await.unwrap();
+        assert!(!exists(&storage, ".expiry/gone.csv").await);
+
+        let report = purge_expired_at(&storage, &[], Utc::now() + chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        assert_eq!(report.deleted, vec![PathBuf::from("tmp/short.csv")]);
+        assert!(report.failed.is_empty());
+
+        assert!(!exists(&storage, "tmp/short.csv").await);
+        assert!(!exists(&storage, ".expiry/tmp/short.csv").await);
+        assert!(exists(&storage, "tmp/long.csv").await);
+        assert!(exists(&storage, "tmp/kept.csv").await);
+        let mirror = storage.as_store("mirror").unwrap();
+        assert!(!mirror.exists(Path::new("tmp/short.csv")).await.unwrap());
+        assert!(mirror.exists(Path::new("tmp/long.csv")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn keeps_objects_replaced_after_their_expiry_was_set() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let storage = Storage::single(drivers::local::new_with_prefix(&tree.root).unwrap());
+        let path = Path::new("report.csv");
+        storage
+            .upload_with_expiry(path, &Bytes::from("temporary"), Duration::from_secs(60))
+            .await
+            .unwrap();
+        // uploaded again without expiry, the marker is left behind
+        storage
+            .upload(path, &Bytes::from("permanent report"))
+            .await
+            .unwrap();
+        assert!(exists(&storage, ".expiry/report.csv").await);
+
+        let report = purge_expired_at(&storage, &[], Utc::now() + chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        assert!(report.deleted.is_empty());
+        assert!(report.failed.is_empty());
+        assert!(exists(&storage, "report.csv").await);
+        assert!(!exists(&storage, ".expiry/report.csv").await);
+    }
+
+    #[tokio::test]
+    async fn keeps_objects_of_the_same_size_replaced_in_stores_without_versions() {
+        // the mem store returns neither an etag nor a modification time
+        let storage = Storage::single(drivers::mem::new());
+        let path = Path::new("report.csv");
+        storage
+            .upload_with_expiry(path, &Bytes::from("draft"), Duration::from_secs(60))
+            .await
+            .unwrap();
+        storage.upload(path, &Bytes::from("final")).await.unwrap();
+
+        let report = purge_expired_at(&storage, &[], Utc::now() + chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        assert!(report.deleted.is_empty());
+        assert!(exists(&storage, "report.csv").await);
+        assert!(!exists(&storage, ".expiry/report.csv").await);
+    }
+
+    #[tokio::test]
+    async fn hides_markers_from_listings() {
+        let storage = Storage::single(drivers::mem::new());
+        storage
+            .upload_with_expiry(
+                Path::new("tmp/export.csv"),
+                &Bytes::from("Loco!"),
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        let paths: Vec<PathBuf> = storage
+            .list(Path::new(""), true)
+            .await
+            .unwrap()
+            .try_filter_map(|entry| async move { Ok((!entry.is_dir).then_some(entry.path)) })
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(paths, vec![PathBuf::from("tmp/export.csv")]);
+    }
+
+    #[tokio::test]
+    async fn moves_expiry_with_renamed_objects() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let storage = Storage::single(drivers::local::new_with_prefix(&tree.root).unwrap());
+        storage
+            .upload_with_expiry(
+                Path::new("tmp/export.csv"),
+                &Bytes::from("Loco!"),
+                Duration::from_secs(60),
+            )
+            .await
+            .unwrap();
+        storage
+            .rename(Path::new("tmp/export.csv"), Path::new("exports/users.csv"))
+            .await
+            .unwrap();
+        assert!(!exists(&storage, ".expiry/tmp/export.csv").await);
+        assert!(exists(&storage, ".expiry/exports/users.csv").await);
+
+        let report = purge_expired_at(&storage, &[], Utc::now() + chrono::Duration::minutes(5))
+            .await
+            .unwrap();
+        assert_eq!(report.deleted, vec![PathBuf::from("exports/users.csv")]);
+    }
+
+    #[tokio::test]
+    async fn clears_expiry_set_by_another_storage() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let path = Path::new("report.csv");
+        let storage = Storage::single(drivers::local::new_with_prefix(&tree.root).unwrap());
+        storage.upload(path, &Bytes::from("Loco!")).await.unwrap();
+        // as set by another process sharing the store
+        Storage::single(drivers::local::new_with_prefix(&tree.root).unwrap())
+            .set_expiry(path, Utc::now() + chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+
+        storage.delete(path).await.unwrap();
+        assert!(!exists(&storage, ".expiry/report.csv").await);
+    }
+
+    #[tokio::test]
+    async fn can_purge_by_rule() {
+        let tree = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let storage = Storage::single(drivers::local::new_with_prefix(&tree.root).unwrap());
+        let content = Bytes::from("Loco!");
+        for path in ["tmp/exports/1.csv", "tmp/exports/2024/2.csv", "users/1.png"] {
+            storage.upload(Path::new(path), &content).await.unwrap();
+        }
+        storage
+            .set_expiry(
+                Path::new("users/1.png"),
+                Utc::now() + chrono::Duration::days(30),
+            )
+            .await
+            .unwrap();
+        let rules = [LifecycleRule {
+            prefix: "tmp/exports/".to_string(),
+            expire_after: 24 * 3600,
+        }];
+
+        let report = purge_expired_at(&storage, &rules, Utc::now())
+            .await
+            .unwrap();
+        assert!(report.deleted.is_empty());
+
+        let report = purge_expired_at(&storage, &rules, Utc::now() + chrono::Duration::days(2))
+            .await
+            .unwrap();
+        let mut deleted = report.deleted.clone();
+        deleted.sort();
+        assert_eq!(
+            deleted,
+            vec![
+                PathBuf::from("tmp/exports/1.csv"),
+                PathBuf::from("tmp/exports/2024/2.csv")
+            ]
+        );
+        assert!(exists(&storage, "users/1.png").await);
+        assert!(exists(&storage, ".expiry/users/1.png").await);
+    }
+
+    #[test]
+    fn can_load_rules_from_config() {
+        let config: crate::config::StorageConfig = serde_yaml::from_str(
+            r"
+stores:
+  files:
+    kind: Mem
+lifecycle:
+  - prefix: tmp/exports/
+    expire_after: 86400
+",
+        )
+        .unwrap();
+        assert_eq!(config.lifecycle.len(), 1);
+        assert_eq!(config.lifecycle[0].prefix, "tmp/exports/");
+        assert_eq!(config.lifecycle[0].expire_after, 86400);
+    }
+}
@@ -10,18 +10,34 @@
 //! strategies. A storage strategy defines the behavior of the storage
 //! operations. Strategies implement the [`strategies::StorageStrategy`].
 //! The selected strategy can be dynamically changed at runtime.
+pub mod checksum;
 mod contents;
 pub mod drivers;
+#[cfg(feature = "storage_images")]
+pub mod images;
+pub mod lifecycle;
+pub mod serve;
+pub mod signed;
 pub mod strategies;
 pub mod stream;
+pub mod sync;
+pub mod tus;
 use std::{
     collections::BTreeMap,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+use axum::http::Method;
 use bytes::Bytes;
+use futures_util::TryStreamExt;
 
-use self::{drivers::StoreDriver, stream::BytesStream};
+use self::{
+    drivers::{ListStream, ObjectMetadata, PresignedRequest, StoreDriver, UploadResponse},
+    signed::UrlSigner,
+    stream::BytesStream,
+};
+use crate::config;
 
 #[derive(thiserror::Error, Debug)]
 #[allow(clippy::module_name_repetitions)]
@@ -38,6 +54,15 @@ pub enum StorageError {
     #[error("secondaries errors")]
     Multi(BTreeMap<String, String>),
 
+    #[error("presigned requests are not supported by the store")]
+    PresignUnsupported,
+
+    #[error("checksum mismatch: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        expected: checksum::Checksum,
+        actual: checksum::Checksum,
+    },
+
     #[error(transparent)]
     Any(#[from] Box<dyn std::error::Error + Send + Sync>),
 }
@@ -50,9 +75,37 @@ impl From<opendal::Error> for StorageError {
     }
 }
 
+/// Whether the error is returned for a missing object.
+pub(crate) fn is_not_found(err: &StorageError) -> bool {
+    matches!(err, StorageError::Store(err) if err.kind() == opendal::ErrorKind::NotFound)
+}
+
+/// Key of a content addressed by its SHA-256 digest, sharded by the first
+/// two characters of the digest to keep directories small.
+fn content_key(prefix: &Path, sha256: &str) -> PathBuf {
+    prefix.join(&sha256[..2]).join(sha256)
+}
+
+/// Creates the application storage from the `storage` configuration block,
+/// or a storage with a null store when the block is not set.
+///
+/// # Errors
+///
+/// When the configured storage could not be created.
+pub fn create_storage(config: &config::Config) -> crate::Result<Storage> {
+    match &config.storage {
+        Some(storage) => Ok(Storage::from_config(storage)?),
+        None => Ok(Storage::single(drivers::null::new())),
+    }
+}
+
 pub struct Storage {
     pub stores: BTreeMap<String, Box<dyn StoreDriver>>,
     pub strategy: Box<dyn strategies::StorageStrategy>,
+    /// Signs URLs served by the application for stores which can't presign
+    /// requests themselves.
+    url_signer: Option<UrlSigner>,
+    expiry: lifecycle::ExpiryMarkers,
 }
 
 impl Storage {
@@ -71,6 +124,8 @@ impl Storage {
         Self {
             strategy: Box::new(strategies::single::SingleStrategy::new(default_key)),
             stores: BTreeMap::from([(default_key.to_string(), store)]),
+            url_signer: None,
+            expiry: lifecycle::ExpiryMarkers::default(),
         }
     }
 
@@ -80,10 +135,119 @@ impl Storage {
         stores: BTreeMap<String, Box<dyn StoreDriver>>,
         strategy: Box<dyn strategies::StorageStrategy>,
     ) -> Self {
-        Self { stores, strategy }
+        Self {
+            stores,
+            strategy,
+            url_signer: None,
+            expiry: lifecycle::ExpiryMarkers::default(),
+        }
     }
 
-    /// Uploads content to the storage at the specified path.
+    /// Creates a storage from the `storage` configuration block, with the
+    /// configured stores, strategy and URL signer.
+    ///
+    /// When no strategy is configured, there must be exactly one store, used
+    /// with the single strategy.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageError`] when a store can't be created, the strategy
+    /// refers to an unknown store, or several stores are configured without
+    /// a strategy.
+    pub fn from_config(config: &config::StorageConfig) -> StorageResult<Self> {
+        let stores = config
+            .stores
+            .iter()
+            .map(|(name, store)| Ok((name.clone(), drivers::from_config(store)?)))
+            .collect::<StorageResult<BTreeMap<_, _>>>()?;
+
+        let strategy: Box<dyn strategies::StorageStrategy> = match &config.strategy {
+            Some(config::StorageStrategyConfig::Single { store }) => {
+                Box::new(strategies::single::SingleStrategy::new(store))
+            }
+            Some(config::StorageStrategyConfig::Mirror {
+                primary,
+                secondaries,
+                failure_mode,
+            }) => Box::new(strategies::mirror::MirrorStrategy::new(
+                primary,
+                Some(secondaries.clone()),
+                failure_mode.clone(),
+            )),
+            Some(config::StorageStrategyConfig::Backup {
+                primary,
+                secondaries,
+                failure_mode,
+            }) => Box::new(strategies::backup::BackupStrategy::new(
+                primary,
+                Some(secondaries.clone()),
+                failure_mode.clone(),
+            )),
+            None => match stores.keys().collect::<Vec<_>>().as_slice() {
+                [store] => Box::new(strategies::single::SingleStrategy::new(store)),
+                _ => {
+                    return Err(StorageError::Any(
+                        "a strategy is required when more than one store is configured".into(),
+                    ))
+                }
+            },
+        };
+
+        let names = match &config.strategy {
+            Some(config::StorageStrategyConfig::Single { store }) => vec![store],
+            Some(
+                config::StorageStrategyConfig::Mirror {
+                    primary,
+                    secondaries,
+                    ..
+                }
+                | config::StorageStrategyConfig::Backup {
+                    primary,
+                    secondaries,
+                    ..
+                },
+            ) => std::iter::once(primary).chain(secondaries).collect(),
+            None => vec![],
+        };
+        if let Some(name) = names.into_iter().find(|name| !stores.contains_key(*name)) {
+            return Err(StorageError::StoreNotFound(name.clone()));
+        }
+
+        let storage = Self::new(stores, strategy);
+        Ok(match &config.signed_urls {
+            Some(signed_urls) => storage.with_url_signer(UrlSigner::new(
+                signed_urls.secret.as_str(),
+                &signed_urls.base_url,
+            )),
+            None => storage,
+        })
+    }
+
+    /// Sets the signer of the URLs served by [`signed::routes`], used for
+    /// stores which can't presign requests themselves, such as
+    /// [`drivers::local`] and [`drivers::mem`].
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage::{self, signed::UrlSigner};
+    ///
+    /// let storage = storage::Storage::single(storage::drivers::mem::new())
+    ///     .with_url_signer(UrlSigner::new("secret", "http://localhost:5150"));
+    /// ```
+    #[must_use]
+    pub fn with_url_signer(mut self, signer: UrlSigner) -> Self {
+        self.url_signer = Some(signer);
+        self
+    }
+
+    /// Returns the signer of the URLs served by [`signed::routes`], if set.
+    #[must_use]
+    pub fn url_signer(&self) -> Option<&UrlSigner> {
+        self.url_signer.as_ref()
+    }
+
+    /// Uploads content to the storage at the specified path, returning the
+    /// response of the store with the checksums of the content.
     ///
     /// This method uses the selected strategy for the upload operation.
     ///
@@ -105,7 +269,7 @@ impl Storage {
     ///
     /// This method returns an error if the upload operation fails or if there
     /// is an issue with the strategy configuration.
-    pub async fn upload(&self, path: &Path, content: &Bytes) -> StorageResult<()> {
+    pub async fn upload(&self, path: &Path, content: &Bytes) -> StorageResult<UploadResponse> {
         self.upload_with_strategy(path, content, &*self.strategy)
             .await
     }
@@ -125,7 +289,7 @@ impl Storage {
         path: &Path,
         content: &Bytes,
         strategy: &dyn strategies::StorageStrategy,
-    ) -> StorageResult<()> {
+    ) -> StorageResult<UploadResponse> {
         strategy.upload(self, path, content).await
     }
 
@@ -226,7 +390,9 @@ impl Storage {
         path: &Path,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<()> {
-        strategy.delete(self, path).await
+        strategy.delete(self, path).await?;
+        // a leftover expiry would delete an object uploaded later at the path
+        lifecycle::clear_expiry(self, path, strategy).await
     }
 
     /// Renames content from one path to another in the storage.
@@ -276,7 +442,9 @@ impl Storage {
         to: &Path,
         strategy: &dyn strategies::StorageStrategy,
     ) -> StorageResult<()> {
-        strategy.rename(self, from, to).await
+        let expires_at = lifecycle::expiry_to_move(self, from, strategy).await?;
+        strategy.rename(self, from, to).await?;
+        lifecycle::move_expiry(self, from, to, expires_at, strategy).await
     }
 
     /// Copies content from one path to another in the storage.
@@ -414,6 +582,53 @@ impl Storage {
         strategy.download_stream(self, path).await
     }
 
+    /// Downloads the given range of bytes of the content as a stream, such
+    /// as for HTTP range requests.
+    ///
+    /// This method uses the selected strategy for the download operation.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// pub async fn stream_range() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("large_file.mp4");
+    ///
+    ///     // the first kilobyte of the file
+    ///     let stream = storage.download_range_stream(path, 0..1024).await.unwrap();
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the download operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn download_range_stream(
+        &self,
+        path: &Path,
+        range: std::ops::Range<u64>,
+    ) -> StorageResult<BytesStream> {
+        self.download_range_stream_with_policy(path, range, &*self.strategy)
+            .await
+    }
+
+    /// Downloads a range of bytes of the content as a stream using a specific
+    /// strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the download operation fails or if there
+    /// is an issue with the strategy configuration.
+    pub async fn download_range_stream_with_policy(
+        &self,
+        path: &Path,
+        range: std::ops::Range<u64>,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<BytesStream> {
+        strategy.download_range_stream(self, path, range).await
+    }
+
     /// Uploads content from a stream to storage, enabling efficient
     /// handling of large files without loading them entirely into memory.
     ///
@@ -435,7 +650,11 @@ impl Storage {
     ///
     /// This method returns an error if the upload operation fails or if there
     /// is an issue with the strategy configuration.
-    pub async fn upload_stream(&self, path: &Path, stream: BytesStream) -> StorageResult<()> {
+    pub async fn upload_stream(
+        &self,
+        path: &Path,
+        stream: BytesStream,
+    ) -> StorageResult<UploadResponse> {
         self.upload_stream_with_policy(path, stream, &*self.strategy)
             .await
     }
@@ -451,7 +670,627 @@ impl Storage {
         path: &Path,
         stream: BytesStream,
         strategy: &dyn strategies::StorageStrategy,
-    ) -> StorageResult<()> {
+    ) -> StorageResult<UploadResponse> {
         strategy.upload_stream(self, path, stream).await
     }
+
+    /// Uploads content after verifying it matches the checksum given by the
+    /// caller, such as a checksum sent by a client.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage::{self, checksum::Checksum, StorageError};
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// pub async fn upload() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("example.txt");
+    ///     let expected = Checksum::Md5("0bd5b958a5e4e11611834b0e8eff652d".to_string());
+    ///     storage.upload_verified(path, &Bytes::from("Loco!"), &expected).await.unwrap();
+    ///
+    ///     let upload = storage.upload_verified(path, &Bytes::from("Loco"), &expected).await;
+    ///     assert!(matches!(upload, Err(StorageError::ChecksumMismatch { .. })));
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// [`StorageError::ChecksumMismatch`] without uploading the content when
+    /// it doesn't match, or an error if the upload operation fails.
+    pub async fn upload_verified(
+        &self,
+        path: &Path,
+        content: &Bytes,
+        expected: &checksum::Checksum,
+    ) -> StorageResult<UploadResponse> {
+        let checksums = checksum::Checksums::of(content);
+        if !checksums.matches(expected) {
+            return Err(StorageError::ChecksumMismatch {
+                expected: expected.clone(),
+                actual: checksums.get(expected),
+            });
+        }
+        self.upload(path, content).await
+    }
+
+    /// Uploads content from a stream, verifying it matches the checksum given
+    /// by the caller once the stream is read. Since the checksum is only known
+    /// once the stream is read, the content is uploaded to a temporary key
+    /// first, then moved to the path when it matches, so content already
+    /// stored at the path is kept when it doesn't.
+    ///
+    /// # Errors
+    ///
+    /// [`StorageError::ChecksumMismatch`] when the content doesn't match, or
+    /// an error if the upload operation fails.
+    pub async fn upload_stream_verified(
+        &self,
+        path: &Path,
+        stream: BytesStream,
+        expected: &checksum::Checksum,
+    ) -> StorageResult<UploadResponse> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(format!(".{}.uploading", uuid::Uuid::new_v4()));
+        let temp = PathBuf::from(temp);
+        let (stream, tracker) = checksum::track(stream);
+        let mut response = self.upload_stream(&temp, stream).await?;
+
+        let checksums = tracker.checksums();
+        let moved = if checksums.matches(expected) {
+            response.checksums = Some(checksums);
+            self.rename(&temp, path).await.map(|()| response)
+        } else {
+            Err(StorageError::ChecksumMismatch {
+                expected: expected.clone(),
+                actual: checksums.get(expected),
+            })
+        };
+        if moved.is_err() {
+            if let Err(err) = self.delete(&temp).await {
+                tracing::warn!(path = %temp.display(), error = %err, "could not delete temporary upload");
+            }
+        }
+        moved
+    }
+
+    /// Stores content under a key derived from its SHA-256 digest, such as
+    /// `{prefix}/2e/2eb1870e…`, and returns the key. The upload is skipped
+    /// when an identical content is already stored, so identical files are
+    /// only stored once.
+    ///
+    /// Since the key may be shared by several owners of the same content,
+    /// it must only be deleted once none of them reference it anymore.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// pub async fn upload() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let content = Bytes::from("Loco!");
+    ///     let key = storage.upload_content_addressed(Path::new("blobs"), &content).await.unwrap();
+    ///     let again = storage.upload_content_addressed(Path::new("blobs"), &content).await.unwrap();
+    ///     assert_eq!(key, again);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the upload operation fails, or the
+    /// stored content could not be checked.
+    pub async fn upload_content_addressed(
+        &self,
+        prefix: &Path,
+        content: &Bytes,
+    ) -> StorageResult<PathBuf> {
+        let key = content_key(prefix, &checksum::Checksums::of(content).sha256);
+        if !self.is_stored(&key).await? {
+            self.upload(&key, content).await?;
+        }
+        Ok(key)
+    }
+
+    /// Stores content from a stream under a key derived from its SHA-256
+    /// digest, see [`Storage::upload_content_addressed`]. Since the digest is
+    /// only known once the stream is read, the content is uploaded to a
+    /// temporary key first, then moved to its key or deleted when an
+    /// identical content is already stored.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the upload operation fails, or the
+    /// stored content could not be checked.
+    pub async fn upload_stream_content_addressed(
+        &self,
+        prefix: &Path,
+        stream: BytesStream,
+    ) -> StorageResult<PathBuf> {
+        let temp = prefix.join(format!(".upload-{}", uuid::Uuid::new_v4()));
+        let (stream, tracker) = checksum::track(stream);
+        self.upload_stream(&temp, stream).await?;
+
+        let key = content_key(prefix, &tracker.checksums().sha256);
+        let moved = match self.is_stored(&key).await {
+            Ok(true) => Ok(false),
+            Ok(false) => self.rename(&temp, &key).await.map(|()| true),
+            Err(err) => Err(err),
+        };
+        if !matches!(moved, Ok(true)) {
+            if let Err(err) = self.delete(&temp).await {
+                tracing::warn!(path = %temp.display(), error = %err, "could not delete temporary upload");
+            }
+        }
+        moved.map(|_| key)
+    }
+
+    /// Whether content is stored at the path, using [`Storage::head`].
+    async fn is_stored(&self, path: &Path) -> StorageResult<bool> {
+        match self.head(path).await {
+            Ok(_) => Ok(true),
+            Err(err) if is_not_found(&err) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Lists the entries under the given prefix, recursively or not. See
+    /// [`StoreDriver::list`]. The expiry markers of [`lifecycle`] are left
+    /// out.
+    ///
+    /// This method uses the selected strategy to choose the store.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// use futures_util::TryStreamExt;
+    /// pub async fn list() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     storage.upload(Path::new("users/1.txt"), &Bytes::from("Loco!")).await.unwrap();
+    ///
+    ///     let entries: Vec<_> = storage
+    ///         .list(Path::new("users"), true)
+    ///         .await
+    ///         .unwrap()
+    ///         .try_collect()
+    ///         .await
+    ///         .unwrap();
+    ///     assert_eq!(entries[0].path, Path::new("users/1.txt"));
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store can't list its entries.
+    pub async fn list(&self, prefix: &Path, recursive: bool) -> StorageResult<ListStream> {
+        self.list_with_policy(prefix, recursive, &*self.strategy)
+            .await
+    }
+
+    /// Lists the entries under the given prefix using a specific strategy,
+    /// leaving out the expiry markers of [`lifecycle`].
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store can't list its entries.
+    pub async fn list_with_policy(
+        &self,
+        prefix: &Path,
+        recursive: bool,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<ListStream> {
+        let entries = strategy.list(self, prefix, recursive).await?;
+        if lifecycle::is_marker(prefix) {
+            return Ok(entries);
+        }
+        Ok(Box::pin(entries.try_filter(|entry| {
+            futures_util::future::ready(!lifecycle::is_marker(&entry.path))
+        })))
+    }
+
+    /// Returns the size, content type, etag and last modification time of the
+    /// content at the specified path.
+    ///
+    /// This method uses the selected strategy to choose the store.
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage;
+    /// use std::path::Path;
+    /// use bytes::Bytes;
+    /// pub async fn head() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new());
+    ///     let path = Path::new("example.txt");
+    ///     storage.upload(path, &Bytes::from("Loco!")).await.unwrap();
+    ///
+    ///     assert_eq!(storage.head(path).await.unwrap().size, 5);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the content doesn't exist.
+    pub async fn head(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        self.head_with_policy(path, &*self.strategy).await
+    }
+
+    /// Returns the metadata of the content at the specified path using a
+    /// specific strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the content doesn't exist.
+    pub async fn head_with_policy(
+        &self,
+        path: &Path,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<ObjectMetadata> {
+        strategy.head(self, path).await
+    }
+
+    /// Creates a request which lets a client download the content at the
+    /// specified path without going through the application, until `expires`
+    /// has passed.
+    ///
+    /// This method uses the selected strategy to choose the store. Stores
+    /// which can't presign requests fall back to the URLs of the configured
+    /// [`UrlSigner`].
+    ///
+    /// # Examples
+    ///```
+    /// use loco_rs::storage::{self, signed::UrlSigner};
+    /// use std::{path::Path, time::Duration};
+    /// use bytes::Bytes;
+    /// pub async fn presigned_get() {
+    ///     let storage = storage::Storage::single(storage::drivers::mem::new())
+    ///         .with_url_signer(UrlSigner::new("secret", "http://localhost:5150"));
+    ///     let path = Path::new("report.pdf");
+    ///     storage.upload(path, &Bytes::from("Loco!")).await.unwrap();
+    ///
+    ///     let request = storage
+    ///         .presigned_get(path, Duration::from_secs(300))
+    ///         .await
+    ///         .unwrap();
+    ///     assert!(request.uri.starts_with("http://localhost:5150/_storage/store/report.pdf?"));
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store can't presign requests and
+    /// no [`UrlSigner`] is configured, or the strategy doesn't allow it.
+    pub async fn presigned_get(
+        &self,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        self.presigned_get_with_policy(path, expires, &*self.strategy)
+            .await
+    }
+
+    /// Creates a presigned download request using a specific strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store can't presign requests and
+    /// no [`UrlSigner`] is configured, or the strategy doesn't allow it.
+    pub async fn presigned_get_with_policy(
+        &self,
+        path: &Path,
+        expires: Duration,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<PresignedRequest> {
+        strategy.presigned_get(self, path, expires).await
+    }
+
+    /// Creates a request which lets a client upload content to the specified
+    /// path without going through the application, until `expires` has
+    /// passed.
+    ///
+    /// This method uses the selected strategy to choose the store. Content
+    /// uploaded with a presigned request is only written to that store, so
+    /// strategies replicating uploads refuse to presign them unless they
+    /// tolerate failures of their secondary stores.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store can't presign requests and
+    /// no [`UrlSigner`] is configured, or the strategy doesn't allow it.
+    pub async fn presigned_put(
+        &self,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        self.presigned_put_with_policy(path, expires, &*self.strategy)
+            .await
+    }
+
+    /// Creates a presigned upload request using a specific strategy.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store can't presign requests and
+    /// no [`UrlSigner`] is configured, or the strategy doesn't allow it.
+    pub async fn presigned_put_with_policy(
+        &self,
+        path: &Path,
+        expires: Duration,
+        strategy: &dyn strategies::StorageStrategy,
+    ) -> StorageResult<PresignedRequest> {
+        strategy.presigned_put(self, path, expires).await
+    }
+
+    /// Presigns a request for the given store, falling back to the URLs of
+    /// the configured [`UrlSigner`] when the store can't presign requests.
+    /// Strategies use this method to presign requests for the store they
+    /// select.
+    ///
+    /// # Errors
+    ///
+    /// This method returns an error if the store doesn't exist, or can't
+    /// presign requests and no [`UrlSigner`] is configured.
+    pub async fn presign_with_store(
+        &self,
+        store_name: &str,
+        method: Method,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        let store = self.as_store_err(store_name)?;
+        let result = if method == Method::PUT {
+            store.presigned_put(path, expires).await
+        } else {
+            store.presigned_get(path, expires).await
+        };
+        match (result, &self.url_signer) {
+            (Err(StorageError::PresignUnsupported), Some(signer)) => {
+                Ok(signer.sign(&method, store_name, path, expires))
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_config(yaml: &str) -> config::StorageConfig {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[tokio::test]
+    async fn can_create_single_store_from_config() {
+        let storage = Storage::from_config(&storage_config(
+            r"
+stores:
+  uploads:
+    kind: Mem
+signed_urls:
+  secret: secret
+  base_url: http://localhost:5150
+",
+        ))
+        .unwrap();
+
+        let path = Path::new("file.txt");
+        storage.upload(path, &Bytes::from("Loco!")).await.unwrap();
+        let content: String = storage.download(path).await.unwrap();
+        assert_eq!(content, "Loco!");
+        assert!(storage.as_store("uploads").is_some());
+        assert!(storage.url_signer().is_some());
+    }
+
+    #[tokio::test]
+    async fn can_create_backup_from_config() {
+        let tree_fs = tree_fs::TreeBuilder::default().drop(true).create().unwrap();
+        let storage = Storage::from_config(&storage_config(&format!(
+            r"
+stores:
+  primary:
+    kind: Mem
+  archive:
+    kind: Local
+    root: {}
+strategy:
+  kind: Backup
+  primary: primary
+  secondaries: [archive]
+  failure_mode:
+    CountFailure: 1
+",
+            tree_fs.root.display()
+        )))
+        .unwrap();
+
+        let path = Path::new("docs/file.txt");
+        storage.upload(path, &Bytes::from("Loco!")).await.unwrap();
+        assert_eq!(
+            std::fs::read_to_string(tree_fs.root.join("docs/file.txt")).unwrap(),
+            "Loco!"
+        );
+    }
+
+    #[test]
+    fn cant_create_from_invalid_config() {
+        let unknown_store = Storage::from_config(&storage_config(
+            r"
+stores:
+  primary:
+    kind: Mem
+strategy:
+  kind: Mirror
+  primary: primary
+  secondaries: [missing]
+  failure_mode: MirrorAll
+",
+        ));
+        assert!(
+            matches!(unknown_store, Err(StorageError::StoreNotFound(name)) if name == "missing")
+        );
+
+        let no_strategy = Storage::from_config(&storage_config(
+            r"
+stores:
+  one:
+    kind: Mem
+  two:
+    kind: Mem
+",
+        ));
+        assert!(matches!(no_strategy, Err(StorageError::Any(_))));
+    }
+
+    async fn list_keys(storage: &Storage) -> Vec<String> {
+        use futures_util::TryStreamExt;
+
+        let mut keys: Vec<String> = storage
+            .list(Path::new(""), true)
+            .await
+            .unwrap()
+            .map_ok(|entry| entry.path.display().to_string())
+            .try_collect()
+            .await
+            .unwrap();
+        keys.sort();
+        keys
+    }
+
+    #[tokio::test]
+    async fn upload_returns_checksums() {
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("primary".to_string(), drivers::mem::new()),
+                ("mirror".to_string(), drivers::mem::new()),
+            ]),
+            Box::new(strategies::mirror::MirrorStrategy::new(
+                "primary",
+                Some(vec!["mirror".to_string()]),
+                strategies::mirror::FailureMode::MirrorAll,
+            )),
+        );
+        let response = storage
+            .upload(Path::new("file.txt"), &Bytes::from("Loco!"))
+            .await
+            .unwrap();
+        assert_eq!(response.checksums, Some(checksum::Checksums::of(b"Loco!")));
+
+        let stream = BytesStream::from_body_stream(futures_util::stream::iter(vec![Ok(
+            Bytes::from("Loco!"),
+        )]));
+        let response = storage
+            .upload_stream(Path::new("stream.txt"), stream)
+            .await
+            .unwrap();
+        assert_eq!(response.checksums, Some(checksum::Checksums::of(b"Loco!")));
+    }
+
+    #[tokio::test]
+    async fn can_upload_verified() {
+        let storage = Storage::single(drivers::mem::new());
+        let content = Bytes::from("Loco!");
+        let checksums = checksum::Checksums::of(&content);
+
+        storage
+            .upload_verified(
+                Path::new("ok.txt"),
+                &content,
+                &checksum::Checksum::Sha256(checksums.sha256.clone()),
+            )
+            .await
+            .unwrap();
+        let mismatch = storage
+            .upload_verified(
+                Path::new("mismatch.txt"),
+                &Bytes::from("Loco"),
+                &checksum::Checksum::Sha256(checksums.sha256.clone()),
+            )
+            .await;
+        assert!(matches!(
+            mismatch,
+            Err(StorageError::ChecksumMismatch { expected: checksum::Checksum::Sha256(expected), .. })
+                if expected == checksums.sha256
+        ));
+
+        let stream = |content: &'static str| {
+            BytesStream::from_body_stream(futures_util::stream::iter(vec![Ok(Bytes::from(
+                content,
+            ))]))
+        };
+        storage
+            .upload_stream_verified(
+                Path::new("stream.txt"),
+                stream("Loco!"),
+                &checksum::Checksum::Md5(checksums.md5.clone()),
+            )
+            .await
+            .unwrap();
+        let mismatch = storage
+            .upload_stream_verified(
+                Path::new("stream-mismatch.txt"),
+                stream("Loco"),
+                &checksum::Checksum::Md5(checksums.md5.clone()),
+            )
+            .await;
+        assert!(matches!(
+            mismatch,
+            Err(StorageError::ChecksumMismatch { .. })
+        ));
+
+        // a mismatch keeps the content already stored at the path
+        let mismatch = storage
+            .upload_stream_verified(
+                Path::new("stream.txt"),
+                stream("Loco"),
+                &checksum::Checksum::Md5(checksums.md5.clone()),
+            )
+            .await;
+        assert!(matches!(
+            mismatch,
+            Err(StorageError::ChecksumMismatch { .. })
+        ));
+        let content: String = storage.download(Path::new("stream.txt")).await.unwrap();
+        assert_eq!(content, "Loco!");
+
+        assert_eq!(list_keys(&storage).await, vec!["ok.txt", "stream.txt"]);
+    }
+
+    #[tokio::test]
+    async fn can_upload_content_addressed() {
+        let storage = Storage::single(drivers::mem::new());
+        let prefix = Path::new("blobs");
+        let sha256 = checksum::Checksums::of(b"Loco!").sha256;
+
+        let key = storage
+            .upload_content_addressed(prefix, &Bytes::from("Loco!"))
+            .await
+            .unwrap();
+        assert_eq!(key, prefix.join(&sha256[..2]).join(&sha256));
+
+        let stream = BytesStream::from_body_stream(futures_util::stream::iter(vec![
+            Ok(Bytes::from("Lo")),
+            Ok(Bytes::from("co!")),
+        ]));
+        let again = storage
+            .upload_stream_content_addressed(prefix, stream)
+            .await
+            .unwrap();
+        assert_eq!(again, key);
+
+        let stream = BytesStream::from_body_stream(futures_util::stream::iter(vec![Ok(
+            Bytes::from("Other"),
+        )]));
+        let other = storage
+            .upload_stream_content_addressed(prefix, stream)
+            .await
+            .unwrap();
+        assert_ne!(other, key);
+
+        let mut expected = vec![key.display().to_string(), other.display().to_string()];
+        expected.sort();
+        assert_eq!(list_keys(&storage).await, expected);
+        let content: String = storage.download(&other).await.unwrap();
+        assert_eq!(content, "Other");
+    }
 }
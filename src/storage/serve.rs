@@ -0,0 +1,530 @@
+//! # Serving Stored Files
+//!
+//! Streams stored files to HTTP clients without loading them in memory,
+//! with support for:
+//!
+//! * `Range` requests, so videos can be seeked and large downloads resumed
+//! * `ETag` and `If-None-Match`, answering `304 Not Modified` for cached files
+//! * `Content-Type`, from the store metadata or the file extension, with
+//!   `X-Content-Type-Options: nosniff` so browsers keep to it
+//! * `Content-Disposition: attachment`, to download instead of display
+//!
+//! In a controller, use [`crate::controller::format::RenderBuilder::file`]:
+//!
+//! ```rust
+//! use std::path::PathBuf;
+//!
+//! use axum::http::HeaderMap;
+//! use loco_rs::prelude::*;
+//!
+//! async fn invoice(
+//!     State(ctx): State<AppContext>,
+//!     Path(id): Path<i32>,
+//!     headers: HeaderMap,
+//! ) -> Result<Response> {
+//!     let path = PathBuf::from(format!("invoices/{id}.pdf"));
+//!     format::render()
+//!         .attachment(&format!("invoice-{id}.pdf"))
+//!         .file(&ctx.storage, &path, &headers)
+//!         .await
+//! }
+//! ```
+//!
+//! Or serve all the files under a key prefix with [`routes`].
+use std::{ops::Range, path::Path};
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, Query, State},
+    http::{header, response::Builder, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+    routing::get,
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::Deserialize;
+
+use super::{
+    drivers::{ObjectMetadata, StoreDriver},
+    lifecycle,
+    stream::BytesStream,
+    Storage, StorageError, StorageResult,
+};
+use crate::{app::AppContext, controller::Routes, Error, Result};
+
+/// Where the served files are read from.
+#[async_trait]
+pub(crate) trait Source: Sync {
+    async fn head(&self, path: &Path) -> StorageResult<ObjectMetadata>;
+    async fn stream(&self, path: &Path, range: Option<Range<u64>>) -> StorageResult<BytesStream>;
+}
+
+#[async_trait]
+impl Source for Storage {
+    async fn head(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        Self::head(self, path).await
+    }
+
+    async fn stream(&self, path: &Path, range: Option<Range<u64>>) -> StorageResult<BytesStream> {
+        match range {
+            Some(range) => self.download_range_stream(path, range).await,
+            None => self.download_stream(path).await,
+        }
+    }
+}
+
+#[async_trait]
+impl Source for &dyn StoreDriver {
+    async fn head(&self, path: &Path) -> StorageResult<ObjectMetadata> {
+        StoreDriver::head(*self, path).await
+    }
+
+    async fn stream(&self, path: &Path, range: Option<Range<u64>>) -> StorageResult<BytesStream> {
+        match range {
+            Some(range) => self.get_range_stream(path, range).await,
+            None => self.get_stream(path).await,
+        }
+    }
+}
+
+/// Maps a missing file to [`Error::NotFound`].
+pub(crate) fn not_found_or(err: StorageError) -> Error {
+    match err {
+        StorageError::Store(err) if err.kind() == opendal::ErrorKind::NotFound => Error::NotFound,
+        err => err.into(),
+    }
+}
+
+/// Range of bytes requested by a `Range` header.
+#[derive(Debug, PartialEq, Eq)]
+enum RequestedRange {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable,
+}
+
+/// Parses a `Range` header for a file of the given size. Only single byte
+/// ranges are supported, the whole file is served for other ranges as
+/// allowed by RFC 9110.
+fn parse_range(value: &str, size: u64) -> RequestedRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return RequestedRange::Full;
+    };
+    if spec.contains(',') {
+        return RequestedRange::Full;
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RequestedRange::Full;
+    };
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=-500, the last 500 bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return RequestedRange::Unsatisfiable;
+            }
+            size.saturating_sub(suffix)..size
+        }
+        // bytes=500-
+        (Ok(start), Err(_)) if end.is_empty() => start..size,
+        // bytes=500-999
+        (Ok(start), Ok(end)) if start <= end => start..end.saturating_add(1).min(size),
+        _ => return RequestedRange::Full,
+    };
+    if range.start >= size {
+        RequestedRange::Unsatisfiable
+    } else {
+        RequestedRange::Partial(range)
+    }
+}
+
+/// Returns the entity tag of a file, derived from its size and modification
+/// time when the store doesn't provide one.
+fn entity_tag(metadata: &ObjectMetadata) -> Option<String> {
+    match (&metadata.e_tag, metadata.last_modified) {
+        (Some(e_tag), _) if e_tag.starts_with('"') || e_tag.starts_with("W/") => {
+            Some(e_tag.clone())
+        }
+        (Some(e_tag), _) => Some(format!("\"{e_tag}\"")),
+        (None, Some(last_modified)) => Some(format!(
+            "W/\"{:x}-{:x}\"",
+            metadata.size,
+            last_modified.timestamp()
+        )),
+        (None, None) => None,
+    }
+}
+
+/// Compares entity tags with the weak comparison of `If-None-Match`.
+fn matches_any(header: &str, e_tag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    header.trim() == "*" || header.split(',').any(|tag| opaque(tag) == opaque(e_tag))
+}
+
+pub(crate) fn http_date(date: chrono::DateTime<chrono::Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+/// Returns a `Content-Disposition` header downloading the file under the
+/// given name, with an ASCII fallback for clients not supporting RFC 6266.
+pub(crate) fn attachment_disposition(filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|c| {
+            if c.is_ascii_graphic() && c != '"' && c != '\\' || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{fallback}\"; filename*=UTF-8''{}",
+        utf8_percent_encode(filename, NON_ALPHANUMERIC)
+    )
+}
+
+/// Builds the response serving a file, see the module documentation.
+pub(crate) async fn respond(
+    mut response: Builder,
+    source: &impl Source,
+    path: &Path,
+    request: &HeaderMap,
+) -> Result<Response> {
+    let metadata = source.head(path).await.map_err(not_found_or)?;
+    let e_tag = entity_tag(&metadata);
+
+    let has_content_type = response
+        .headers_ref()
+        .is_some_and(|headers| headers.contains_key(header::CONTENT_TYPE));
+    if !has_content_type {
+        let content_type = metadata.content_type.clone().unwrap_or_else(|| {
+            mime_guess::from_path(path)
+                .first_or_octet_stream()
+                .to_string()
+        });
+        response = response.header(header::CONTENT_TYPE, content_type);
+    }
+    response = response
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::X_CONTENT_TYPE_OPTIONS, "nosniff");
+    if let Some(e_tag) = &e_tag {
+        response = response.header(header::ETAG, e_tag);
+    }
+    if let Some(last_modified) = metadata.last_modified {
+        response = response.header(header::LAST_MODIFIED, http_date(last_modified));
+    }
+
+    let header = |name| {
+        request
+            .get(name)
+            .and_then(|value: &HeaderValue| value.to_str().ok())
+    };
+    if let (Some(if_none_match), Some(e_tag)) = (header(header::IF_NONE_MATCH), &e_tag) {
+        if matches_any(if_none_match, e_tag) {
+            return Ok(response
+                .status(StatusCode::NOT_MODIFIED)
+                .body(Body::empty())?);
+        }
+    }
+
+    // a range only applies to the version of the file named by `If-Range`
+    let range_applies = header(header::IF_RANGE).map_or(true, |if_range| {
+        e_tag
+            .as_deref()
+            .is_some_and(|e_tag| !e_tag.starts_with("W/") && if_range == e_tag)
+            || metadata
+                .last_modified
+                .is_some_and(|last_modified| if_range == http_date(last_modified))
+    });
+    let range = match header(header::RANGE) {
+        Some(range) if range_applies => parse_range(range, metadata.size),
+        _ => RequestedRange::Full,
+    };
+
+    match range {
+        RequestedRange::Full => {
+            let stream = source.stream(path, None).await.map_err(not_found_or)?;
+            Ok(response
+                .header(header::CONTENT_LENGTH, metadata.size)
+                .body(stream.into_body())?)
+        }
+        RequestedRange::Partial(range) => {
+            let content_range =
+                format!("bytes {}-{}/{}", range.start, range.end - 1, metadata.size);
+            let length = range.end - range.start;
+            let stream = source
+                .stream(path, Some(range))
+                .await
+                .map_err(not_found_or)?;
+            Ok(response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, content_range)
+                .header(header::CONTENT_LENGTH, length)
+                .body(stream.into_body())?)
+        }
+        RequestedRange::Unsatisfiable => Ok(response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{}", metadata.size))
+            .body(Body::empty())?),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ServeQuery {
+    /// Downloads the file as an attachment instead of displaying it.
+    #[serde(default)]
+    download: bool,
+}
+
+/// Routes serving the files of `ctx.storage` under `key_prefix`, at
+/// `{prefix}/{*path}`. Add `?download=true` to a URL to download the file as
+/// an attachment.
+///
+/// Every file under `key_prefix` becomes public: only use it for public
+/// files such as product images, and serve private files from controllers
+/// checking access with [`crate::controller::format::RenderBuilder::file`].
+/// The expiry markers of [`lifecycle`] are never served.
+///
+/// # Example
+///
+/// ```rust
+/// use loco_rs::{controller::AppRoutes, storage};
+///
+/// // serves `public/images/logo.png` at `/files/images/logo.png`
+/// fn routes() -> AppRoutes {
+///     AppRoutes::with_default_routes().add_route(storage::serve::routes("/files", "public"))
+/// }
+/// ```
+#[must_use]
+pub fn routes(prefix: &str, key_prefix: &str) -> Routes {
+    let key_prefix = key_prefix.trim_matches('/').to_string();
+    Routes::new().prefix(prefix).add(
+        "/{*path}",
+        get(
+            move |State(ctx): State<AppContext>,
+                  UrlPath(path): UrlPath<String>,
+                  Query(query): Query<ServeQuery>,
+                  headers: HeaderMap| async move {
+                if path.split('/').any(|segment| segment == "..") {
+                    return Err(Error::NotFound);
+                }
+                let key = Path::new(&key_prefix).join(path.trim_start_matches('/'));
+                if lifecycle::is_marker(&key) {
+                    return Err(Error::NotFound);
+                }
+                let mut response = Builder::new();
+                if query.download {
+                    if let Some(filename) = key.file_name().and_then(|name| name.to_str()) {
+                        response = response.header(
+                            header::CONTENT_DISPOSITION,
+                            attachment_disposition(filename),
+                        );
+                    }
+                }
+                respond(response, &*ctx.storage, &key, &headers).await
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Request;
+    use bytes::Bytes;
+    use rstest::rstest;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::storage::drivers;
+
+    #[rstest]
+    #[case("bytes=0-99", RequestedRange::Partial(0..100))]
+    #[case("bytes=900-", RequestedRange::Partial(900..1000))]
+    #[case("bytes=900-2000", RequestedRange::Partial(900..1000))]
+    #[case("bytes=-100", RequestedRange::Partial(900..1000))]
+    #[case("bytes=-2000", RequestedRange::Partial(0..1000))]
+    #[case("bytes=1000-", RequestedRange::Unsatisfiable)]
+    #[case("bytes=-0", RequestedRange::Unsatisfiable)]
+    #[case("bytes=0-1,5-6", RequestedRange::Full)]
+    #[case("bytes=5-1", RequestedRange::Full)]
+    #[case("items=0-1", RequestedRange::Full)]
+    fn can_parse_range(#[case] header: &str, #[case] expected: RequestedRange) {
+        assert_eq!(parse_range(header, 1000), expected);
+    }
+
+    #[test]
+    fn can_create_attachment_disposition() {
+        assert_eq!(
+            attachment_disposition("résumé \"final\".pdf"),
+            "attachment; filename=\"r_sum_ _final_.pdf\"; \
+             filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22%2Epdf"
+        );
+    }
+
+    async fn send(
+        storage: Storage,
+        uri: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> (StatusCode, HeaderMap, Bytes) {
+        send_under("public", storage, uri, headers).await
+    }
+
+    async fn send_under(
+        key_prefix: &str,
+        storage: Storage,
+        uri: &str,
+        headers: &[(header::HeaderName, &str)],
+    ) -> (StatusCode, HeaderMap, Bytes) {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.storage = storage.into();
+        let router = routes("/files", key_prefix)
+            .handlers
+            .into_iter()
+            .fold(axum::Router::new(), |router, handler| {
+                router.route(&format!("/files{}", handler.uri), handler.method)
+            })
+            .with_state(ctx);
+        let mut request = Request::builder().uri(uri);
+        for (name, value) in headers {
+            request = request.header(name, *value);
+        }
+        let response = router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, headers, body)
+    }
+
+    async fn storage() -> (Storage, tree_fs::Tree) {
+        let tree_fs = tree_fs::TreeBuilder::default()
+            .add("public/docs/report.pdf", "0123456789")
+            .add("private/secret.txt", "secret")
+            .drop(true)
+            .create()
+            .unwrap();
+        let storage = Storage::single(drivers::local::new_with_prefix(&tree_fs.root).unwrap());
+        (storage, tree_fs)
+    }
+
+    #[tokio::test]
+    async fn can_serve_file() {
+        let (storage, _tree_fs) = storage().await;
+        let (status, headers, body) = send(storage, "/files/docs/report.pdf", &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "0123456789");
+        assert_eq!(headers[header::CONTENT_TYPE], "application/pdf");
+        assert_eq!(headers[header::CONTENT_LENGTH], "10");
+        assert_eq!(headers[header::ACCEPT_RANGES], "bytes");
+        assert!(headers.contains_key(header::ETAG));
+        assert!(!headers.contains_key(header::CONTENT_DISPOSITION));
+    }
+
+    #[tokio::test]
+    async fn can_serve_range() {
+        let (storage, _tree_fs) = storage().await;
+        let (status, headers, body) = send(
+            storage,
+            "/files/docs/report.pdf",
+            &[(header::RANGE, "bytes=2-5")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(body, "2345");
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(headers[header::CONTENT_LENGTH], "4");
+
+        let (storage, _tree_fs) = self::storage().await;
+        let (status, headers, _) = send(
+            storage,
+            "/files/docs/report.pdf",
+            &[(header::RANGE, "bytes=20-")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn can_slice_streams_of_stores_without_range_reads() {
+        let storage = Storage::single(drivers::mem::new());
+        storage
+            .upload(Path::new("file.txt"), &Bytes::from("0123456789"))
+            .await
+            .unwrap();
+        let content = storage
+            .download_range_stream(Path::new("file.txt"), 3..7)
+            .await
+            .unwrap()
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(content, "3456");
+        let content = storage
+            .download_stream(Path::new("file.txt"))
+            .await
+            .unwrap()
+            .slice(3..7)
+            .collect()
+            .await
+            .unwrap();
+        assert_eq!(content, "3456");
+    }
+
+    #[tokio::test]
+    async fn can_answer_not_modified() {
+        let (storage, _tree_fs) = storage().await;
+        let (_, headers, _) = send(storage, "/files/docs/report.pdf", &[]).await;
+        let e_tag = headers[header::ETAG].to_str().unwrap().to_string();
+
+        let (storage, _tree_fs) = self::storage().await;
+        let (status, _, body) = send(
+            storage,
+            "/files/docs/report.pdf",
+            &[(header::IF_NONE_MATCH, &format!("\"other\", {e_tag}"))],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+    }
+
+    #[tokio::test]
+    async fn can_download_as_attachment() {
+        let (storage, _tree_fs) = storage().await;
+        let (status, headers, _) = send(storage, "/files/docs/report.pdf?download=true", &[]).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"report.pdf\"; filename*=UTF-8''report%2Epdf"
+        );
+    }
+
+    #[tokio::test]
+    async fn cant_serve_outside_of_key_prefix() {
+        let (storage, _tree_fs) = storage().await;
+        let (status, _, _) = send(storage, "/files/../private/secret.txt", &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (storage, _tree_fs) = self::storage().await;
+        let (status, _, _) = send(storage, "/files/docs/missing.pdf", &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn cant_serve_expiry_markers() {
+        let (storage, _tree_fs) = storage().await;
+        storage
+            .set_expiry(
+                Path::new("public/docs/report.pdf"),
+                chrono::Utc::now() + chrono::Duration::days(1),
+            )
+            .await
+            .unwrap();
+        let (status, _, _) =
+            send_under("", storage, "/files/.expiry/public/docs/report.pdf", &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,397 @@
+//! # Signed Storage URLs
+//!
+//! Stores such as [`super::drivers::local`] and [`super::drivers::mem`] can't
+//! presign requests themselves. For those, [`super::Storage`] creates URLs
+//! signed with a [`UrlSigner`], served by the application under
+//! [`ROUTE_PREFIX`] with the [`routes`] of this module.
+//!
+//! A signed URL names the store and path, and carries its expiry time and an
+//! HMAC-SHA256 signature of the method, store, path and expiry:
+//!
+//! ```text
+//! http://localhost:5150/_storage/uploads/avatars/1.png?expires=1700000000&signature=9f86d0...
+//! ```
+//!
+//! # Example
+//!
+//! ```rust, no_run
+//! use loco_rs::{controller::AppRoutes, storage};
+//!
+//! fn routes() -> AppRoutes {
+//!     AppRoutes::with_default_routes().add_route(storage::signed::routes())
+//! }
+//! ```
+use std::{path::Path, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::{Path as UrlPath, Query, State},
+    http::{response::Builder, HeaderMap, Method},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use futures_util::TryStreamExt;
+use hmac::{Hmac, Mac};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use serde::Deserialize;
+use sha2::Sha256;
+
+use super::{drivers::PresignedRequest, stream::BytesStream};
+use crate::{app::AppContext, controller::Routes, Error, Result};
+
+/// Path under which the signed URLs are served.
+pub const ROUTE_PREFIX: &str = "/_storage";
+
+/// Characters escaped in the segments of signed URL paths.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Creates and verifies signed URLs for the objects of a [`super::Storage`].
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Vec<u8>,
+    base_url: String,
+}
+
+impl std::fmt::Debug for UrlSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("UrlSigner")
+            .field("base_url", &self.base_url)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UrlSigner {
+    /// Creates a signer with the secret used to sign URLs, and the base URL
+    /// of the application serving them, such as `https://example.com`.
+    #[must_use]
+    pub fn new(secret: impl Into<Vec<u8>>, base_url: &str) -> Self {
+        Self {
+            secret: secret.into(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Creates a request for the given method, store and path, valid until
+    /// `expires` has passed.
+    #[must_use]
+    pub fn sign(
+        &self,
+        method: &Method,
+        store: &str,
+        path: &Path,
+        expires: Duration,
+    ) -> PresignedRequest {
+        let path = normalize(path);
+        let expires_at = chrono::Utc::now()
+            .timestamp()
+            .saturating_add(i64::try_from(expires.as_secs()).unwrap_or(i64::MAX));
+        let signature = hex::encode(
+            self.mac(method, store, &path, expires_at)
+                .finalize()
+                .into_bytes(),
+        );
+        let encoded_path = path
+            .split('/')
+            .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+            .collect::<Vec<_>>()
+            .join("/");
+
+        PresignedRequest {
+            method: method.clone(),
+            uri: format!(
+                "{}{ROUTE_PREFIX}/{}/{encoded_path}?expires={expires_at}&signature={signature}",
+                self.base_url,
+                utf8_percent_encode(store, PATH_SEGMENT),
+            ),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Verifies the signature of a request for the given method, store and
+    /// path, and that it has not expired.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Unauthorized`] when the signature is invalid or the
+    /// request has expired.
+    pub fn verify(
+        &self,
+        method: &Method,
+        store: &str,
+        path: &str,
+        expires_at: i64,
+        signature: &str,
+    ) -> Result<()> {
+        let signature =
+            hex::decode(signature).map_err(|_| Error::Unauthorized("invalid signature".into()))?;
+        self.mac(method, store, path.trim_start_matches('/'), expires_at)
+            .verify_slice(&signature)
+            .map_err(|_| Error::Unauthorized("invalid signature".into()))?;
+        if expires_at < chrono::Utc::now().timestamp() {
+            return Err(Error::Unauthorized("signed URL has expired".into()));
+        }
+        Ok(())
+    }
+
+    fn mac(&self, method: &Method, store: &str, path: &str, expires_at: i64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(format!("{method}\n{store}\n{path}\n{expires_at}").as_bytes());
+        mac
+    }
+}
+
+/// Returns the path as signed, without a leading slash.
+fn normalize(path: &Path) -> String {
+    path.display()
+        .to_string()
+        .trim_start_matches('/')
+        .to_string()
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedQuery {
+    expires: i64,
+    signature: String,
+}
+
+fn verify(
+    ctx: &AppContext,
+    method: &Method,
+    store: &str,
+    path: &str,
+    query: &SignedQuery,
+) -> Result<()> {
+    let Some(signer) = ctx.storage.url_signer() else {
+        return Err(Error::NotFound);
+    };
+    signer.verify(method, store, path, query.expires, &query.signature)
+}
+
+/// Serves the content of a signed download URL, see [`super::serve`].
+async fn download(
+    State(ctx): State<AppContext>,
+    UrlPath((store, path)): UrlPath<(String, String)>,
+    Query(query): Query<SignedQuery>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    verify(&ctx, &Method::GET, &store, &path, &query)?;
+    super::serve::respond(
+        Builder::new(),
+        &ctx.storage.as_store_err(&store)?,
+        Path::new(&path),
+        &headers,
+    )
+    .await
+}
+
+/// Stores the body of a signed upload URL.
+async fn upload(
+    State(ctx): State<AppContext>,
+    UrlPath((store, path)): UrlPath<(String, String)>,
+    Query(query): Query<SignedQuery>,
+    body: Body,
+) -> Result<Response> {
+    verify(&ctx, &Method::PUT, &store, &path, &query)?;
+    let stream = BytesStream::from_body_stream(
+        body.into_data_stream()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err)),
+    );
+    ctx.storage
+        .as_store_err(&store)?
+        .upload_stream(Path::new(&path), stream)
+        .await?;
+    Ok(().into_response())
+}
+
+/// Routes serving the signed URLs created by a [`UrlSigner`].
+#[must_use]
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix(ROUTE_PREFIX)
+        .add("/{store}/{*path}", get(download).put(upload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(uri: &str) -> (String, i64, String) {
+        let (path, query) = uri.split_once('?').unwrap();
+        let params: std::collections::HashMap<_, _> = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .collect();
+        (
+            path.to_string(),
+            params["expires"].parse().unwrap(),
+            params["signature"].to_string(),
+        )
+    }
+
+    #[test]
+    fn can_sign_and_verify() {
+        let signer = UrlSigner::new("secret", "http://localhost:5150/");
+        let request = signer.sign(
+            &Method::GET,
+            "store",
+            Path::new("/docs/annual report.pdf"),
+            Duration::from_secs(60),
+        );
+        let (path, expires, signature) = query(&request.uri);
+        assert_eq!(
+            path,
+            "http://localhost:5150/_storage/store/docs/annual%20report.pdf"
+        );
+
+        assert!(signer
+            .verify(
+                &Method::GET,
+                "store",
+                "docs/annual report.pdf",
+                expires,
+                &signature
+            )
+            .is_ok());
+        // the signature covers the method, store, path and expiry
+        assert!(signer
+            .verify(
+                &Method::PUT,
+                "store",
+                "docs/annual report.pdf",
+                expires,
+                &signature
+            )
+            .is_err());
+        assert!(signer
+            .verify(
+                &Method::GET,
+                "other",
+                "docs/annual report.pdf",
+                expires,
+                &signature
+            )
+            .is_err());
+        assert!(signer
+            .verify(&Method::GET, "store", "docs/other.pdf", expires, &signature)
+            .is_err());
+        assert!(signer
+            .verify(
+                &Method::GET,
+                "store",
+                "docs/annual report.pdf",
+                expires + 1,
+                &signature
+            )
+            .is_err());
+        assert!(UrlSigner::new("other", "http://localhost:5150")
+            .verify(
+                &Method::GET,
+                "store",
+                "docs/annual report.pdf",
+                expires,
+                &signature
+            )
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn can_serve_signed_urls() {
+        use tower::ServiceExt;
+
+        use crate::storage::{drivers, Storage};
+
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.storage = Storage::single(drivers::mem::new())
+            .with_url_signer(UrlSigner::new("secret", "http://localhost:5150"))
+            .into();
+        let storage = ctx.storage.clone();
+        let router = axum::Router::new()
+            .route(
+                &format!("{ROUTE_PREFIX}/{{store}}/{{*path}}"),
+                get(download).put(upload),
+            )
+            .with_state(ctx);
+        let send = |method: Method, uri: &str, body: Body| {
+            let uri = uri.trim_start_matches("http://localhost:5150").to_string();
+            let router = router.clone();
+            async move {
+                let request = axum::http::Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(body)
+                    .unwrap();
+                router.oneshot(request).await.unwrap()
+            }
+        };
+        let path = Path::new("docs/report.txt");
+
+        let put = storage
+            .presigned_put(path, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(put.method, Method::PUT);
+        let response = send(Method::PUT, &put.uri, Body::from("Loco!")).await;
+        assert_eq!(response.status(), 200);
+        let content: String = storage.download(path).await.unwrap();
+        assert_eq!(content, "Loco!");
+
+        let get = storage
+            .presigned_get(path, Duration::from_secs(60))
+            .await
+            .unwrap();
+        let response = send(Method::GET, &get.uri, Body::empty()).await;
+        assert_eq!(response.status(), 200);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body, "Loco!");
+
+        // a download URL can't be used to upload
+        let response = send(Method::PUT, &get.uri, Body::from("changed")).await;
+        assert_eq!(response.status(), 401);
+
+        let tampered = get.uri.replace("report.txt", "other.txt");
+        let response = send(Method::GET, &tampered, Body::empty()).await;
+        assert_eq!(response.status(), 401);
+    }
+
+    #[tokio::test]
+    async fn cant_presign_without_signer() {
+        let storage = crate::storage::Storage::single(crate::storage::drivers::mem::new());
+        assert!(matches!(
+            storage
+                .presigned_get(Path::new("file.txt"), Duration::from_secs(60))
+                .await,
+            Err(crate::storage::StorageError::PresignUnsupported)
+        ));
+    }
+
+    #[test]
+    fn cant_verify_expired_url() {
+        let signer = UrlSigner::new("secret", "http://localhost:5150");
+        let expires_at = chrono::Utc::now().timestamp() - 1;
+        let signature = hex::encode(
+            signer
+                .mac(&Method::GET, "store", "file.txt", expires_at)
+                .finalize()
+                .into_bytes(),
+        );
+        assert!(matches!(
+            signer.verify(&Method::GET, "store", "file.txt", expires_at, &signature),
+            Err(Error::Unauthorized(msg)) if msg == "signed URL has expired"
+        ));
+    }
+}
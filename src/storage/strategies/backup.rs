@@ -21,14 +21,19 @@
 //!
 //! * `download`: Initiates the download of the given path only from primary
 //!   storage.
-use std::{collections::BTreeMap, path::Path};
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
+use axum::http::Method;
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    drivers::{ListStream, ObjectMetadata, PresignedRequest, UploadResponse},
+    strategies::StorageStrategy,
+    Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`BackupStrategy`].
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum FailureMode {
     /// Fail if any secondary storage backend encounters an error.
     BackupAll,
@@ -58,8 +63,13 @@ impl StorageStrategy for BackupStrategy {
     ///
     /// Returns a [`StorageResult`] indicating success or an error depend of the
     /// [`FailureMode`].
-    async fn upload(&self, storage: &Storage, path: &Path, content: &Bytes) -> StorageResult<()> {
-        storage
+    async fn upload(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        content: &Bytes,
+    ) -> StorageResult<UploadResponse> {
+        let response = storage
             .as_store_err(&self.primary)?
             .upload(path, content)
             .await?;
@@ -84,7 +94,7 @@ impl StorageStrategy for BackupStrategy {
             return Err(StorageError::Multi(collect_errors));
         }
 
-        Ok(())
+        Ok(response)
     }
 
     /// Downloads content only from primary storage backend.
@@ -209,6 +219,25 @@ impl StorageStrategy for BackupStrategy {
         storage.as_store_err(&self.primary)?.get_stream(path).await
     }
 
+    /// Downloads a range of bytes of the content as a stream, see
+    /// [`Storage::download_range_stream`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream
+    async fn download_range_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: std::ops::Range<u64>,
+    ) -> StorageResult<super::super::stream::BytesStream> {
+        // For backup strategy, we only download from primary
+        storage
+            .as_store_err(&self.primary)?
+            .get_range_stream(path, range)
+            .await
+    }
+
     /// Uploads content from a stream to the primary and backup storage
     ///
     /// # Errors
@@ -219,7 +248,7 @@ impl StorageStrategy for BackupStrategy {
         storage: &Storage,
         path: &Path,
         stream: super::super::stream::BytesStream,
-    ) -> StorageResult<()> {
+    ) -> StorageResult<UploadResponse> {
         // For backup strategy, we need to buffer the stream content once
         // to be able to upload to multiple stores
         let content = stream
@@ -228,7 +257,7 @@ impl StorageStrategy for BackupStrategy {
             .map_err(|e| StorageError::Any(Box::new(e)))?;
 
         // Upload to primary
-        storage
+        let response = storage
             .as_store_err(&self.primary)?
             .upload(path, &content)
             .await?;
@@ -254,7 +283,82 @@ impl StorageStrategy for BackupStrategy {
             }
         }
 
-        Ok(())
+        Ok(response)
+    }
+
+    /// Presigns a download request for the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presigned_get(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage
+            .presign_with_store(&self.primary, Method::GET, path, expires)
+            .await
+    }
+
+    /// Presigns an upload request for the primary storage.
+    ///
+    /// Content uploaded with the request is not backed up, so this fails when
+    /// the [`FailureMode`] doesn't allow that many backup failures.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presigned_put(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        let skipped: BTreeMap<String, String> = self
+            .secondaries
+            .iter()
+            .flatten()
+            .map(|secondary_store| {
+                (
+                    secondary_store.clone(),
+                    "presigned uploads are not backed up".to_string(),
+                )
+            })
+            .collect();
+        if self.failure_mode.should_fail(&skipped) {
+            return Err(StorageError::Multi(skipped));
+        }
+        storage
+            .presign_with_store(&self.primary, Method::PUT, path, expires)
+            .await
+    }
+
+    /// Lists the entries of the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream of entries.
+    async fn list(
+        &self,
+        storage: &Storage,
+        prefix: &Path,
+        recursive: bool,
+    ) -> StorageResult<ListStream> {
+        storage
+            .as_store_err(&self.primary)?
+            .list(prefix, recursive)
+            .await
+    }
+
+    /// Returns the metadata of the content in the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the metadata.
+    async fn head(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        storage.as_store_err(&self.primary)?.head(path).await
     }
 }
 
@@ -20,14 +20,19 @@
 //!   primary, it looks for the content in the secondary storages. If the
 //!   content is not found in any storage backend (both primary and secondary),
 //!   it returns an error.
-use std::{collections::BTreeMap, path::Path};
+use std::{collections::BTreeMap, path::Path, time::Duration};
 
+use axum::http::Method;
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageError, StorageResult};
+use crate::storage::{
+    drivers::{ListStream, ObjectMetadata, PresignedRequest, UploadResponse},
+    strategies::StorageStrategy,
+    Storage, StorageError, StorageResult,
+};
 
 /// Enum representing the failure mode for the [`MirrorStrategy`].
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum FailureMode {
     /// Fail if any secondary storage mirror encounters an error.
     MirrorAll,
@@ -60,8 +65,13 @@ impl StorageStrategy for MirrorStrategy {
     ///
     /// Returns a [`StorageResult`] indicating success or an error depend of the
     /// [`FailureMode`].
-    async fn upload(&self, storage: &Storage, path: &Path, content: &Bytes) -> StorageResult<()> {
-        storage
+    async fn upload(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        content: &Bytes,
+    ) -> StorageResult<UploadResponse> {
+        let response = storage
             .as_store_err(&self.primary)?
             .upload(path, content)
             .await?;
@@ -86,7 +96,7 @@ impl StorageStrategy for MirrorStrategy {
             return Err(StorageError::Multi(collect_errors));
         }
 
-        Ok(())
+        Ok(response)
     }
 
     /// Downloads content from the primary storage backend. If the primary
@@ -241,6 +251,40 @@ impl StorageStrategy for MirrorStrategy {
         }
     }
 
+    /// Downloads a range of bytes of the content as a stream, see
+    /// [`Storage::download_range_stream`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream
+    async fn download_range_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: std::ops::Range<u64>,
+    ) -> StorageResult<super::super::stream::BytesStream> {
+        // Try primary first, then secondaries
+        if let Ok(stream) = storage
+            .as_store_err(&self.primary)?
+            .get_range_stream(path, range.clone())
+            .await
+        {
+            return Ok(stream);
+        }
+        for secondary_store in self.secondaries.iter().flatten() {
+            if let Some(store) = storage.as_store(secondary_store) {
+                if let Ok(stream) = store.get_range_stream(path, range.clone()).await {
+                    return Ok(stream);
+                }
+            }
+        }
+        // If all failed, return error from primary
+        storage
+            .as_store_err(&self.primary)?
+            .get_range_stream(path, range)
+            .await
+    }
+
     /// Uploads content from a stream to the primary and secondary storage
     ///
     /// # Errors
@@ -251,7 +295,7 @@ impl StorageStrategy for MirrorStrategy {
         storage: &Storage,
         path: &Path,
         stream: super::super::stream::BytesStream,
-    ) -> StorageResult<()> {
+    ) -> StorageResult<UploadResponse> {
         // For mirroring, we need to buffer the stream content once
         // to be able to upload to multiple stores
         let content = stream
@@ -260,7 +304,7 @@ impl StorageStrategy for MirrorStrategy {
             .map_err(|e| StorageError::Any(Box::new(e)))?;
 
         // Upload to primary
-        storage
+        let response = storage
             .as_store_err(&self.primary)?
             .upload(path, &content)
             .await?;
@@ -286,7 +330,106 @@ impl StorageStrategy for MirrorStrategy {
             }
         }
 
-        Ok(())
+        Ok(response)
+    }
+
+    /// Presigns a download request for the primary storage, or for the first
+    /// secondary storage holding the content when the primary doesn't.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presigned_get(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        let primary = storage.as_store_err(&self.primary)?;
+        if !primary.exists(path).await.unwrap_or(false) {
+            for secondary_store in self.secondaries.iter().flatten() {
+                if let Some(store) = storage.as_store(secondary_store) {
+                    if store.exists(path).await.unwrap_or(false) {
+                        return storage
+                            .presign_with_store(secondary_store, Method::GET, path, expires)
+                            .await;
+                    }
+                }
+            }
+        }
+        storage
+            .presign_with_store(&self.primary, Method::GET, path, expires)
+            .await
+    }
+
+    /// Presigns an upload request for the primary storage.
+    ///
+    /// Content uploaded with the request is not mirrored, so this fails when
+    /// the [`FailureMode`] doesn't allow mirror failures.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presigned_put(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        let skipped: BTreeMap<String, String> = self
+            .secondaries
+            .iter()
+            .flatten()
+            .map(|secondary_store| {
+                (
+                    secondary_store.clone(),
+                    "presigned uploads are not mirrored".to_string(),
+                )
+            })
+            .collect();
+        if self.failure_mode.should_fail(&skipped) {
+            return Err(StorageError::Multi(skipped));
+        }
+        storage
+            .presign_with_store(&self.primary, Method::PUT, path, expires)
+            .await
+    }
+
+    /// Lists the entries of the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream of entries.
+    async fn list(
+        &self,
+        storage: &Storage,
+        prefix: &Path,
+        recursive: bool,
+    ) -> StorageResult<ListStream> {
+        storage
+            .as_store_err(&self.primary)?
+            .list(prefix, recursive)
+            .await
+    }
+
+    /// Returns the metadata of the content in the primary storage, or in
+    /// secondary storage if primary fails.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the metadata.
+    async fn head(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        let res = storage.as_store_err(&self.primary)?.head(path).await;
+        if res.is_err() {
+            for secondary_store in self.secondaries.iter().flatten() {
+                if let Some(store) = storage.as_store(secondary_store) {
+                    if let Ok(metadata) = store.head(path).await {
+                        return Ok(metadata);
+                    }
+                }
+            }
+        }
+        res
     }
 }
 
@@ -808,4 +951,72 @@ mod tests {
         assert!(store_1.exists(new_path.as_path()).await.unwrap());
         assert!(store_3.exists(new_path.as_path()).await.unwrap());
     }
+
+    #[rstest::rstest]
+    #[case(FailureMode::MirrorAll, false)]
+    #[case(FailureMode::AllowMirrorFailure, true)]
+    #[tokio::test]
+    async fn presigned_put_depends_on_failure_mode(
+        #[case] failure_mode: FailureMode,
+        #[case] allowed: bool,
+    ) {
+        let strategy = Box::new(MirrorStrategy::new(
+            "store_1",
+            Some(vec!["store_2".to_string()]),
+            failure_mode,
+        )) as Box<dyn StorageStrategy>;
+
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("store_1".to_string(), drivers::mem::new()),
+                ("store_2".to_string(), drivers::mem::new()),
+            ]),
+            strategy,
+        )
+        .with_url_signer(crate::storage::signed::UrlSigner::new(
+            "secret",
+            "http://localhost:5150",
+        ));
+
+        let result = storage
+            .presigned_put(Path::new("1.txt"), Duration::from_secs(60))
+            .await;
+        assert_eq!(result.is_ok(), allowed);
+    }
+
+    #[tokio::test]
+    async fn presigned_get_falls_back_to_secondary_holding_content() {
+        let strategy = Box::new(MirrorStrategy::new(
+            "store_1",
+            Some(vec!["store_2".to_string()]),
+            FailureMode::MirrorAll,
+        )) as Box<dyn StorageStrategy>;
+
+        let storage = Storage::new(
+            BTreeMap::from([
+                ("store_1".to_string(), drivers::mem::new()),
+                ("store_2".to_string(), drivers::mem::new()),
+            ]),
+            strategy,
+        )
+        .with_url_signer(crate::storage::signed::UrlSigner::new(
+            "secret",
+            "http://localhost:5150",
+        ));
+        let path = Path::new("1.txt");
+        storage
+            .as_store("store_2")
+            .unwrap()
+            .upload(path, &Bytes::from("file content"))
+            .await
+            .unwrap();
+
+        let request = storage
+            .presigned_get(path, Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(request
+            .uri
+            .starts_with("http://localhost:5150/_storage/store_2/1.txt?"));
+    }
 }
@@ -2,15 +2,26 @@ pub mod backup;
 pub mod mirror;
 pub mod single;
 
-use std::path::Path;
+use std::{ops::Range, path::Path, time::Duration};
 
 use bytes::Bytes;
 
-use crate::storage::{stream::BytesStream, Storage, StorageResult};
+use crate::storage::{
+    drivers::{ListStream, ObjectMetadata, PresignedRequest, UploadResponse},
+    stream::BytesStream,
+    Storage, StorageError, StorageResult,
+};
 
 #[async_trait::async_trait]
 pub trait StorageStrategy: Sync + Send {
-    async fn upload(&self, storage: &Storage, path: &Path, content: &Bytes) -> StorageResult<()>;
+    /// Upload content, returning the response of the primary store, with
+    /// the checksums of the content.
+    async fn upload(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        content: &Bytes,
+    ) -> StorageResult<UploadResponse>;
     async fn download(&self, storage: &Storage, path: &Path) -> StorageResult<Bytes>;
     async fn delete(&self, storage: &Storage, path: &Path) -> StorageResult<()>;
     async fn rename(&self, storage: &Storage, from: &Path, to: &Path) -> StorageResult<()>;
@@ -21,6 +32,20 @@ pub trait StorageStrategy: Sync + Send {
     /// Strategies must implement this method to support streaming downloads.
     async fn download_stream(&self, storage: &Storage, path: &Path) -> StorageResult<BytesStream>;
 
+    /// Download a range of bytes of the content as a stream, see
+    /// [`Storage::download_range_stream`].
+    ///
+    /// The default implementation downloads the whole content with
+    /// `download_stream` and drops the bytes outside of the range.
+    async fn download_range_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: Range<u64>,
+    ) -> StorageResult<BytesStream> {
+        Ok(self.download_stream(storage, path).await?.slice(range))
+    }
+
     /// Upload content from a stream for memory-efficient large file handling.
     ///
     /// Strategies must implement this method to support streaming uploads.
@@ -29,5 +54,54 @@ pub trait StorageStrategy: Sync + Send {
         storage: &Storage,
         path: &Path,
         stream: BytesStream,
-    ) -> StorageResult<()>;
+    ) -> StorageResult<UploadResponse>;
+
+    /// List the entries under a prefix, see [`Storage::list`].
+    ///
+    /// The default implementation returns an error.
+    async fn list(
+        &self,
+        _storage: &Storage,
+        _prefix: &Path,
+        _recursive: bool,
+    ) -> StorageResult<ListStream> {
+        Err(StorageError::Any(
+            "listing is not supported by the strategy".into(),
+        ))
+    }
+
+    /// Read the metadata of the content at a path, see [`Storage::head`].
+    ///
+    /// The default implementation returns an error.
+    async fn head(&self, _storage: &Storage, _path: &Path) -> StorageResult<ObjectMetadata> {
+        Err(StorageError::Any(
+            "metadata is not supported by the strategy".into(),
+        ))
+    }
+
+    /// Presign a download request, see [`Storage::presigned_get`].
+    ///
+    /// The default implementation returns
+    /// [`StorageError::PresignUnsupported`].
+    async fn presigned_get(
+        &self,
+        _storage: &Storage,
+        _path: &Path,
+        _expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        Err(StorageError::PresignUnsupported)
+    }
+
+    /// Presign an upload request, see [`Storage::presigned_put`].
+    ///
+    /// The default implementation returns
+    /// [`StorageError::PresignUnsupported`].
+    async fn presigned_put(
+        &self,
+        _storage: &Storage,
+        _path: &Path,
+        _expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        Err(StorageError::PresignUnsupported)
+    }
 }
@@ -2,11 +2,16 @@
 //!
 //! This module provides an implementation of the [`StorageStrategy`] for a
 //! single storage strategy.
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
+use axum::http::Method;
 use bytes::Bytes;
 
-use crate::storage::{strategies::StorageStrategy, Storage, StorageResult};
+use crate::storage::{
+    drivers::{ListStream, ObjectMetadata, PresignedRequest, UploadResponse},
+    strategies::StorageStrategy,
+    Storage, StorageResult,
+};
 
 /// Represents a single storage strategy.
 #[derive(Clone)]
@@ -33,12 +38,16 @@ impl StorageStrategy for SingleStrategy {
     /// # Errors
     ///
     /// Returns a [`StorageResult`] indicating of the operation status.
-    async fn upload(&self, storage: &Storage, path: &Path, content: &Bytes) -> StorageResult<()> {
+    async fn upload(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        content: &Bytes,
+    ) -> StorageResult<UploadResponse> {
         storage
             .as_store_err(&self.primary)?
             .upload(path, content)
-            .await?;
-        Ok(())
+            .await
     }
 
     /// Downloads content
@@ -94,6 +103,24 @@ impl StorageStrategy for SingleStrategy {
         storage.as_store_err(&self.primary)?.get_stream(path).await
     }
 
+    /// Downloads a range of bytes of the content as a stream, see
+    /// [`Storage::download_range_stream`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream
+    async fn download_range_stream(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        range: std::ops::Range<u64>,
+    ) -> StorageResult<super::super::stream::BytesStream> {
+        storage
+            .as_store_err(&self.primary)?
+            .get_range_stream(path, range)
+            .await
+    }
+
     /// Uploads content from a stream to the primary storage
     ///
     /// # Errors
@@ -104,12 +131,69 @@ impl StorageStrategy for SingleStrategy {
         storage: &Storage,
         path: &Path,
         stream: super::super::stream::BytesStream,
-    ) -> StorageResult<()> {
+    ) -> StorageResult<UploadResponse> {
         storage
             .as_store_err(&self.primary)?
             .upload_stream(path, stream)
-            .await?;
-        Ok(())
+            .await
+    }
+
+    /// Presigns a download request for the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presigned_get(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage
+            .presign_with_store(&self.primary, Method::GET, path, expires)
+            .await
+    }
+
+    /// Presigns an upload request for the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the presigned request.
+    async fn presigned_put(
+        &self,
+        storage: &Storage,
+        path: &Path,
+        expires: Duration,
+    ) -> StorageResult<PresignedRequest> {
+        storage
+            .presign_with_store(&self.primary, Method::PUT, path, expires)
+            .await
+    }
+
+    /// Lists the entries of the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the stream of entries.
+    async fn list(
+        &self,
+        storage: &Storage,
+        prefix: &Path,
+        recursive: bool,
+    ) -> StorageResult<ListStream> {
+        storage
+            .as_store_err(&self.primary)?
+            .list(prefix, recursive)
+            .await
+    }
+
+    /// Returns the metadata of the content in the primary storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`StorageResult`] with the metadata.
+    async fn head(&self, storage: &Storage, path: &Path) -> StorageResult<ObjectMetadata> {
+        storage.as_store_err(&self.primary)?.head(path).await
     }
 }
 
@@ -31,6 +31,52 @@ impl BytesStream {
         })
     }
 
+    /// Create a `BytesStream` reading the given range of bytes from an
+    /// `OpenDAL` `Reader`.
+    pub(crate) async fn from_reader_range(
+        reader: Reader,
+        range: std::ops::Range<u64>,
+    ) -> Result<Self, crate::storage::StorageError> {
+        let stream = reader
+            .into_bytes_stream(range)
+            .await
+            .map_err(crate::storage::StorageError::from)?;
+        let mapped_stream = stream
+            .map(|result| result.map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e)));
+
+        Ok(Self {
+            inner: Box::pin(mapped_stream),
+        })
+    }
+
+    /// Returns the stream of the given range of bytes of this stream, reading
+    /// and dropping the bytes before the range.
+    #[must_use]
+    pub fn slice(self, range: std::ops::Range<u64>) -> Self {
+        let stream = self
+            .scan(0u64, move |offset, chunk| {
+                let chunk = match chunk {
+                    Ok(_) if *offset >= range.end => None,
+                    Ok(chunk) => {
+                        let start = *offset;
+                        *offset += chunk.len() as u64;
+                        let from = range.start.saturating_sub(start).min(chunk.len() as u64);
+                        let to = range.end.saturating_sub(start).min(chunk.len() as u64);
+                        #[allow(clippy::cast_possible_truncation)]
+                        Some(Ok(chunk.slice(from as usize..to as usize)))
+                    }
+                    Err(err) => Some(Err(err)),
+                };
+                futures_util::future::ready(chunk)
+            })
+            .filter(|chunk| {
+                futures_util::future::ready(!matches!(chunk, Ok(chunk) if chunk.is_empty()))
+            });
+        Self {
+            inner: Box::pin(stream),
+        }
+    }
+
     /// Collect the entire stream into a single `Bytes` buffer.
     /// This method should be used carefully as it loads the entire content into memory.
     ///
@@ -0,0 +1,411 @@
+//! # Store Synchronization
+//!
+//! Compares the objects of two stores and copies the missing or differing
+//! ones, such as to repair a secondary store of a mirror or backup strategy
+//! after an outage, when its [`FailureMode`](super::strategies::mirror::FailureMode)
+//! tolerated failed writes.
+//!
+//! Objects are compared by size, and by a SHA-256 digest of their content
+//! when [`SyncOptions::checksum`] is set, since the entity tags of different
+//! kinds of stores are not comparable.
+//!
+//! These are used by the `cargo loco storage sync` and `cargo loco storage
+//! verify` commands.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use futures_util::{StreamExt, TryStreamExt};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use super::{drivers::StoreDriver, is_not_found, lifecycle, StorageError, StorageResult};
+
+/// How an object of the target store differs from the source store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Drift {
+    /// The object is missing from the target store.
+    Missing,
+    /// The object of the target store has a different content.
+    Differs,
+    /// The object only exists in the target store.
+    Extra,
+}
+
+impl std::fmt::Display for Drift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing => write!(f, "missing"),
+            Self::Differs => write!(f, "differs"),
+            Self::Extra => write!(f, "extra"),
+        }
+    }
+}
+
+/// An object which is out of sync between two stores.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DriftEntry {
+    pub path: PathBuf,
+    pub drift: Drift,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncOptions {
+    /// Only compares the objects under this directory path, such as `users/`.
+    /// The whole stores are compared when empty.
+    pub prefix: PathBuf,
+    /// Compares the content digests of the objects of the same size, which
+    /// downloads the objects from both stores.
+    pub checksum: bool,
+    /// Deletes the objects which only exist in the target store.
+    pub delete: bool,
+    /// Reports what would be done without changing the target store.
+    pub dry_run: bool,
+}
+
+/// Result of [`sync`].
+#[derive(Debug, Default, Serialize)]
+pub struct SyncReport {
+    /// Objects found out of sync, and handled unless it's a dry run.
+    pub drifts: Vec<DriftEntry>,
+    /// Objects which could not be copied or deleted, with the reason.
+    pub failed: BTreeMap<PathBuf, String>,
+}
+
+/// Returns the objects of the `to` store which are missing, differ, or are
+/// extra compared to the `from` store.
+///
+/// # Errors
+///
+/// When a store could not be listed or an object could not be read.
+pub async fn verify(
+    from: &dyn StoreDriver,
+    to: &dyn StoreDriver,
+    options: &SyncOptions,
+) -> StorageResult<Vec<DriftEntry>> {
+    let source = list_files(from, &options.prefix).await?;
+    let mut target = list_files(to, &options.prefix).await?;
+
+    let mut drifts = vec![];
+    for (path, from_size) in source {
+        let drift = match target.remove(&path) {
+            Some(to_size) => {
+                compare(from, to, &path, (from_size, to_size), options.checksum).await?
+            }
+            None => Some(Drift::Missing),
+        };
+        if let Some(drift) = drift {
+            drifts.push(DriftEntry { path, drift });
+        }
+    }
+    drifts.extend(target.into_keys().map(|path| DriftEntry {
+        path,
+        drift: Drift::Extra,
+    }));
+    drifts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(drifts)
+}
+
+/// Copies the objects missing or differing in the `to` store from the `from`
+/// store, and deletes the extra ones when [`SyncOptions::delete`] is set.
+///
+/// A failure to copy or delete an object doesn't stop the synchronization,
+/// failed objects are returned in [`SyncReport::failed`].
+///
+/// # Errors
+///
+/// When a store could not be listed or an object could not be compared.
+pub async fn sync(
+    from: &dyn StoreDriver,
+    to: &dyn StoreDriver,
+    options: &SyncOptions,
+) -> StorageResult<SyncReport> {
+    let mut report = SyncReport {
+        drifts: verify(from, to, options).await?,
+        ..SyncReport::default()
+    };
+    if options.dry_run {
+        return Ok(report);
+    }
+
+    for entry in &report.drifts {
+        let result = match entry.drift {
+            Drift::Missing | Drift::Differs => copy(from, to, &entry.path).await,
+            Drift::Extra if options.delete => to.delete(&entry.path).await,
+            Drift::Extra => continue,
+        };
+        if let Err(err) = result {
+            report.failed.insert(entry.path.clone(), err.to_string());
+        }
+    }
+    Ok(report)
+}
+
+/// Lists the paths of the files under the prefix, with the size returned by
+/// the listing. Expiry markers are left out: they record the version of the
+/// object in the store they were set on.
+async fn list_files(
+    store: &dyn StoreDriver,
+    prefix: &Path,
+) -> StorageResult<BTreeMap<PathBuf, u64>> {
+    store
+        .list(prefix, true)
+        .await?
+        .try_filter_map(|entry| async move {
+            Ok((!entry.is_dir && !lifecycle::is_marker(&entry.path))
+                .then_some((entry.path, entry.metadata.size)))
+        })
+        .try_collect()
+        .await
+}
+
+/// Compares an object listed in both stores, which may have been changed or
+/// deleted since the listing.
+async fn compare(
+    from: &dyn StoreDriver,
+    to: &dyn StoreDriver,
+    path: &Path,
+    listed_sizes: (u64, u64),
+    checksum: bool,
+) -> StorageResult<Option<Drift>> {
+    let sizes = futures_util::try_join!(
+        size(from, path, listed_sizes.0),
+        size(to, path, listed_sizes.1)
+    )?;
+    let drift = match sizes {
+        (Some(from_size), Some(to_size)) if from_size != to_size => Some(Drift::Differs),
+        (Some(_), Some(_)) if checksum => {
+            match futures_util::try_join!(digest(from, path), digest(to, path))? {
+                (Some(_), None) => Some(Drift::Missing),
+                (None, Some(_)) => Some(Drift::Extra),
+                (from_digest, to_digest) => (from_digest != to_digest).then_some(Drift::Differs),
+            }
+        }
+        (Some(_), Some(_)) | (None, None) => None,
+        (Some(_), None) => Some(Drift::Missing),
+        (None, Some(_)) => Some(Drift::Extra),
+    };
+    Ok(drift)
+}
+
+/// Returns the size of an object, or `None` when it doesn't exist anymore.
+///
+/// Listings don't return the size for every store, so a listed size of zero
+/// is checked with a `head` request.
+async fn size(store: &dyn StoreDriver, path: &Path, listed: u64) -> StorageResult<Option<u64>> {
+    if listed > 0 {
+        return Ok(Some(listed));
+    }
+    found(store.head(path).await.map(|metadata| metadata.size))
+}
+
+/// Maps a missing object to `None`.
+fn found<T>(result: StorageResult<T>) -> StorageResult<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(err) if is_not_found(&err) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Returns the digest of an object, or `None` when it doesn't exist anymore.
+async fn digest(store: &dyn StoreDriver, path: &Path) -> StorageResult<Option<Vec<u8>>> {
+    let Some(mut stream) = found(store.get_stream(path).await)? else {
+        return Ok(None);
+    };
+    let mut hasher = Sha256::new();
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => hasher.update(chunk),
+            // readers may only find out the object is gone on the first read
+            Err(err) if is_io_not_found(&err) => return Ok(None),
+            Err(err) => return Err(StorageError::Any(Box::new(err))),
+        }
+    }
+    Ok(Some(hasher.finalize().to_vec()))
+}
+
+/// Whether a stream error is caused by a missing object, looking through the
+/// errors it wraps.
+fn is_io_not_found(err: &std::io::Error) -> bool {
+    let mut err = err;
+    loop {
+        if err.kind() == std::io::ErrorKind::NotFound {
+            return true;
+        }
+        let Some(inner) = err.get_ref() else {
+            return false;
+        };
+        if let Some(inner) = inner.downcast_ref::<std::io::Error>() {
+            err = inner;
+        } else {
+            return inner
+                .downcast_ref::<opendal::Error>()
+                .is_some_and(|inner| inner.kind() == opendal::ErrorKind::NotFound);
+        }
+    }
+}
+
+async fn copy(from: &dyn StoreDriver, to: &dyn StoreDriver, path: &Path) -> StorageResult<()> {
+    to.upload_stream(path, from.get_stream(path).await?).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::Bytes;
+
+    use super::*;
+    use crate::storage::drivers::mem;
+
+    async fn stores() -> (Box<dyn StoreDriver>, Box<dyn StoreDriver>) {
+        let from = mem::new();
+        let to = mem::new();
+        for (path, content) in [
+            ("users/1.txt", "one"),
+            ("users/2.txt", "two"),
+            ("users/3.txt", "three"),
+            ("posts/1.txt", "post"),
+        ] {
+            from.upload(Path::new(path), &Bytes::from(content))
+                .await
+                .unwrap();
+        }
+        for (path, content) in [
+            ("users/1.txt", "one"),
+            // same size, other content
+            ("users/2.txt", "TWO"),
+            ("users/3.txt", "3"),
+            ("users/4.txt", "four"),
+        ] {
+            to.upload(Path::new(path), &Bytes::from(content))
+                .await
+                .unwrap();
+        }
+        (from, to)
+    }
+
+    fn drifts(drifts: &[DriftEntry]) -> Vec<(String, Drift)> {
+        drifts
+            .iter()
+            .map(|entry| (entry.path.display().to_string(), entry.drift))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn can_verify() {
+        let (from, to) = stores().await;
+
+        let report = verify(from.as_ref(), to.as_ref(), &SyncOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            drifts(&report),
+            vec![
+                ("posts/1.txt".to_string(), Drift::Missing),
+                ("users/3.txt".to_string(), Drift::Differs),
+                ("users/4.txt".to_string(), Drift::Extra),
+            ]
+        );
+
+        let options = SyncOptions {
+            prefix: PathBuf::from("users/"),
+            checksum: true,
+            ..SyncOptions::default()
+        };
+        let report = verify(from.as_ref(), to.as_ref(), &options).await.unwrap();
+        assert_eq!(
+            drifts(&report),
+            vec![
+                ("users/2.txt".to_string(), Drift::Differs),
+                ("users/3.txt".to_string(), Drift::Differs),
+                ("users/4.txt".to_string(), Drift::Extra),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_objects_deleted_after_listing() {
+        let (from, to) = stores().await;
+        let path = Path::new("users/1.txt");
+        to.delete(path).await.unwrap();
+
+        // as listed before the deletion, with sizes only known from `head`
+        for checksum in [false, true] {
+            assert_eq!(
+                compare(from.as_ref(), to.as_ref(), path, (0, 0), checksum)
+                    .await
+                    .unwrap(),
+                Some(Drift::Missing)
+            );
+            assert_eq!(
+                compare(to.as_ref(), from.as_ref(), path, (0, 0), checksum)
+                    .await
+                    .unwrap(),
+                Some(Drift::Extra)
+            );
+        }
+        // listed sizes are trusted, the digest finds the deletion
+        assert_eq!(
+            compare(from.as_ref(), to.as_ref(), path, (3, 3), true)
+                .await
+                .unwrap(),
+            Some(Drift::Missing)
+        );
+    }
+
+    #[tokio::test]
+    async fn can_sync() {
+        let (from, to) = stores().await;
+        let options = SyncOptions {
+            checksum: true,
+            ..SyncOptions::default()
+        };
+
+        let report = sync(from.as_ref(), to.as_ref(), &options).await.unwrap();
+        assert_eq!(report.drifts.len(), 4);
+        assert!(report.failed.is_empty());
+
+        assert_eq!(
+            drifts(&verify(from.as_ref(), to.as_ref(), &options).await.unwrap()),
+            vec![("users/4.txt".to_string(), Drift::Extra)]
+        );
+        assert_eq!(
+            to.get(Path::new("users/2.txt"))
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap(),
+            Bytes::from("two")
+        );
+
+        let options = SyncOptions {
+            delete: true,
+            ..options
+        };
+        sync(from.as_ref(), to.as_ref(), &options).await.unwrap();
+        assert!(verify(from.as_ref(), to.as_ref(), &options)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn dry_run_keeps_target() {
+        let (from, to) = stores().await;
+        let options = SyncOptions {
+            delete: true,
+            dry_run: true,
+            ..SyncOptions::default()
+        };
+
+        let report = sync(from.as_ref(), to.as_ref(), &options).await.unwrap();
+        assert_eq!(report.drifts.len(), 3);
+        assert_eq!(
+            verify(from.as_ref(), to.as_ref(), &options).await.unwrap(),
+            report.drifts
+        );
+    }
+}
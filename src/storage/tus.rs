@@ -0,0 +1,1152 @@
+//! # Resumable Uploads
+//!
+//! Routes implementing the [tus](https://tus.io/protocols/resumable-upload)
+//! resumable upload protocol 1.0.0, so clients on unreliable connections can
+//! upload large files in several requests and resume an interrupted upload
+//! from the last byte received.
+//!
+//! Every `PATCH` request of a client is stored as a part of the upload in
+//! `ctx.storage`, under `tmp/tus/{id}/` by default, next to the state of the
+//! upload. Once all the bytes are received, the parts are streamed into a
+//! single file with [`Storage::upload_stream`], under `uploads/` by default,
+//! and deleted. Since only complete requests are stored, clients should send
+//! the file in chunks (the `chunkSize` option of `tus-js-client`) so an
+//! interruption only loses the current chunk.
+//!
+//! Supported extensions are `creation`, `creation-defer-length`,
+//! `termination` and `expiration`.
+//!
+//! ```rust
+//! use loco_rs::{controller::AppRoutes, storage::tus::Tus};
+//!
+//! fn routes() -> AppRoutes {
+//!     AppRoutes::with_default_routes()
+//!         .add_route(Tus::new().max_size(10 * 1024 * 1024 * 1024).routes("/api/uploads"))
+//! }
+//! ```
+//!
+//! Once the upload is complete, the client sends its URL to the application,
+//! which claims the stored file with [`Tus::finish`].
+//!
+//! Expired uploads are deleted when a request reaches them, and by
+//! [`Tus::purge_expired`], which should run periodically (such as from a
+//! scheduled task) so abandoned uploads don't pile up in the storage.
+//!
+//! A `PATCH` or `DELETE` request locks its upload in `ctx.cache`, so requests
+//! for the same upload are rejected with `423 Locked` across instances. With
+//! the `Null` cache, uploads are only locked within the process.
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, Path as UrlPath, Request, State},
+    http::{header, response::Builder, HeaderMap, HeaderName, StatusCode},
+    response::Response,
+    routing::{head, post},
+};
+use base64::Engine;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    is_not_found, serve::http_date, stream::BytesStream, Storage, StorageError, StorageResult,
+};
+use crate::{app::AppContext, cache::Cache, controller::Routes, Result};
+
+/// Version of the protocol implemented by the routes.
+pub const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,creation-defer-length,termination,expiration";
+const OFFSET_CONTENT_TYPE: &str = "application/offset+octet-stream";
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_DEFER_LENGTH: HeaderName = HeaderName::from_static("upload-defer-length");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_EXPIRES: HeaderName = HeaderName::from_static("upload-expires");
+
+/// State of a resumable upload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TusUpload {
+    pub id: String,
+    /// Size of the file, unknown until sent by the client when it deferred
+    /// the length.
+    pub length: Option<u64>,
+    /// Number of bytes received.
+    pub offset: u64,
+    /// Metadata sent by the client, such as `filename` and `filetype`.
+    pub metadata: BTreeMap<String, String>,
+    /// Key of the file in the storage, once the upload is complete.
+    pub key: Option<PathBuf>,
+    /// Time after which the upload can't be resumed.
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Offsets of the stored parts.
+    parts: Vec<u64>,
+}
+
+impl TusUpload {
+    /// Whether all the bytes of the file were received and stored.
+    #[must_use]
+    pub fn is_complete(&self) -> bool {
+        self.key.is_some()
+    }
+
+    /// Whether the upload can't be resumed, or its file claimed, anymore.
+    fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at <= Utc::now())
+    }
+}
+
+/// Configuration of the resumable upload routes.
+#[derive(Debug, Clone)]
+pub struct Tus {
+    parts_prefix: PathBuf,
+    key_prefix: PathBuf,
+    max_size: Option<u64>,
+    expires_after: Option<Duration>,
+    lock_ttl: Duration,
+    /// Uploads currently receiving a request in this process.
+    locks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Default for Tus {
+    fn default() -> Self {
+        Self {
+            parts_prefix: PathBuf::from("tmp/tus"),
+            key_prefix: PathBuf::from("uploads"),
+            max_size: None,
+            expires_after: Some(Duration::from_secs(24 * 3600)),
+            lock_ttl: Duration::from_secs(600),
+            locks: Arc::default(),
+        }
+    }
+}
+
+/// Releases the lock of an upload when dropped.
+struct UploadLock {
+    locks: Arc<Mutex<HashSet<String>>>,
+    id: String,
+    /// Cache holding the lock shared with other instances.
+    cache: Option<Arc<Cache>>,
+}
+
+impl UploadLock {
+    /// Releases the lock, before the response is sent so the next request
+    /// of the client finds the upload unlocked.
+    async fn release(mut self) {
+        if let Some(cache) = self.cache.take() {
+            let key = lock_key(&self.id);
+            if let Err(err) = cache.remove(&key).await {
+                tracing::warn!(key, error = %err, "could not release upload lock");
+            }
+        }
+    }
+}
+
+impl Drop for UploadLock {
+    fn drop(&mut self) {
+        // a cancelled request releases the cache lock in the background
+        if let Some(cache) = self.cache.take() {
+            let key = lock_key(&self.id);
+            tokio::spawn(async move {
+                if let Err(err) = cache.remove(&key).await {
+                    tracing::warn!(key, error = %err, "could not release upload lock");
+                }
+            });
+        }
+        self.locks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(&self.id);
+    }
+}
+
+fn lock_key(id: &str) -> String {
+    format!("loco:tus:lock:{id}")
+}
+
+impl Tus {
+    /// Creates the configuration, storing parts under `tmp/tus` and complete
+    /// files under `uploads`, with uploads expiring after 24 hours.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the key prefix of the parts and state of the uploads in progress.
+    #[must_use]
+    pub fn parts_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.parts_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the key prefix of the complete files.
+    #[must_use]
+    pub fn key_prefix(mut self, prefix: impl Into<PathBuf>) -> Self {
+        self.key_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the maximum size of a file in bytes.
+    #[must_use]
+    pub const fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Sets how long after its creation an upload can be resumed, or `None`
+    /// for uploads which never expire.
+    #[must_use]
+    pub const fn expires_after(mut self, expires_after: Option<Duration>) -> Self {
+        self.expires_after = expires_after;
+        self
+    }
+
+    /// Sets how long a request can hold the lock of an upload, after which
+    /// the lock is released even if the instance holding it stopped. It must
+    /// be longer than a `PATCH` request of a chunk takes.
+    #[must_use]
+    pub const fn lock_ttl(mut self, lock_ttl: Duration) -> Self {
+        self.lock_ttl = lock_ttl;
+        self
+    }
+
+    /// Returns the key of a complete upload and deletes its state, once the
+    /// application took over the file. Returns `None` when the upload is
+    /// missing, expired or not complete yet.
+    ///
+    /// A complete upload expires `expires_after` its completion, after which
+    /// its file is deleted by [`Tus::purge_expired`] unless claimed.
+    ///
+    /// # Errors
+    ///
+    /// When the state of the upload could not be read or deleted.
+    pub async fn finish(&self, storage: &Storage, id: &str) -> StorageResult<Option<PathBuf>> {
+        let Some(upload) = self.upload(storage, id).await? else {
+            return Ok(None);
+        };
+        if upload.is_expired() {
+            self.remove(storage, &upload).await?;
+            return Ok(None);
+        }
+        if upload.key.is_some() {
+            storage.delete(&self.state_path(id)).await?;
+        }
+        Ok(upload.key)
+    }
+
+    /// Deletes the expired uploads with their parts, and the file of those
+    /// completed but never claimed with [`Tus::finish`], along with parts
+    /// left without the state of their upload. Returns the number of deleted
+    /// uploads.
+    ///
+    /// Uploads receiving a request are skipped.
+    ///
+    /// # Errors
+    ///
+    /// When the uploads could not be listed, read or deleted.
+    pub async fn purge_expired(&self, ctx: &AppContext) -> StorageResult<u64> {
+        let mut files: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+        let mut entries = match ctx.storage.list(&self.parts_prefix, true).await {
+            Ok(entries) => entries,
+            Err(err) if is_not_found(&err) => return Ok(0),
+            Err(err) => return Err(err),
+        };
+        while let Some(entry) = entries.try_next().await? {
+            let id = entry
+                .path
+                .strip_prefix(&self.parts_prefix)
+                .ok()
+                .and_then(|path| path.iter().next())
+                .and_then(|id| id.to_str())
+                .filter(|id| is_valid_id(id));
+            if let (Some(id), false) = (id, entry.is_dir) {
+                files.entry(id.to_string()).or_default().push(entry.path);
+            }
+        }
+
+        let mut purged = 0;
+        for (id, files) in files {
+            if !files.contains(&self.state_path(&id)) {
+                for file in files {
+                    ctx.storage.delete(&file).await?;
+                }
+                continue;
+            }
+            let Some(lock) = self.lock(&ctx.cache, &id).await else {
+                continue;
+            };
+            let removed = match self.upload(&ctx.storage, &id).await {
+                Ok(Some(upload)) if upload.is_expired() => {
+                    self.remove(&ctx.storage, &upload).await.map(|()| true)
+                }
+                Ok(_) => Ok(false),
+                Err(err) => Err(err),
+            };
+            lock.release().await;
+            if removed? {
+                purged += 1;
+            }
+        }
+        Ok(purged)
+    }
+
+    /// Returns the upload with the given id, which is the last segment of
+    /// its URL.
+    ///
+    /// # Errors
+    ///
+    /// When the state of the upload could not be read.
+    pub async fn upload(&self, storage: &Storage, id: &str) -> StorageResult<Option<TusUpload>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+        match storage.download::<String>(&self.state_path(id)).await {
+            Ok(state) => Ok(Some(
+                serde_json::from_str(&state).map_err(|err| StorageError::Any(err.into()))?,
+            )),
+            Err(err) if is_not_found(&err) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Routes of the resumable uploads at `prefix`, which clients use as
+    /// their upload endpoint.
+    #[must_use]
+    pub fn routes(self, prefix: &str) -> Routes {
+        let (create, status, append, terminate) =
+            (self.clone(), self.clone(), self.clone(), self.clone());
+        let max_size = self.max_size;
+        Routes::new()
+            .prefix(prefix)
+            .add(
+                "/",
+                post(
+                    move |State(ctx): State<AppContext>,
+                          OriginalUri(uri): OriginalUri,
+                          headers: HeaderMap| async move {
+                        create.create(&ctx, uri.path(), &headers).await
+                    },
+                )
+                .options(move || async move { capabilities(max_size) }),
+            )
+            .add(
+                "/{id}",
+                head(
+                    move |State(ctx): State<AppContext>,
+                          UrlPath(id): UrlPath<String>,
+                          headers: HeaderMap| async move {
+                        status.status(&ctx, &id, &headers).await
+                    },
+                )
+                .patch(
+                    move |State(ctx): State<AppContext>,
+                          UrlPath(id): UrlPath<String>,
+                          request: Request| async move {
+                        append.append(&ctx, &id, request).await
+                    },
+                )
+                .delete(
+                    move |State(ctx): State<AppContext>,
+                          UrlPath(id): UrlPath<String>,
+                          headers: HeaderMap| async move {
+                        terminate.terminate(&ctx, &id, &headers).await
+                    },
+                )
+                .options(move || async move { capabilities(max_size) }),
+            )
+    }
+
+    fn state_path(&self, id: &str) -> PathBuf {
+        self.parts_prefix.join(id).join("state.json")
+    }
+
+    fn part_path(&self, id: &str, offset: u64) -> PathBuf {
+        self.parts_prefix.join(id).join(format!("{offset:020}"))
+    }
+
+    async fn save(&self, storage: &Storage, upload: &TusUpload) -> Result<()> {
+        storage
+            .upload(
+                &self.state_path(&upload.id),
+                &Bytes::from(serde_json::to_vec(upload)?),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Deletes the parts, state and file of an upload.
+    async fn remove(&self, storage: &Storage, upload: &TusUpload) -> StorageResult<()> {
+        for offset in &upload.parts {
+            storage.delete(&self.part_path(&upload.id, *offset)).await?;
+        }
+        storage.delete(&self.state_path(&upload.id)).await?;
+        if let Some(key) = &upload.key {
+            storage.delete(key).await?;
+        }
+        Ok(())
+    }
+
+    /// Returns the upload unless it's missing or expired.
+    async fn find(&self, storage: &Storage, id: &str) -> Result<Option<TusUpload>> {
+        match self.upload(storage, id).await? {
+            Some(upload) if upload.is_expired() => {
+                self.remove(storage, &upload).await?;
+                Ok(None)
+            }
+            upload => Ok(upload),
+        }
+    }
+
+    /// Locks an upload in this process, then in the cache so other instances
+    /// see the lock. Returns `None` when the upload is already locked.
+    async fn lock(&self, cache: &Arc<Cache>, id: &str) -> Option<UploadLock> {
+        let inserted = self
+            .locks
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(id.to_string());
+        if !inserted {
+            return None;
+        }
+        let mut lock = UploadLock {
+            locks: self.locks.clone(),
+            id: id.to_string(),
+            cache: None,
+        };
+        // the first increment of the key takes the lock
+        match cache.increment(&lock_key(id), 1, Some(self.lock_ttl)).await {
+            Ok(1) => lock.cache = Some(cache.clone()),
+            Ok(_) => return None,
+            Err(err) => {
+                tracing::debug!(id, error = %err, "could not lock upload in the cache, locking it in this process only");
+            }
+        }
+        Some(lock)
+    }
+
+    async fn create(&self, ctx: &AppContext, path: &str, headers: &HeaderMap) -> Result<Response> {
+        if let Some(response) = check_version(headers) {
+            return Ok(response);
+        }
+        let length = match (
+            header_u64(headers, &UPLOAD_LENGTH),
+            headers.get(&UPLOAD_DEFER_LENGTH),
+        ) {
+            (Some(Ok(length)), None) => Some(length),
+            (None, Some(defer)) if defer == "1" => None,
+            _ => return Ok(status(StatusCode::BAD_REQUEST).body(Body::empty())?),
+        };
+        if self.exceeds_max_size(length.unwrap_or_default()) {
+            return Ok(status(StatusCode::PAYLOAD_TOO_LARGE).body(Body::empty())?);
+        }
+        let Some(metadata) = headers
+            .get(&UPLOAD_METADATA)
+            .map_or(Some(BTreeMap::new()), |metadata| {
+                metadata.to_str().ok().and_then(parse_metadata)
+            })
+        else {
+            return Ok(status(StatusCode::BAD_REQUEST).body(Body::empty())?);
+        };
+
+        let mut upload = TusUpload {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            length,
+            offset: 0,
+            metadata,
+            key: None,
+            expires_at: self
+                .expires_after
+                .and_then(|expires_after| chrono::Duration::from_std(expires_after).ok())
+                .map(|expires_after| Utc::now() + expires_after),
+            parts: vec![],
+        };
+        if upload.length == Some(0) {
+            self.complete(&ctx.storage, &mut upload).await?;
+        }
+        self.save(&ctx.storage, &upload).await?;
+
+        let location = format!("{}/{}", path.trim_end_matches('/'), upload.id);
+        Ok(with_expiry(status(StatusCode::CREATED), &upload)
+            .header(header::LOCATION, location)
+            .body(Body::empty())?)
+    }
+
+    async fn status(&self, ctx: &AppContext, id: &str, headers: &HeaderMap) -> Result<Response> {
+        if let Some(response) = check_version(headers) {
+            return Ok(response);
+        }
+        let Some(upload) = self.find(&ctx.storage, id).await? else {
+            return Ok(status(StatusCode::NOT_FOUND).body(Body::empty())?);
+        };
+
+        let mut response = with_expiry(status(StatusCode::OK), &upload)
+            .header(UPLOAD_OFFSET, upload.offset)
+            .header(header::CACHE_CONTROL, "no-store");
+        response = match upload.length {
+            Some(length) => response.header(UPLOAD_LENGTH, length),
+            None => response.header(UPLOAD_DEFER_LENGTH, 1),
+        };
+        if !upload.metadata.is_empty() {
+            response = response.header(UPLOAD_METADATA, encode_metadata(&upload.metadata));
+        }
+        Ok(response.body(Body::empty())?)
+    }
+
+    async fn append(&self, ctx: &AppContext, id: &str, request: Request) -> Result<Response> {
+        let headers = request.headers();
+        if let Some(response) = check_version(headers) {
+            return Ok(response);
+        }
+        if headers
+            .get(header::CONTENT_TYPE)
+            .map_or(true, |content_type| content_type != OFFSET_CONTENT_TYPE)
+        {
+            return Ok(status(StatusCode::UNSUPPORTED_MEDIA_TYPE).body(Body::empty())?);
+        }
+        let Some(Ok(offset)) = header_u64(headers, &UPLOAD_OFFSET) else {
+            return Ok(status(StatusCode::BAD_REQUEST).body(Body::empty())?);
+        };
+        let Some(lock) = self.lock(&ctx.cache, id).await else {
+            return Ok(status(StatusCode::LOCKED).body(Body::empty())?);
+        };
+        let response = self.append_locked(ctx, id, offset, request).await;
+        lock.release().await;
+        response
+    }
+
+    async fn append_locked(
+        &self,
+        ctx: &AppContext,
+        id: &str,
+        offset: u64,
+        request: Request,
+    ) -> Result<Response> {
+        let headers = request.headers();
+        let Some(mut upload) = self.find(&ctx.storage, id).await? else {
+            return Ok(status(StatusCode::NOT_FOUND).body(Body::empty())?);
+        };
+        if offset != upload.offset || upload.is_complete() {
+            return Ok(status(StatusCode::CONFLICT).body(Body::empty())?);
+        }
+        match (upload.length, header_u64(headers, &UPLOAD_LENGTH)) {
+            (_, None) => {}
+            (None, Some(Ok(length))) if length >= upload.offset => {
+                if self.exceeds_max_size(length) {
+                    return Ok(status(StatusCode::PAYLOAD_TOO_LARGE).body(Body::empty())?);
+                }
+                upload.length = Some(length);
+            }
+            (Some(length), Some(Ok(sent))) if length == sent => {}
+            _ => return Ok(status(StatusCode::BAD_REQUEST).body(Body::empty())?),
+        }
+
+        // bytes the upload can still receive
+        let remaining = upload
+            .length
+            .or(self.max_size)
+            .map(|length| length - upload.offset);
+        let received = Arc::new(AtomicU64::new(0));
+        let counter = received.clone();
+        let body = request.into_body().into_data_stream().map(move |chunk| {
+            let chunk = chunk.map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+            let total =
+                counter.fetch_add(chunk.len() as u64, Ordering::Relaxed) + chunk.len() as u64;
+            if remaining.is_some_and(|remaining| total > remaining) {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "upload exceeds its length",
+                ));
+            }
+            Ok(chunk)
+        });
+
+        let part = self.part_path(id, offset);
+        if let Err(err) = ctx
+            .storage
+            .upload_stream(&part, BytesStream::from_body_stream(body))
+            .await
+        {
+            if let Err(err) = ctx.storage.delete(&part).await {
+                tracing::warn!(path = %part.display(), error = %err, "could not delete partial part");
+            }
+            let received = received.load(Ordering::Relaxed);
+            if remaining.is_some_and(|remaining| received > remaining) {
+                return Ok(status(StatusCode::PAYLOAD_TOO_LARGE).body(Body::empty())?);
+            }
+            return Err(err.into());
+        }
+
+        let received = received.load(Ordering::Relaxed);
+        if received > 0 {
+            upload.parts.push(offset);
+            upload.offset += received;
+        } else if let Err(err) = ctx.storage.delete(&part).await {
+            tracing::warn!(path = %part.display(), error = %err, "could not delete empty part");
+        }
+        if upload.length == Some(upload.offset) {
+            self.complete(&ctx.storage, &mut upload).await?;
+        }
+        self.save(&ctx.storage, &upload).await?;
+
+        Ok(with_expiry(status(StatusCode::NO_CONTENT), &upload)
+            .header(UPLOAD_OFFSET, upload.offset)
+            .body(Body::empty())?)
+    }
+
+    async fn terminate(&self, ctx: &AppContext, id: &str, headers: &HeaderMap) -> Result<Response> {
+        if let Some(response) = check_version(headers) {
+            return Ok(response);
+        }
+        let Some(lock) = self.lock(&ctx.cache, id).await else {
+            return Ok(status(StatusCode::LOCKED).body(Body::empty())?);
+        };
+        let removed = match self.upload(&ctx.storage, id).await {
+            Ok(Some(upload)) => self.remove(&ctx.storage, &upload).await.map(|()| true),
+            Ok(None) => Ok(false),
+            Err(err) => Err(err),
+        };
+        lock.release().await;
+        if removed? {
+            Ok(status(StatusCode::NO_CONTENT).body(Body::empty())?)
+        } else {
+            Ok(status(StatusCode::NOT_FOUND).body(Body::empty())?)
+        }
+    }
+
+    /// Streams the parts of a fully received upload into its file, then
+    /// deletes them.
+    async fn complete(&self, storage: &Arc<Storage>, upload: &mut TusUpload) -> Result<()> {
+        let mut key = upload.id.clone();
+        if let Some(extension) = upload
+            .metadata
+            .get("filename")
+            .and_then(|filename| Path::new(filename).extension())
+            .and_then(|extension| extension.to_str())
+        {
+            key = format!("{key}.{extension}");
+        }
+        let key = self.key_prefix.join(key);
+
+        let parts: Vec<PathBuf> = upload
+            .parts
+            .iter()
+            .map(|offset| self.part_path(&upload.id, *offset))
+            .collect();
+        let reader = storage.clone();
+        let content = futures_util::stream::iter(parts.clone())
+            .then(move |part| {
+                let storage = reader.clone();
+                async move { storage.download_stream(&part).await }
+            })
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))
+            .try_flatten();
+        storage
+            .upload_stream(&key, BytesStream::from_body_stream(content))
+            .await?;
+
+        for part in &parts {
+            if let Err(err) = storage.delete(part).await {
+                tracing::warn!(path = %part.display(), error = %err, "could not delete upload part");
+            }
+        }
+        upload.parts.clear();
+        upload.key = Some(key);
+        // the application has as long to claim the file with `Tus::finish`,
+        // after which it's deleted
+        upload.expires_at = self
+            .expires_after
+            .and_then(|expires_after| chrono::Duration::from_std(expires_after).ok())
+            .map(|expires_after| Utc::now() + expires_after);
+        Ok(())
+    }
+
+    fn exceeds_max_size(&self, length: u64) -> bool {
+        self.max_size.is_some_and(|max_size| length > max_size)
+    }
+}
+
+/// Ids are generated as UUIDs without hyphens.
+fn is_valid_id(id: &str) -> bool {
+    id.len() == 32 && id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+fn status(status: StatusCode) -> Builder {
+    Response::builder()
+        .status(status)
+        .header(TUS_RESUMABLE, TUS_VERSION)
+}
+
+fn with_expiry(builder: Builder, upload: &TusUpload) -> Builder {
+    match upload.expires_at {
+        Some(expires_at) if !upload.is_complete() => {
+            builder.header(UPLOAD_EXPIRES, http_date(expires_at))
+        }
+        _ => builder,
+    }
+}
+
+/// Answers requests of clients using another version of the protocol.
+fn check_version(headers: &HeaderMap) -> Option<Response> {
+    if headers
+        .get(&TUS_RESUMABLE)
+        .is_some_and(|version| version == TUS_VERSION)
+    {
+        return None;
+    }
+    Response::builder()
+        .status(StatusCode::PRECONDITION_FAILED)
+        .header(TUS_VERSION_HEADER, TUS_VERSION)
+        .body(Body::empty())
+        .ok()
+}
+
+fn capabilities(max_size: Option<u64>) -> Result<Response> {
+    let mut response = status(StatusCode::NO_CONTENT)
+        .header(TUS_VERSION_HEADER, TUS_VERSION)
+        .header(TUS_EXTENSION, TUS_EXTENSIONS);
+    if let Some(max_size) = max_size {
+        response = response.header(TUS_MAX_SIZE, max_size);
+    }
+    Ok(response.body(Body::empty())?)
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Option<std::result::Result<u64, ()>> {
+    headers.get(name).map(|value| {
+        value
+            .to_str()
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .ok_or(())
+    })
+}
+
+/// Parses an `Upload-Metadata` header, made of comma separated keys each
+/// followed by a base64 encoded value, when the key has a value.
+fn parse_metadata(header: &str) -> Option<BTreeMap<String, String>> {
+    let mut metadata = BTreeMap::new();
+    for pair in header
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+    {
+        let (key, value) = match pair.split_once(' ') {
+            Some((key, value)) => {
+                let value = base64::engine::general_purpose::STANDARD
+                    .decode(value.trim())
+                    .ok()?;
+                (key, String::from_utf8_lossy(&value).into_owned())
+            }
+            None => (pair, String::new()),
+        };
+        if metadata.insert(key.to_string(), value).is_some() {
+            return None;
+        }
+    }
+    Some(metadata)
+}
+
+fn encode_metadata(metadata: &BTreeMap<String, String>) -> String {
+    metadata
+        .iter()
+        .map(|(key, value)| {
+            if value.is_empty() {
+                key.clone()
+            } else {
+                format!(
+                    "{key} {}",
+                    base64::engine::general_purpose::STANDARD.encode(value)
+                )
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{Method, Request};
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::storage::drivers;
+
+    struct Client {
+        router: axum::Router,
+        storage: Arc<Storage>,
+    }
+
+    impl Client {
+        async fn new(tus: Tus) -> Self {
+            let mut ctx = crate::tests_cfg::app::get_app_context().await;
+            ctx.storage = Storage::single(drivers::mem::new()).into();
+            let storage = ctx.storage.clone();
+            let router = tus
+                .routes("/uploads")
+                .handlers
+                .into_iter()
+                .fold(axum::Router::new(), |router, handler| {
+                    let uri = format!("/uploads{}", handler.uri);
+                    router.route(uri.trim_end_matches('/'), handler.method)
+                })
+                .with_state(ctx);
+            Self { router, storage }
+        }
+
+        async fn send(
+            &self,
+            method: Method,
+            uri: &str,
+            headers: &[(&str, &str)],
+            body: &'static str,
+        ) -> (StatusCode, HeaderMap) {
+            let mut request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header(TUS_RESUMABLE, TUS_VERSION);
+            for (name, value) in headers {
+                request = request.header(*name, *value);
+            }
+            let response = self
+                .router
+                .clone()
+                .oneshot(request.body(Body::from(body)).unwrap())
+                .await
+                .unwrap();
+            (response.status(), response.headers().clone())
+        }
+
+        async fn create(&self, headers: &[(&str, &str)]) -> String {
+            let (status, headers) = self.send(Method::POST, "/uploads", headers, "").await;
+            assert_eq!(status, StatusCode::CREATED);
+            headers[header::LOCATION].to_str().unwrap().to_string()
+        }
+
+        async fn patch(
+            &self,
+            location: &str,
+            offset: &str,
+            headers: &[(&str, &str)],
+            body: &'static str,
+        ) -> (StatusCode, HeaderMap) {
+            let mut headers = headers.to_vec();
+            headers.push(("content-type", OFFSET_CONTENT_TYPE));
+            headers.push(("upload-offset", offset));
+            self.send(Method::PATCH, location, &headers, body).await
+        }
+    }
+
+    fn id(location: &str) -> &str {
+        location.rsplit('/').next().unwrap()
+    }
+
+    #[test]
+    fn can_parse_metadata() {
+        let metadata =
+            parse_metadata("filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential")
+                .unwrap();
+        assert_eq!(metadata["filename"], "world_domination_plan.pdf");
+        assert_eq!(metadata["is_confidential"], "");
+        assert_eq!(
+            encode_metadata(&metadata),
+            "filename d29ybGRfZG9taW5hdGlvbl9wbGFuLnBkZg==,is_confidential"
+        );
+
+        assert!(parse_metadata("filename not-base64!").is_none());
+        assert!(parse_metadata("filename,filename").is_none());
+    }
+
+    #[tokio::test]
+    async fn can_discover_capabilities() {
+        let client = Client::new(Tus::new().max_size(1024)).await;
+        let (status, headers) = client.send(Method::OPTIONS, "/uploads", &[], "").await;
+
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(headers[TUS_VERSION_HEADER], TUS_VERSION);
+        assert_eq!(headers[TUS_EXTENSION], TUS_EXTENSIONS);
+        assert_eq!(headers[TUS_MAX_SIZE], "1024");
+    }
+
+    #[tokio::test]
+    async fn can_upload_in_chunks() {
+        let tus = Tus::new();
+        let client = Client::new(tus.clone()).await;
+        let location = client
+            .create(&[
+                ("upload-length", "10"),
+                ("upload-metadata", "filename cmVwb3J0LnR4dA=="),
+            ])
+            .await;
+        assert!(location.starts_with("/uploads/"));
+
+        let (status, headers) = client.patch(&location, "0", &[], "Loco").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(headers[UPLOAD_OFFSET], "4");
+        assert!(headers.contains_key(UPLOAD_EXPIRES));
+
+        // a client resuming the upload asks for the offset
+        let (status, headers) = client.send(Method::HEAD, &location, &[], "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[UPLOAD_OFFSET], "4");
+        assert_eq!(headers[UPLOAD_LENGTH], "10");
+        assert_eq!(headers[UPLOAD_METADATA], "filename cmVwb3J0LnR4dA==");
+        assert_eq!(headers[header::CACHE_CONTROL], "no-store");
+
+        let (status, _) = client.patch(&location, "0", &[], "Loco").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, headers) = client.patch(&location, "4", &[], " rocks").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(headers[UPLOAD_OFFSET], "10");
+
+        let upload = tus
+            .upload(&client.storage, id(&location))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(upload.is_complete());
+        let key = upload.key.unwrap();
+        assert_eq!(key, Path::new("uploads").join(format!("{}.txt", upload.id)));
+        assert_eq!(
+            client.storage.download::<String>(&key).await.unwrap(),
+            "Loco rocks"
+        );
+        assert!(client
+            .storage
+            .download::<String>(&tus.part_path(&upload.id, 0))
+            .await
+            .is_err());
+
+        let (status, _) = client.patch(&location, "10", &[], "!").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn can_defer_length() {
+        let tus = Tus::new();
+        let client = Client::new(tus.clone()).await;
+        let location = client.create(&[("upload-defer-length", "1")]).await;
+
+        let (status, headers) = client.send(Method::HEAD, &location, &[], "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[UPLOAD_DEFER_LENGTH], "1");
+
+        let (status, _) = client.patch(&location, "0", &[], "Loco").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = client
+            .patch(&location, "4", &[("upload-length", "5")], "!")
+            .await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let upload = tus
+            .upload(&client.storage, id(&location))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            client
+                .storage
+                .download::<String>(&upload.key.unwrap())
+                .await
+                .unwrap(),
+            "Loco!"
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_requests() {
+        let client = Client::new(Tus::new().max_size(8)).await;
+
+        let (status, headers) = client
+            .router
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/uploads")
+                    .header("upload-length", "4")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .map(|response| (response.status(), response.headers().clone()))
+            .unwrap();
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        assert_eq!(headers[TUS_VERSION_HEADER], TUS_VERSION);
+
+        let (status, _) = client
+            .send(Method::POST, "/uploads", &[("upload-length", "9")], "")
+            .await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let location = client.create(&[("upload-length", "4")]).await;
+        let (status, _) = client
+            .send(Method::PATCH, &location, &[("upload-offset", "0")], "Loco")
+            .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let (status, _) = client.patch(&location, "0", &[], "Loco!").await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        let (_, headers) = client.send(Method::HEAD, &location, &[], "").await;
+        assert_eq!(headers[UPLOAD_OFFSET], "0");
+
+        let (status, _) = client
+            .patch(
+                "/uploads/0123456789abcdef0123456789abcdef",
+                "0",
+                &[],
+                "Loco",
+            )
+            .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn can_terminate_upload() {
+        let client = Client::new(Tus::new()).await;
+        let location = client.create(&[("upload-length", "10")]).await;
+        client.patch(&location, "0", &[], "Loco").await;
+
+        let (status, _) = client.send(Method::DELETE, &location, &[], "").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, _) = client.send(Method::HEAD, &location, &[], "").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn can_finish_complete_upload() {
+        let tus = Tus::new();
+        let client = Client::new(tus.clone()).await;
+        let location = client.create(&[("upload-length", "4")]).await;
+
+        assert_eq!(
+            tus.finish(&client.storage, id(&location)).await.unwrap(),
+            None
+        );
+        client.patch(&location, "0", &[], "Loco").await;
+
+        let key = tus
+            .finish(&client.storage, id(&location))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            client.storage.download::<String>(&key).await.unwrap(),
+            "Loco"
+        );
+        assert!(tus
+            .upload(&client.storage, id(&location))
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn locks_uploads_across_instances() {
+        let ctx = crate::tests_cfg::app::get_app_context().await;
+        let id = "0123456789abcdef0123456789abcdef";
+        // two instances share the cache, but not their in-process locks
+        let (first, second) = (Tus::new(), Tus::new());
+
+        let lock = first.lock(&ctx.cache, id).await;
+        assert!(lock.is_some());
+        assert!(second.lock(&ctx.cache, id).await.is_none());
+        assert!(first.lock(&ctx.cache, id).await.is_none());
+
+        lock.unwrap().release().await;
+        assert!(second.lock(&ctx.cache, id).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn can_purge_expired_uploads() {
+        let mut ctx = crate::tests_cfg::app::get_app_context().await;
+        ctx.storage = Storage::single(drivers::mem::new()).into();
+        let tus = Tus::new();
+        let (expired, pending, orphan) = (
+            "0123456789abcdef0123456789abcdef",
+            "1123456789abcdef0123456789abcdef",
+            "2123456789abcdef0123456789abcdef",
+        );
+        let upload = |id: &str, expires_at| TusUpload {
+            id: id.to_string(),
+            length: Some(8),
+            offset: 4,
+            metadata: BTreeMap::new(),
+            key: None,
+            expires_at: Some(expires_at),
+            parts: vec![0],
+        };
+        for upload in [
+            upload(expired, Utc::now() - chrono::Duration::seconds(1)),
+            upload(pending, Utc::now() + chrono::Duration::hours(1)),
+        ] {
+            tus.save(&ctx.storage, &upload).await.unwrap();
+            ctx.storage
+                .upload(&tus.part_path(&upload.id, 0), &Bytes::from("Loco"))
+                .await
+                .unwrap();
+        }
+        // a part left by an upload whose state was deleted
+        ctx.storage
+            .upload(&tus.part_path(orphan, 0), &Bytes::from("Loco"))
+            .await
+            .unwrap();
+        // an expired upload completed but never claimed
+        let mut unclaimed = upload(
+            "3123456789abcdef0123456789abcdef",
+            Utc::now() - chrono::Duration::seconds(1),
+        );
+        let key = PathBuf::from("uploads/unclaimed");
+        unclaimed.key = Some(key.clone());
+        unclaimed.parts.clear();
+        tus.save(&ctx.storage, &unclaimed).await.unwrap();
+        ctx.storage
+            .upload(&key, &Bytes::from("Loco"))
+            .await
+            .unwrap();
+
+        assert_eq!(tus.purge_expired(&ctx).await.unwrap(), 2);
+        let remaining: Vec<PathBuf> = ctx
+            .storage
+            .list(Path::new(""), true)
+            .await
+            .unwrap()
+            .map_ok(|entry| entry.path)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(
+            remaining,
+            vec![tus.part_path(pending, 0), tus.state_path(pending)]
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_uploads_are_removed() {
+        let tus = Tus::new().expires_after(Some(Duration::ZERO));
+        let client = Client::new(tus.clone()).await;
+        let location = client.create(&[("upload-length", "10")]).await;
+
+        let (status, _) = client.patch(&location, "0", &[], "Loco").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert!(tus
+            .upload(&client.storage, id(&location))
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
@@ -34,6 +34,7 @@ pub fn test_config() -> Config {
             mode: config::WorkerMode::ForegroundBlocking,
         },
         mailer: None,
+        storage: None,
         initializers: None,
         settings: None,
         scheduler: Some(scheduler::Config {
@@ -46,6 +47,9 @@ pub fn test_config() -> Config {
                     cron: "*/5 * * * * *".to_string(),
                     tags: Some(vec!["base".to_string()]),
                     output: None,
+                    timezone: None,
+                    overlap: scheduler::Overlap::Allow,
+                    timeout: None,
                 },
             )]),
 
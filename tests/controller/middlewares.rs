@@ -81,6 +81,64 @@ async fn etag(#[case] enable: bool) {
     handle.abort();
 }
 
+#[rstest]
+#[case(true)]
+#[case(false)]
+#[tokio::test]
+async fn response_cache(#[case] enable: bool) {
+    async fn action() -> Result<Response> {
+        format::render().text(&uuid::Uuid::new_v4().to_string())
+    }
+
+    let mut ctx: AppContext = tests_cfg::app::get_app_context().await;
+
+    ctx.config.server.middlewares.response_cache = Some(middleware::response_cache::Config {
+        enable,
+        ttl: Some(60_000),
+        ..Default::default()
+    });
+
+    let port = get_available_port().await;
+    let handle = infra_cfg::server::start_with_route(ctx, "/", get(action), Some(port)).await;
+
+    let client = reqwest::Client::new();
+    let first = client
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("response");
+    let etag = first.headers().get("etag").cloned();
+    let first = first.text().await.expect("body");
+
+    let second = client
+        .get(get_base_url_port(port))
+        .send()
+        .await
+        .expect("response");
+    assert_eq!(
+        second.headers().get("x-cache").map(|v| v.to_str().unwrap()),
+        enable.then_some("HIT")
+    );
+    let second = second.text().await.expect("body");
+
+    if enable {
+        assert_eq!(first, second);
+
+        let revalidated = client
+            .get(get_base_url_port(port))
+            .header("if-none-match", etag.expect("etag"))
+            .send()
+            .await
+            .expect("response");
+        assert_eq!(revalidated.status(), StatusCode::NOT_MODIFIED);
+    } else {
+        assert_ne!(first, second);
+        assert!(etag.is_none());
+    }
+
+    handle.abort();
+}
+
 #[rstest]
 #[case(true, "remote: 51.50.51.50")]
 #[case(false, "--")]